@@ -0,0 +1,255 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+//! The async entry points and progress callbacks need a JS event loop and
+//! JS functions, and the errors exports throw are JS objects, so these only
+//! run under `wasm-pack test --node`.
+
+use wasm_bindgen::{JsCast, JsValue};
+use wasm_bindgen_futures::JsFuture;
+use wasm_bindgen_test::*;
+use web_sys::AbortSignal;
+
+use crate::panic_free_tests::error_code;
+use crate::wasm_structs::*;
+use crate::*;
+
+fn aborted_signal() -> AbortSignal {
+    js_sys::eval("AbortSignal.abort()")
+        .unwrap()
+        .unchecked_into()
+}
+
+fn new_transaction(key: &Key) -> WasmTransaction {
+    WasmTransaction::new(key.spending_key(), get_latest_transaction_version()).unwrap()
+}
+
+#[wasm_bindgen_test]
+async fn transaction_is_held_until_post_async_settles() {
+    let key = create_key_to_js();
+    let mut transaction = new_transaction(&key);
+    let note = WasmNote::new(
+        &key.public_address(),
+        10,
+        "memo",
+        &WasmAsset::native_id(),
+        &key.public_address(),
+    )
+    .unwrap();
+
+    let pending = transaction.post_async(None, 0, Some(aborted_signal()), None);
+    for held in [
+        transaction.output(&note).err(),
+        transaction.set_expiration_sequence(10).err(),
+        transaction.post_wasm(vec![], vec![], vec![], vec![]).err(),
+    ] {
+        assert_eq!(
+            error_code(held.unwrap()),
+            ErrorCode::OperationInFlight as u32
+        );
+    }
+    let second = JsFuture::from(transaction.build_circuits_async(None, 0, None, None)).await;
+    assert_eq!(
+        error_code(second.err().unwrap()),
+        ErrorCode::OperationInFlight as u32
+    );
+    // Reading doesn't need the transaction itself
+    assert_eq!(transaction.expiration_sequence().unwrap(), 0);
+
+    let aborted = JsFuture::from(pending).await;
+    assert_eq!(
+        error_code(aborted.err().unwrap()),
+        ErrorCode::Aborted as u32
+    );
+    assert!(transaction.output(&note).is_ok());
+    assert!(transaction.set_expiration_sequence(10).is_ok());
+}
+
+#[wasm_bindgen_test]
+async fn failed_post_wipes_transaction_with_change_added() {
+    let key = create_key_to_js();
+    let asset = WasmAsset::new(key.public_address(), "coin".into(), "".into()).unwrap();
+    let mint_and_output = |transaction: &mut WasmTransaction| {
+        let note = WasmNote::new(
+            &key.public_address(),
+            10,
+            "",
+            &asset.id(),
+            &key.public_address(),
+        )
+        .unwrap();
+        transaction.mint(&asset, 10, None).unwrap();
+        transaction.output(&note).unwrap();
+    };
+
+    // Aborted once proving has started, so after the circuits are built
+    let signal: AbortSignal =
+        js_sys::eval("(globalThis.postController = new AbortController()).signal")
+            .unwrap()
+            .unchecked_into();
+    let abort_on_prove = js_sys::Function::new_with_args(
+        "event",
+        "if (event.phase === 'prove') globalThis.postController.abort()",
+    );
+    let mut transaction = new_transaction(&key);
+    mint_and_output(&mut transaction);
+    let aborted = JsFuture::from(transaction.post_async(
+        None,
+        0,
+        Some(signal),
+        Some(abort_on_prove.unchecked_into()),
+    ))
+    .await;
+    assert_eq!(
+        error_code(aborted.err().unwrap()),
+        ErrorCode::Aborted as u32
+    );
+    assert_eq!(
+        error_code(transaction.post(None, 1).err().unwrap()),
+        ErrorCode::UseAfterWipe as u32
+    );
+
+    let throw_on_prove = js_sys::Function::new_with_args(
+        "event",
+        "if (event.phase === 'prove') throw new Error('cancelled')",
+    );
+    let mut transaction = new_transaction(&key);
+    mint_and_output(&mut transaction);
+    assert!(transaction
+        .post_with_progress(None, 0, throw_on_prove.unchecked_into())
+        .is_err());
+    assert_eq!(
+        error_code(transaction.post(None, 1).err().unwrap()),
+        ErrorCode::UseAfterWipe as u32
+    );
+}
+
+#[wasm_bindgen_test]
+async fn wipe_rejects_pending_async_calls() {
+    let key = create_key_to_js();
+    for post in [true, false] {
+        let mut transaction = new_transaction(&key);
+        let pending = if post {
+            transaction.post_async(None, 0, None, None)
+        } else {
+            transaction.build_circuits_async(None, 0, None, None)
+        };
+        transaction.wipe();
+
+        let wiped = JsFuture::from(pending).await;
+        assert_eq!(
+            error_code(wiped.err().unwrap()),
+            ErrorCode::UseAfterWipe as u32
+        );
+        assert_eq!(
+            error_code(transaction.set_expiration_sequence(10).err().unwrap()),
+            ErrorCode::UseAfterWipe as u32
+        );
+    }
+}
+
+#[wasm_bindgen_test]
+fn wiped_handles_refuse_every_call() {
+    let key = create_key_to_js();
+    let mut transaction = new_transaction(&key);
+    let note = WasmNote::new(
+        &key.public_address(),
+        10,
+        "",
+        &WasmAsset::native_id(),
+        &key.public_address(),
+    )
+    .unwrap();
+    transaction.wipe();
+    for wiped in [
+        transaction.output(&note).err(),
+        transaction.burn(&WasmAsset::native_id(), 1).err(),
+        transaction.post(None, 0).err(),
+        transaction.build_circuits(None, 0).err(),
+        transaction.post_miners_fee().err(),
+        transaction.estimated_size(None).err(),
+        transaction.spends().err(),
+        transaction.balance_report(0).err(),
+        transaction.set_expiration_sequence(10).err(),
+    ] {
+        assert_eq!(error_code(wiped.unwrap()), ErrorCode::UseAfterWipe as u32);
+    }
+
+    let mut spending_key = WasmSpendingKey::generate();
+    spending_key.wipe();
+    for wiped in [
+        spending_key.to_hex().err(),
+        spending_key.to_words(LanguageCode::English).err(),
+        spending_key.view_key().err(),
+        spending_key.public_address().err(),
+        WasmTransaction::from_spending_key(&spending_key, 1).err(),
+        ViewOnlyKey::from_spending_key(&spending_key).err(),
+    ] {
+        assert_eq!(error_code(wiped.unwrap()), ErrorCode::UseAfterWipe as u32);
+    }
+}
+
+#[wasm_bindgen_test]
+fn post_reports_build_and_each_proof() {
+    let key = create_key_to_js();
+    let mut transaction = new_transaction(&key);
+    let asset = WasmAsset::new(key.public_address(), "coin".into(), "".into()).unwrap();
+    let note = WasmNote::new(
+        &key.public_address(),
+        10,
+        "",
+        &asset.id(),
+        &key.public_address(),
+    )
+    .unwrap();
+    transaction.mint(&asset, 10, None).unwrap();
+    transaction.output(&note).unwrap();
+
+    let events = js_sys::Array::new();
+    let progress = js_sys::Function::new_with_args(
+        "event",
+        "globalThis.progressEvents.push(`${event.phase} ${event.index}/${event.total}`)",
+    );
+    js_sys::Reflect::set(&js_sys::global(), &"progressEvents".into(), &events).unwrap();
+    transaction
+        .post_with_progress(None, 0, progress.unchecked_into())
+        .unwrap();
+
+    let events: Vec<String> = events
+        .iter()
+        .map(|event| event.as_string().unwrap())
+        .collect();
+    assert_eq!(
+        events,
+        [
+            "buildSpends 0/0",
+            "buildSpends 0/0",
+            "buildOutputs 2/2",
+            "prove 0/2",
+            "prove 1/2",
+            "prove 2/2",
+            "sign 0/1",
+            "sign 1/1",
+        ]
+    );
+}
+
+#[wasm_bindgen_test]
+fn thrown_errors_are_recognized() {
+    let thrown = WasmNote::deserialize(&[]).err().unwrap();
+    assert!(is_ironfish_error(&thrown));
+    assert!(!is_ironfish_error(&js_sys::Error::new("other").into()));
+    assert!(!is_ironfish_error(&JsValue::from("SerializationError")));
+}
+
+#[wasm_bindgen_test]
+fn account_mnemonic_language_is_detected() {
+    let key = WasmSpendingKey::generate();
+    let words = key.to_words(LanguageCode::Spanish).unwrap();
+    let account = WasmAccount::decode(words, None, None, None, None, None).unwrap();
+    assert_eq!(
+        account.spending_key().unwrap().to_hex().unwrap(),
+        key.to_hex().unwrap()
+    );
+}
@@ -1,19 +1,32 @@
-use ironfish_rust::{keys::Language, sapling_bls12, PublicAddress, SaplingKey};
+use ironfish_rust::{keys::Language, sapling_bls12, SaplingKey, ViewKey};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use wasm_structs::WasmIronfishError;
+use wasm_structs::tasks::checkpoint;
+use wasm_structs::{
+    address::parse_public_address, derivation, mnemonic, signing, ErrorCode, WasmError,
+    WasmIronfishError, WasmSpendingKey,
+};
+use web_sys::AbortSignal;
+use zeroize::{Zeroize, Zeroizing};
 
 use wasm_bindgen::prelude::*;
 
+pub mod logger;
 pub mod panic_hook;
 pub mod wasm_structs;
 
+#[cfg(all(test, target_arch = "wasm32"))]
+mod async_tests;
+#[cfg(test)]
+mod panic_free_tests;
+
 #[cfg(feature = "wee_alloc")]
 #[global_allocator]
 static ALLOC: wee_alloc::WeeAlloc = wee_alloc::WeeAlloc::INIT;
 
 #[wasm_bindgen]
 pub fn send_array_to_js() -> Box<[JsValue]> {
+    let _entry = panic_hook::entry!();
     vec![
         JsValue::NULL,
         JsValue::UNDEFINED,
@@ -34,7 +47,8 @@ pub struct Obj {
 }
 
 #[wasm_bindgen]
-pub fn send_obj_to_js() -> JsValue {
+pub fn send_obj_to_js() -> Result<JsValue, JsValue> {
+    let _entry = panic_hook::entry!();
     let mut map = HashMap::new();
     map.insert(0, String::from("ex"));
 
@@ -46,10 +60,11 @@ pub fn send_obj_to_js() -> JsValue {
         field5: "哈哈哈".to_string(),
     };
 
-    serde_wasm_bindgen::to_value(&obj).unwrap()
+    Ok(serde_wasm_bindgen::to_value(&obj)?)
 }
 
 #[wasm_bindgen]
+#[derive(Clone, Copy)]
 pub enum LanguageCode {
     English,
     ChineseSimplified,
@@ -111,19 +126,63 @@ impl Key {
     pub fn public_address(&self) -> String {
         self.public_address.clone()
     }
+
+    /// Moves the spending key into an opaque handle and wipes it from this
+    /// object, so the hex string doesn't linger in memory.
+    #[wasm_bindgen(js_name = "toSpendingKey")]
+    pub fn to_spending_key(&mut self) -> Result<WasmSpendingKey, JsValue> {
+        let _entry = panic_hook::entry!();
+
+        let key = WasmSpendingKey::from_hex(self.spending_key_hex()?.to_string())?;
+        self.spending_key.zeroize();
+        Ok(key)
+    }
+
+    /// Overwrites every key held by this object with zeros. The getters
+    /// return empty strings afterwards.
+    #[wasm_bindgen]
+    pub fn wipe(&mut self) {
+        self.spending_key.zeroize();
+        self.view_key.zeroize();
+        self.incoming_view_key.zeroize();
+        self.outgoing_view_key.zeroize();
+        self.public_address.zeroize();
+    }
+}
+
+impl Key {
+    /// The spending key, unless `wipe` or `toSpendingKey` has cleared it.
+    pub(crate) fn spending_key_hex(&self) -> Result<&str, WasmError> {
+        if self.spending_key.is_empty() {
+            return Err(WasmError::new(
+                ErrorCode::UseAfterWipe,
+                "key has been wiped or moved to a spending key handle",
+            ));
+        }
+        Ok(&self.spending_key)
+    }
+
+    fn from_sapling_key(sapling_key: &SaplingKey) -> Key {
+        Key {
+            spending_key: sapling_key.hex_spending_key(),
+            view_key: sapling_key.view_key().hex_key(),
+            incoming_view_key: sapling_key.incoming_view_key().hex_key(),
+            outgoing_view_key: sapling_key.outgoing_view_key().hex_key(),
+            public_address: sapling_key.public_address().hex_public_address(),
+        }
+    }
+}
+
+impl Drop for Key {
+    fn drop(&mut self) {
+        self.wipe();
+    }
 }
 
 #[wasm_bindgen(js_name = "generateKey")]
 pub fn create_key_to_js() -> Key {
-    let sapling_key = SaplingKey::generate_key();
-
-    Key {
-        spending_key: sapling_key.hex_spending_key(),
-        view_key: sapling_key.view_key().hex_key(),
-        incoming_view_key: sapling_key.incoming_view_key().hex_key(),
-        outgoing_view_key: sapling_key.outgoing_view_key().hex_key(),
-        public_address: sapling_key.public_address().hex_public_address(),
-    }
+    let _entry = panic_hook::entry!();
+    Key::from_sapling_key(&SaplingKey::generate_key())
 }
 
 #[wasm_bindgen(js_name = "spending_key_to_words")]
@@ -131,7 +190,10 @@ pub fn spending_key_to_words(
     private_key: &str,
     language_code: LanguageCode,
 ) -> Result<String, JsValue> {
-    let key = SaplingKey::from_hex(private_key).map_err(WasmIronfishError)?;
+    let _entry = panic_hook::entry!();
+    let key = SaplingKey::from_hex(private_key).map_err(|e| {
+        WasmError::new(ErrorCode::InvalidSpendingKey, e).with_argument("private_key")
+    })?;
     let mnemonic = key
         .to_words(language_code.into())
         .map_err(WasmIronfishError)?;
@@ -143,31 +205,141 @@ pub fn words_to_spending_key(
     words: String,
     language_code: LanguageCode,
 ) -> Result<String, JsValue> {
-    let key = SaplingKey::from_words(words, language_code.into()).map_err(WasmIronfishError)?;
-    Ok(key.hex_spending_key())
+    let _entry = panic_hook::entry!();
+    let words = Zeroizing::new(words);
+    let key = WasmSpendingKey::from_phrase(&words, language_code)?;
+    key.to_hex()
+}
+
+/// Restores the full `Key` for a spending key mnemonic, detecting the
+/// language when it isn't given. Errors identify the first unknown word by
+/// `index` with dictionary `suggestions`, a wrong word count, or a checksum
+/// mismatch, each with its own code.
+#[wasm_bindgen(js_name = "words_to_key")]
+pub fn words_to_key(words: String, language_code: Option<LanguageCode>) -> Result<Key, JsValue> {
+    let _entry = panic_hook::entry!();
+    let words = Zeroizing::new(words);
+    let spending_key = derivation::master_key(&words, language_code)?;
+    let key = SaplingKey::new(*spending_key)
+        .map_err(|e| WasmError::new(ErrorCode::InvalidSpendingKey, e).with_argument("words"))?;
+    Ok(Key::from_sapling_key(&key))
+}
+
+/// Derives account `index` from a mnemonic, so several accounts can be
+/// restored from one backup. Account 0 is the mnemonic's own key; see the
+/// `derivation` module for the scheme. The phrase is validated as in
+/// `words_to_key`.
+#[wasm_bindgen(js_name = "derive_account")]
+pub fn derive_account(
+    mut words: String,
+    index: u32,
+    language_code: Option<LanguageCode>,
+) -> Result<Key, JsValue> {
+    let _entry = panic_hook::entry!();
+    let master = derivation::master_key(&words, language_code);
+    words.zeroize();
+    let spending_key = derivation::derive_spending_key(&*master?, index);
+    let key = SaplingKey::new(*spending_key)
+        .map_err(|e| WasmError::new(ErrorCode::InvalidSpendingKey, e).with_argument("index"))?;
+    Ok(Key::from_sapling_key(&key))
+}
+
+/// The wordlist most of the phrase's words belong to, or undefined if none.
+#[wasm_bindgen(js_name = "detect_mnemonic_language")]
+pub fn detect_mnemonic_language(words: &str) -> Option<LanguageCode> {
+    let _entry = panic_hook::entry!();
+    mnemonic::detect_language(words)
 }
 
 #[wasm_bindgen(js_name = "generatePublicAddress")]
 pub fn create_public_key_to_js(private_key: &str) -> Result<Key, JsValue> {
-    let sapling_key = SaplingKey::from_hex(private_key).map_err(WasmIronfishError)?;
-
-    Ok(Key {
-        spending_key: sapling_key.hex_spending_key(),
-        view_key: sapling_key.view_key().hex_key(),
-        incoming_view_key: sapling_key.incoming_view_key().hex_key(),
-        outgoing_view_key: sapling_key.outgoing_view_key().hex_key(),
-        public_address: sapling_key.public_address().hex_public_address(),
-    })
+    let _entry = panic_hook::entry!();
+    let sapling_key = SaplingKey::from_hex(private_key).map_err(|e| {
+        WasmError::new(ErrorCode::InvalidSpendingKey, e).with_argument("private_key")
+    })?;
+
+    Ok(Key::from_sapling_key(&sapling_key))
+}
+
+/// Signs `message` to prove control of the key's public address, e.g. for
+/// signing in to a dApp. See `verify_message`.
+#[wasm_bindgen(js_name = "sign_message")]
+pub fn sign_message(mut private_key: String, message: &[u8]) -> Result<Vec<u8>, JsValue> {
+    let _entry = panic_hook::entry!();
+    let sapling_key = SaplingKey::from_hex(&private_key);
+    private_key.zeroize();
+    let sapling_key = sapling_key.map_err(|e| {
+        WasmError::new(ErrorCode::InvalidSpendingKey, e).with_argument("private_key")
+    })?;
+    Ok(signing::sign(&sapling_key, message)?.to_vec())
+}
+
+/// The key a valid `verify_message` signature proves its signer holds.
+/// Neither value is 0, so the result can be tested for truthiness.
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SignerKey {
+    /// Checked against a public address: the signer holds its incoming
+    /// view key. View-only wallets and auditors hold that key too, so this
+    /// does not show the signer can spend from the address.
+    IncomingViewKey = 1,
+    /// Checked against a view key: the signer holds the spending key.
+    SpendingKey = 2,
+}
+
+/// Checks a `sign_message` signature, returning which key it proves the
+/// signer holds, or undefined when it doesn't verify. Pass the signer's
+/// view key to prove control of the spending key; a public address only
+/// proves the incoming view key.
+#[wasm_bindgen(js_name = "verify_message")]
+pub fn verify_message(
+    public_address_or_view_key: &str,
+    message: &[u8],
+    signature: &[u8],
+) -> Result<Option<SignerKey>, JsValue> {
+    let _entry = panic_hook::entry!();
+    let argument = "public_address_or_view_key";
+    match public_address_or_view_key.len() {
+        128 => {
+            let view_key = ViewKey::from_hex(public_address_or_view_key).map_err(|e| {
+                WasmError::new(ErrorCode::InvalidViewKey, e).with_argument(argument)
+            })?;
+            let verified = signing::verify_with_view_key(&view_key, message, signature)?;
+            Ok(verified.then_some(SignerKey::SpendingKey))
+        }
+        _ => {
+            let address = parse_public_address(public_address_or_view_key, argument)?;
+            let verified = signing::verify_with_address(&address, message, signature)?;
+            Ok(verified.then_some(SignerKey::IncomingViewKey))
+        }
+    }
 }
 
 #[wasm_bindgen(js_name = "initialize_sapling")]
 pub fn initialize_sapling() {
+    let _entry = panic_hook::entry!();
     let _ = sapling_bls12::SAPLING.clone();
 }
 
+/// Async counterpart of `initialize_sapling`. Loading the parameters is a
+/// single blocking step, but this yields once first so callers can show
+/// their loading state, and honours an already aborted signal.
+#[wasm_bindgen(js_name = "initialize_sapling_async")]
+pub async fn initialize_sapling_async(signal: Option<AbortSignal>) -> Result<(), JsValue> {
+    panic_hook::instrument(panic_hook::function_name!(), async move {
+        checkpoint(&signal).await?;
+        initialize_sapling();
+        Ok(())
+    })
+    .await
+}
+
+/// Accepts raw hex and bech32m encoded addresses. A mistyped bech32m address
+/// fails its checksum rather than decoding to a different address.
 #[wasm_bindgen(js_name = "is_valid_public_address")]
 pub fn is_valid_public_address(hex_address: String) -> bool {
-    PublicAddress::from_hex(&hex_address).is_ok()
+    let _entry = panic_hook::entry!();
+    parse_public_address(&hex_address, "hex_address").is_ok()
 }
 
 #[cfg(test)]
@@ -186,4 +358,43 @@ mod tests {
 
         assert_eq!(key1.public_address(), key2.public_address());
     }
+
+    #[test]
+    fn test_verify_message_names_the_proven_key() {
+        let key = create_key_to_js();
+        let message = b"sign in to example.com";
+        let signature = sign_message(key.spending_key(), message).unwrap();
+
+        assert_eq!(
+            verify_message(&key.public_address(), message, &signature).unwrap(),
+            Some(SignerKey::IncomingViewKey)
+        );
+        assert_eq!(
+            verify_message(&key.view_key(), message, &signature).unwrap(),
+            Some(SignerKey::SpendingKey)
+        );
+        assert_eq!(
+            verify_message(&key.view_key(), b"sign in to evil.com", &signature).unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn test_wiped_key_is_refused() {
+        let mut key = Key {
+            spending_key: "00".repeat(32),
+            view_key: "".to_string(),
+            incoming_view_key: "".to_string(),
+            outgoing_view_key: "".to_string(),
+            public_address: "".to_string(),
+        };
+        assert!(key.spending_key_hex().is_ok());
+
+        key.wipe();
+        assert_eq!(key.spending_key(), "");
+        assert_eq!(
+            key.spending_key_hex().err().unwrap().code,
+            ErrorCode::UseAfterWipe
+        );
+    }
 }
@@ -0,0 +1,197 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+use serde::Serialize;
+use std::cell::{Cell, RefCell};
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+
+use crate::panic_hook;
+use crate::wasm_structs::{ErrorCode, WasmError};
+
+#[wasm_bindgen(typescript_custom_section)]
+const ILOG: &'static str = r#"
+type LogLevelName = 'trace' | 'debug' | 'info' | 'warn' | 'error';
+
+interface LogRecord {
+    level: LogLevelName;
+    target: string;
+    message: string;
+    elapsedMs?: number;
+}
+
+type LogCallback = (record: LogRecord) => void;
+
+type LogSink = 'console' | LogCallback | null | undefined;
+"#;
+
+#[wasm_bindgen]
+/// Where log records go. The LogRecord TypeScript interface above must be
+/// manually updated if changing LogRecord.
+extern "C" {
+    #[wasm_bindgen(typescript_type = "LogSink")]
+    pub type LogSink;
+}
+
+/// Verbosity threshold. Records below the configured level are dropped
+/// before their message is formatted.
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LogLevel {
+    Trace = 0,
+    Debug = 1,
+    Info = 2,
+    Warn = 3,
+    Error = 4,
+    Off = 5,
+}
+
+impl LogLevel {
+    fn name(&self) -> &'static str {
+        match self {
+            LogLevel::Trace => "trace",
+            LogLevel::Debug => "debug",
+            LogLevel::Info => "info",
+            LogLevel::Warn => "warn",
+            LogLevel::Error => "error",
+            LogLevel::Off => "off",
+        }
+    }
+}
+
+#[derive(Clone)]
+enum Sink {
+    Nothing,
+    Console,
+    Callback(js_sys::Function),
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct LogRecord<'a> {
+    level: &'static str,
+    target: &'a str,
+    message: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    elapsed_ms: Option<f64>,
+}
+
+// Nothing is logged until the host opts in with `setLogSink`, so production
+// bundles stay quiet without any configuration.
+thread_local! {
+    static LEVEL: Cell<LogLevel> = const { Cell::new(LogLevel::Info) };
+    static SINK: RefCell<Sink> = const { RefCell::new(Sink::Nothing) };
+}
+
+#[wasm_bindgen(js_name = "setLogLevel")]
+pub fn set_log_level(level: LogLevel) {
+    LEVEL.with(|current| current.set(level));
+}
+
+/// Routes log records to the console, to a callback, or nowhere when given
+/// null or undefined.
+#[wasm_bindgen(js_name = "setLogSink")]
+pub fn set_log_sink(sink: LogSink) -> Result<(), JsValue> {
+    let _entry = panic_hook::entry!();
+    let sink: JsValue = sink.into();
+    let sink = if sink.is_null() || sink.is_undefined() {
+        Sink::Nothing
+    } else if sink.as_string().as_deref() == Some("console") {
+        Sink::Console
+    } else if let Some(callback) = sink.dyn_ref::<js_sys::Function>() {
+        Sink::Callback(callback.clone())
+    } else {
+        return Err(WasmError::new(
+            ErrorCode::InvalidArgument,
+            "expected 'console', a function, or null",
+        )
+        .with_argument("sink")
+        .into());
+    };
+    SINK.with(|current| *current.borrow_mut() = sink);
+    Ok(())
+}
+
+/// Whether a record at `level` would be written anywhere.
+pub(crate) fn enabled(level: LogLevel) -> bool {
+    level != LogLevel::Off
+        && level >= LEVEL.with(Cell::get)
+        && SINK.with(|sink| !matches!(*sink.borrow(), Sink::Nothing))
+}
+
+pub(crate) fn write(level: LogLevel, target: &str, message: &str, elapsed_ms: Option<f64>) {
+    // Cloned so SINK isn't borrowed while a callback runs, as the callback
+    // may log again or call setLogSink
+    let sink = SINK.with(|sink| sink.borrow().clone());
+    match sink {
+        Sink::Nothing => {}
+        Sink::Console => {
+            let line = match elapsed_ms {
+                Some(elapsed_ms) => format!("[{}] {} ({:.1}ms)", target, message, elapsed_ms),
+                None => format!("[{}] {}", target, message),
+            };
+            let line = JsValue::from_str(&line);
+            match level {
+                LogLevel::Trace | LogLevel::Debug => web_sys::console::debug_1(&line),
+                LogLevel::Info => web_sys::console::info_1(&line),
+                LogLevel::Warn => web_sys::console::warn_1(&line),
+                LogLevel::Error | LogLevel::Off => web_sys::console::error_1(&line),
+            }
+        }
+        Sink::Callback(callback) => {
+            let record = LogRecord {
+                level: level.name(),
+                target,
+                message,
+                elapsed_ms,
+            };
+            // A failing log callback must never break the operation being
+            // logged, so its errors are dropped.
+            if let Ok(record) = serde_wasm_bindgen::to_value(&record) {
+                let _ = callback.call1(&JsValue::NULL, &record);
+            }
+        }
+    }
+}
+
+/// Logs a formatted message at the given level, formatting it only when the
+/// record would actually be written.
+macro_rules! log {
+    ($level:expr, $target:expr, $($arg:tt)+) => {
+        if $crate::logger::enabled($level) {
+            $crate::logger::write($level, $target, &format!($($arg)+), None);
+        }
+    };
+}
+
+pub(crate) use log;
+
+/// Times a unit of work. Emits a debug record with the elapsed time when
+/// dropped, so early returns through `?` are timed as well.
+pub(crate) struct Span {
+    name: &'static str,
+    start: Option<f64>,
+}
+
+/// Starts a span. The clock is only read when debug records are enabled.
+pub(crate) fn span(name: &'static str) -> Span {
+    let start = if enabled(LogLevel::Debug) {
+        log!(LogLevel::Trace, name, "start");
+        Some(js_sys::Date::now())
+    } else {
+        None
+    };
+    Span { name, start }
+}
+
+impl Drop for Span {
+    fn drop(&mut self) {
+        if let Some(start) = self.start {
+            if enabled(LogLevel::Debug) {
+                let elapsed_ms = js_sys::Date::now() - start;
+                write(LogLevel::Debug, self.name, "done", Some(elapsed_ms));
+            }
+        }
+    }
+}
@@ -0,0 +1,457 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+//! The exports themselves, which throw JS errors.
+
+use wasm_bindgen::JsCast;
+use wasm_bindgen::JsValue;
+use wasm_bindgen_futures::JsFuture;
+use wasm_bindgen_test::*;
+use web_sys::AbortSignal;
+
+use super::{assert_thrown, bad_bytes, bad_strings};
+use crate::wasm_structs::*;
+use crate::*;
+
+fn native_asset_id() -> Vec<u8> {
+    WasmAsset::native_id()
+}
+
+fn note_for(key: &Key) -> WasmNote {
+    WasmNote::new(
+        &key.public_address(),
+        10,
+        "memo",
+        &native_asset_id(),
+        &key.public_address(),
+    )
+    .unwrap()
+}
+
+#[wasm_bindgen_test]
+fn keys_reject_bad_input() {
+    for input in bad_strings() {
+        assert_thrown(
+            spending_key_to_words(&input, LanguageCode::English),
+            ErrorCode::InvalidSpendingKey,
+            Some("private_key"),
+        );
+        assert_thrown(
+            words_to_spending_key(input.clone(), LanguageCode::English),
+            ErrorCode::InvalidMnemonic,
+            Some("words"),
+        );
+        assert_thrown(
+            create_public_key_to_js(&input),
+            ErrorCode::InvalidSpendingKey,
+            Some("private_key"),
+        );
+        assert!(!is_valid_public_address(input));
+    }
+}
+
+#[wasm_bindgen_test]
+fn note_rejects_bad_input() {
+    let key = create_key_to_js();
+    let address = key.public_address();
+    let asset_id = native_asset_id();
+
+    for input in bad_strings() {
+        assert_thrown(
+            WasmNote::new(&input, 1, "", &asset_id, &address),
+            ErrorCode::InvalidPublicAddress,
+            Some("owner"),
+        );
+        assert_thrown(
+            WasmNote::new(&address, 1, "", &asset_id, &input),
+            ErrorCode::InvalidPublicAddress,
+            Some("sender"),
+        );
+        assert_thrown(
+            note_for(&key).nullifier(&input, 0),
+            ErrorCode::InvalidViewKey,
+            Some("owner_view_key"),
+        );
+    }
+    for input in bad_bytes() {
+        assert_thrown(
+            WasmNote::new(&address, 1, "", &input, &address),
+            ErrorCode::InvalidAssetId,
+            Some("asset_id"),
+        );
+        assert_thrown(
+            WasmNote::deserialize(&input),
+            ErrorCode::MalformedNote,
+            Some("bytes"),
+        );
+    }
+
+    let long_memo = "m".repeat(4096);
+    assert_thrown(
+        WasmNote::new(&address, u64::MAX, &long_memo, &asset_id, &address),
+        ErrorCode::InvalidMemo,
+        Some("memo"),
+    );
+}
+
+#[wasm_bindgen_test]
+fn encrypted_note_rejects_bad_input() {
+    let hash = vec![0; 32];
+    for input in bad_bytes() {
+        assert_thrown(
+            WasmNoteEncrypted::new(&input),
+            ErrorCode::MalformedNote,
+            Some("bytes"),
+        );
+        assert_thrown(
+            WasmNoteEncrypted::combine_hash(0, &input, &hash),
+            ErrorCode::InvalidArgument,
+            Some("left"),
+        );
+        assert_thrown(
+            WasmNoteEncrypted::combine_hash(0, &hash, &input),
+            ErrorCode::InvalidArgument,
+            Some("right"),
+        );
+    }
+    assert_thrown(
+        WasmNoteEncrypted::combine_hash(usize::MAX, &hash, &hash),
+        ErrorCode::IndexOutOfBounds,
+        Some("depth"),
+    );
+
+    let notes = js_sys::Array::new();
+    for input in bad_bytes() {
+        notes.push(&js_sys::Uint8Array::from(&input[..]));
+    }
+    for input in bad_strings() {
+        assert_thrown(
+            decrypt_notes_for_owner(notes.clone(), &input, None),
+            ErrorCode::InvalidViewKey,
+            Some("owner_hex_key"),
+        );
+    }
+    let key = create_key_to_js();
+    assert_thrown(
+        decrypt_notes_for_owner(notes, &key.incoming_view_key(), None),
+        ErrorCode::MalformedNote,
+        Some("serialized_notes"),
+    );
+}
+
+#[wasm_bindgen_test]
+fn asset_rejects_bad_input() {
+    let key = create_key_to_js();
+    for input in bad_strings() {
+        assert_thrown(
+            WasmAsset::new(input.clone(), "name".into(), "".into()),
+            ErrorCode::InvalidPublicAddress,
+            Some("creator_public_address"),
+        );
+    }
+    assert_thrown(
+        WasmAsset::new(key.public_address(), "n".repeat(4096), "".into()),
+        ErrorCode::InvalidArgument,
+        Some("name"),
+    );
+    assert_thrown(
+        WasmAsset::new(key.public_address(), "name".into(), "m".repeat(4096)),
+        ErrorCode::InvalidArgument,
+        Some("metadata"),
+    );
+    for input in bad_bytes() {
+        assert_thrown(
+            WasmAsset::deserialize(&input),
+            ErrorCode::MalformedAsset,
+            Some("bytes"),
+        );
+    }
+}
+
+#[wasm_bindgen_test]
+fn proofs_and_posted_transactions_reject_bad_input() {
+    for input in bad_bytes() {
+        assert_thrown(
+            WasmProof::from_array(&input),
+            ErrorCode::MalformedProof,
+            Some("v"),
+        );
+        assert_thrown(
+            WasmTransactionPosted::new(&input),
+            ErrorCode::MalformedTransaction,
+            Some("bytes"),
+        );
+    }
+}
+
+#[wasm_bindgen_test]
+fn transaction_rejects_bad_input() {
+    let key = create_key_to_js();
+    let version = get_latest_transaction_version();
+
+    for input in bad_strings() {
+        assert_thrown(
+            WasmTransaction::new(input.clone(), version),
+            ErrorCode::InvalidSpendingKey,
+            Some("spender_hex_key"),
+        );
+    }
+    assert_thrown(
+        WasmTransaction::new(key.spending_key(), u8::MAX),
+        ErrorCode::InvalidTransactionVersion,
+        Some("version"),
+    );
+
+    let mut transaction = WasmTransaction::new(key.spending_key(), version).unwrap();
+    for input in bad_bytes() {
+        assert_thrown(
+            transaction.burn(&input, 1),
+            ErrorCode::InvalidAssetId,
+            Some("asset_id_bytes"),
+        );
+    }
+
+    let asset = WasmAsset::new(key.public_address(), "name".into(), "".into()).unwrap();
+    for input in bad_strings() {
+        assert_thrown(
+            transaction.mint(&asset, 1, Some(input.clone())),
+            ErrorCode::InvalidPublicAddress,
+            Some("transfer_ownership_to"),
+        );
+        assert_thrown(
+            transaction.post(Some(input.clone()), 0),
+            ErrorCode::InvalidPublicAddress,
+            Some("change_goes_to"),
+        );
+        assert_thrown(
+            transaction.build_circuits(Some(input), 0),
+            ErrorCode::InvalidPublicAddress,
+            Some("change_goes_to"),
+        );
+    }
+
+    for input in bad_bytes() {
+        let key_pair =
+            WasmEphemeralKeyPair::from_array(js_sys::Uint8Array::from(&input[..]).into());
+        assert_thrown(
+            transaction.post_wasm(vec![], vec![], vec![key_pair], vec![]),
+            ErrorCode::InvalidArgument,
+            Some("output_diffie_hellman_keys"),
+        );
+    }
+}
+
+#[wasm_bindgen_test]
+fn posted_transaction_rejects_out_of_bounds_index() {
+    let key = create_key_to_js();
+    let mut transaction =
+        WasmTransaction::new(key.spending_key(), get_latest_transaction_version()).unwrap();
+    transaction.output(&note_for(&key)).unwrap();
+    let posted = transaction.post_miners_fee().unwrap();
+
+    assert!(posted.get_note(0).is_ok());
+    for index in [posted.notes_length(), usize::MAX] {
+        assert_thrown(
+            posted.get_note(index),
+            ErrorCode::IndexOutOfBounds,
+            Some("index"),
+        );
+    }
+    for index in [0, usize::MAX] {
+        assert_thrown(
+            posted.get_spend(index),
+            ErrorCode::IndexOutOfBounds,
+            Some("index"),
+        );
+    }
+}
+
+fn aborted_signal() -> AbortSignal {
+    js_sys::eval("AbortSignal.abort()")
+        .unwrap()
+        .unchecked_into()
+}
+
+/// A witness for `note` as the only leaf of a tree whose other nodes are
+/// all zero, along with the root it hashes to.
+fn witness_for(note: &WasmNote) -> (JsWitness, Vec<u8>) {
+    let sibling = vec![0; 32];
+    let mut root = note.hash();
+    for depth in 0..32 {
+        root = WasmNoteEncrypted::combine_hash(depth, &root, &sibling).unwrap();
+    }
+    let make = js_sys::Function::new_with_args(
+        "root",
+        "const node = { side: () => 'Left', hashOfSibling: () => new Uint8Array(32) };
+         return {
+             verify: () => true,
+             authPath: () => Array(32).fill(node),
+             treeSize: () => 1,
+             serializeRootHash: () => root,
+         };",
+    );
+    let witness = make
+        .call1(&JsValue::NULL, &js_sys::Uint8Array::from(&root[..]))
+        .unwrap();
+    (witness.unchecked_into(), root)
+}
+
+#[wasm_bindgen_test]
+async fn async_entry_points_reject_bad_input() {
+    let aborted = initialize_sapling_async(Some(aborted_signal())).await;
+    assert_thrown(aborted, ErrorCode::Aborted, None);
+
+    let key = create_key_to_js();
+    let notes = js_sys::Array::new();
+    for input in bad_bytes() {
+        notes.push(&js_sys::Uint8Array::from(&input[..]));
+    }
+    for input in bad_strings() {
+        let decrypted = decrypt_notes_for_owner_async(notes.clone(), input, None, None).await;
+        assert_thrown(decrypted, ErrorCode::InvalidViewKey, Some("owner_hex_key"));
+    }
+    let malformed =
+        decrypt_notes_for_owner_async(notes.clone(), key.incoming_view_key(), None, None).await;
+    assert_thrown(
+        malformed,
+        ErrorCode::MalformedNote,
+        Some("serialized_notes"),
+    );
+
+    let view_only = ViewOnlyKey::from_incoming_view_key(&key.incoming_view_key(), None).unwrap();
+    let malformed =
+        JsFuture::from(view_only.decrypt_notes_for_owner_async(notes, None, None)).await;
+    assert_thrown(
+        malformed,
+        ErrorCode::MalformedNote,
+        Some("serialized_notes"),
+    );
+
+    let transaction =
+        WasmTransaction::new(key.spending_key(), get_latest_transaction_version()).unwrap();
+    for input in bad_strings() {
+        let posted =
+            JsFuture::from(transaction.post_async(Some(input.clone()), 0, None, None)).await;
+        assert_thrown(
+            posted,
+            ErrorCode::InvalidPublicAddress,
+            Some("change_goes_to"),
+        );
+        let built =
+            JsFuture::from(transaction.build_circuits_async(Some(input), 0, None, None)).await;
+        assert_thrown(
+            built,
+            ErrorCode::InvalidPublicAddress,
+            Some("change_goes_to"),
+        );
+    }
+    for input in bad_bytes() {
+        let key_pair =
+            WasmEphemeralKeyPair::from_array(js_sys::Uint8Array::from(&input[..]).into());
+        let posted = JsFuture::from(transaction.post_wasm_async(
+            vec![],
+            vec![],
+            vec![key_pair],
+            vec![],
+            None,
+        ))
+        .await;
+        assert_thrown(
+            posted,
+            ErrorCode::InvalidArgument,
+            Some("output_diffie_hellman_keys"),
+        );
+    }
+}
+
+#[wasm_bindgen_test]
+fn view_only_keys_reject_bad_input() {
+    let key = create_key_to_js();
+    for input in bad_strings() {
+        assert_thrown(
+            ViewOnlyKey::from_view_key(&input, None),
+            ErrorCode::InvalidViewKey,
+            Some("view_key"),
+        );
+        assert_thrown(
+            ViewOnlyKey::from_incoming_view_key(&input, None),
+            ErrorCode::InvalidViewKey,
+            Some("incoming_view_key"),
+        );
+        assert_thrown(
+            ViewOnlyKey::from_view_key(&key.view_key(), Some(input.clone())),
+            ErrorCode::InvalidViewKey,
+            Some("outgoing_view_key"),
+        );
+        assert_thrown(
+            ViewOnlyKey::from_incoming_view_key(&key.incoming_view_key(), Some(input)),
+            ErrorCode::InvalidViewKey,
+            Some("outgoing_view_key"),
+        );
+    }
+
+    // Keys missing the part an export needs throw rather than panic
+    let incoming_only =
+        ViewOnlyKey::from_incoming_view_key(&key.incoming_view_key(), None).unwrap();
+    let note = note_for(&key);
+    assert_thrown(
+        incoming_only.nullifier(&note, 0),
+        ErrorCode::InvalidViewKey,
+        None,
+    );
+    let mut transaction =
+        WasmTransaction::new(key.spending_key(), get_latest_transaction_version()).unwrap();
+    transaction.output(&note).unwrap();
+    let posted = transaction.post_miners_fee().unwrap();
+    let encrypted = WasmNoteEncrypted::new(&posted.get_note(0).unwrap()).unwrap();
+    assert_thrown(
+        incoming_only.decrypt_note_for_spender(&encrypted),
+        ErrorCode::InvalidViewKey,
+        None,
+    );
+    assert!(incoming_only.decrypt_note_for_owner(&encrypted).is_some());
+}
+
+#[wasm_bindgen_test]
+fn spend_descriptions_match_their_spend() {
+    let key = create_key_to_js();
+    let note = note_for(&key);
+    let (witness, root) = witness_for(&note);
+
+    let mut transaction =
+        WasmTransaction::new(key.spending_key(), get_latest_transaction_version()).unwrap();
+    transaction.spend(&note, witness).unwrap();
+    let posted = transaction.post(None, 0).unwrap();
+
+    let spend = posted.get_spend(0).unwrap();
+    assert_eq!(spend.tree_size(), 1);
+    assert_eq!(spend.root_hash().unwrap(), root);
+    assert_eq!(
+        spend.nullifier(),
+        note.nullifier(&key.view_key(), 0).unwrap()
+    );
+}
+
+#[wasm_bindgen_test]
+fn log_callback_can_replace_the_sink() {
+    use crate::logger::{log, set_log_level, set_log_sink, LogLevel};
+    use std::cell::Cell;
+    use std::rc::Rc;
+    use wasm_bindgen::closure::Closure;
+
+    let calls = Rc::new(Cell::new(0));
+    let counted = calls.clone();
+    let callback = Closure::<dyn FnMut(JsValue)>::new(move |_record: JsValue| {
+        counted.set(counted.get() + 1);
+        // Neither must find the sink still borrowed by the call to this
+        set_log_sink(JsValue::NULL.unchecked_into()).unwrap();
+        log!(LogLevel::Info, "test", "from the callback");
+    });
+    set_log_level(LogLevel::Info);
+    set_log_sink(callback.as_ref().clone().unchecked_into()).unwrap();
+
+    log!(LogLevel::Info, "test", "first");
+    log!(LogLevel::Info, "test", "second");
+    assert_eq!(calls.get(), 1);
+}
@@ -0,0 +1,72 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+//! Feeds entry points empty, short and oversized inputs and checks that each
+//! one is rejected with an error instead of panicking, since a panic aborts
+//! the wasm instance. The parsers behind the exports return `WasmError`s, so
+//! `parsers` runs on the host under `cargo test`. The exports throw JS
+//! errors, so `exports` only runs in a JS engine, under
+//! `wasm-pack test --node`.
+
+use crate::wasm_structs::{ErrorCode, WasmError};
+
+mod parsers;
+
+#[cfg(target_arch = "wasm32")]
+mod exports;
+
+fn bad_bytes() -> Vec<Vec<u8>> {
+    vec![
+        vec![],
+        vec![1],
+        vec![0xff; 31],
+        vec![0xff; 33],
+        vec![0xff; 4096],
+    ]
+}
+
+fn bad_strings() -> Vec<String> {
+    vec![
+        String::new(),
+        "a".to_string(),
+        "zz".repeat(32),
+        "ab".repeat(4096),
+    ]
+}
+
+/// Checks that `result` failed with `code`, naming `argument`.
+#[track_caller]
+fn assert_rejected<T>(result: Result<T, WasmError>, code: ErrorCode, argument: Option<&str>) {
+    let Err(error) = result else {
+        panic!("expected {code:?}, got Ok");
+    };
+    assert_eq!(error.code, code, "{}", error.message);
+    assert_eq!(error.argument.as_deref(), argument, "{}", error.message);
+}
+
+#[cfg(target_arch = "wasm32")]
+pub(crate) fn error_code(error: wasm_bindgen::JsValue) -> u32 {
+    js_sys::Reflect::get(&error, &"code".into())
+        .ok()
+        .and_then(|code| code.as_f64())
+        .expect("thrown value should carry a code") as u32
+}
+
+/// Checks that `result` threw an error with `code`, naming `argument`.
+#[cfg(target_arch = "wasm32")]
+#[track_caller]
+fn assert_thrown<T>(
+    result: Result<T, wasm_bindgen::JsValue>,
+    code: ErrorCode,
+    argument: Option<&str>,
+) {
+    let Err(error) = result else {
+        panic!("expected {code:?}, got Ok");
+    };
+    let thrown = js_sys::Reflect::get(&error, &"argument".into())
+        .ok()
+        .and_then(|argument| argument.as_string());
+    assert_eq!(error_code(error), code as u32);
+    assert_eq!(thrown.as_deref(), argument);
+}
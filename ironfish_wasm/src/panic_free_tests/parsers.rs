@@ -0,0 +1,113 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+//! The parsers behind the exports, which return `WasmError`s.
+
+use super::{assert_rejected, bad_bytes, bad_strings};
+use crate::wasm_structs::address::{decode_address_bytes, parse_public_address};
+use crate::wasm_structs::keystore::open;
+use crate::wasm_structs::mnemonic::parse_phrase;
+use crate::wasm_structs::shamir::{combine, Share};
+use crate::wasm_structs::*;
+
+#[test]
+fn addresses_and_ids_reject_bad_input() {
+    for input in bad_strings() {
+        assert_rejected(
+            decode_address_bytes(&input, "address"),
+            ErrorCode::InvalidPublicAddress,
+            Some("address"),
+        );
+        assert_rejected(
+            parse_public_address(&input, "address"),
+            ErrorCode::InvalidPublicAddress,
+            Some("address"),
+        );
+    }
+    for input in bad_bytes() {
+        assert_rejected(
+            read_asset_id(&input, "asset_id"),
+            ErrorCode::InvalidAssetId,
+            Some("asset_id"),
+        );
+        assert_rejected(
+            untag_transaction(0, &input),
+            ErrorCode::MalformedTransaction,
+            Some("bytes"),
+        );
+    }
+}
+
+#[test]
+fn accounts_and_keystores_reject_bad_input() {
+    for input in bad_strings() {
+        assert_rejected(
+            AccountRecord::from_json(&input),
+            ErrorCode::MalformedAccount,
+            None,
+        );
+        assert_rejected(
+            AccountRecord::from_bech32(&input),
+            ErrorCode::MalformedAccount,
+            None,
+        );
+        assert_rejected(
+            AccountRecord::from_base64(&input),
+            ErrorCode::MalformedAccount,
+            None,
+        );
+    }
+    for input in bad_bytes() {
+        assert_rejected(
+            AccountRecord::from_bytes(&input),
+            ErrorCode::MalformedAccount,
+            None,
+        );
+        assert_rejected(
+            open(&input, b"password"),
+            ErrorCode::MalformedKeystore,
+            None,
+        );
+    }
+}
+
+#[test]
+fn phrases_and_shares_reject_bad_input() {
+    assert_rejected(
+        parse_phrase("", None),
+        ErrorCode::InvalidMnemonicLength,
+        Some("words"),
+    );
+    for input in &bad_strings()[1..] {
+        assert_rejected(
+            parse_phrase(input, None),
+            ErrorCode::InvalidMnemonic,
+            Some("words"),
+        );
+    }
+    for input in bad_strings() {
+        assert_rejected(
+            Share::from_words(&input, None),
+            ErrorCode::MalformedShare,
+            None,
+        );
+    }
+    assert_rejected(combine(&[]), ErrorCode::InvalidArgument, Some("shares"));
+}
+
+#[test]
+fn payments_reject_bad_input() {
+    for input in bad_strings() {
+        assert_rejected(
+            PaymentRequest::parse(&input, None),
+            ErrorCode::MalformedPaymentRequest,
+            Some("uri"),
+        );
+        assert_rejected(
+            parse_payment_list(&input),
+            ErrorCode::MalformedPaymentList,
+            Some("json"),
+        );
+    }
+}
@@ -2,9 +2,38 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
 
+use serde::Serialize;
 use wasm_bindgen::prelude::*;
 
+use crate::wasm_structs::{ErrorCode, WasmError};
+
+use std::cell::{Cell, RefCell};
+use std::future::Future;
 use std::panic;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::task::Poll;
+
+#[wasm_bindgen(typescript_custom_section)]
+const IPANIC_REPORT: &'static str = r#"
+interface PanicReport {
+    message: string;
+    file?: string;
+    line?: number;
+    column?: number;
+    function?: string;
+    stack: string;
+}
+
+type PanicReporter = (report: PanicReport) => void;
+"#;
+
+#[wasm_bindgen]
+/// Host callback told about every panic. The PanicReport TypeScript
+/// interface above must be manually updated if changing PanicReport.
+extern "C" {
+    #[wasm_bindgen(extends = js_sys::Function, typescript_type = "PanicReporter")]
+    pub type PanicReporter;
+}
 
 #[wasm_bindgen]
 extern "C" {
@@ -17,25 +46,256 @@ extern "C" {
     fn stack(error: &Error) -> String;
 }
 
+#[derive(Serialize)]
+struct PanicReport {
+    message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    file: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    line: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    column: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    function: Option<&'static str>,
+    stack: String,
+}
+
+// A panic leaves the instance in an unknown state: it aborts in the middle of
+// whatever the exported function was doing, without running destructors.
+static POISONED: AtomicBool = AtomicBool::new(false);
+
+thread_local! {
+    static CURRENT_FUNCTION: Cell<Option<&'static str>> = const { Cell::new(None) };
+    static REPORTER: RefCell<Option<PanicReporter>> = const { RefCell::new(None) };
+}
+
 fn hook_impl(info: &panic::PanicInfo) {
+    POISONED.store(true, Ordering::SeqCst);
+
     let e = Error::new();
     let stack = e.stack();
 
-    let er = js_sys::Error::new(&info.to_string());
-    let _ = js_sys::Reflect::set(&er, &"stack".into(), &stack.into());
+    let message = match info.payload().downcast_ref::<&str>() {
+        Some(message) => message.to_string(),
+        None => match info.payload().downcast_ref::<String>() {
+            Some(message) => message.clone(),
+            None => "Box<dyn Any>".to_string(),
+        },
+    };
+    let report = PanicReport {
+        message,
+        file: info.location().map(|location| location.file().to_string()),
+        line: info.location().map(|location| location.line()),
+        column: info.location().map(|location| location.column()),
+        function: CURRENT_FUNCTION.with(Cell::get),
+        stack,
+    };
+
+    let er = js_sys::Error::new(&describe(&report));
+    er.set_name("RustPanic");
+    let _ = js_sys::Reflect::set(&er, &"stack".into(), &report.stack.as_str().into());
+    if let Ok(value) = serde_wasm_bindgen::to_value(&report) {
+        let _ = js_sys::Reflect::set(&er, &"report".into(), &value);
+        REPORTER.with(|reporter| {
+            // try_borrow: panicking again inside the hook would abort
+            if let Ok(Some(reporter)) = reporter.try_borrow().as_deref() {
+                let _ = reporter.call1(&JsValue::NULL, &value);
+            }
+        });
+    }
 
     wasm_bindgen::throw_val(er.into());
 }
 
+fn describe(report: &PanicReport) -> String {
+    let mut description = String::from("panicked");
+    if let Some(function) = report.function {
+        description.push_str(&format!(" in {}", function));
+    }
+    if let (Some(file), Some(line), Some(column)) = (&report.file, report.line, report.column) {
+        description.push_str(&format!(" at {}:{}:{}", file, line, column));
+    }
+    format!("{}: {}", description, report.message)
+}
+
 pub fn hook(info: &panic::PanicInfo) {
     hook_impl(info);
 }
 
+/// Installs `hook` the first time it is called. Only on wasm32: the hook
+/// calls into JS, so under a host `cargo test` it would abort the whole test
+/// binary rather than fail the one test that panicked.
 #[inline]
 pub fn set_once() {
-    use std::sync::Once;
-    static SET_HOOK: Once = Once::new();
-    SET_HOOK.call_once(|| {
-        panic::set_hook(Box::new(hook));
-    });
+    #[cfg(target_arch = "wasm32")]
+    {
+        use std::sync::Once;
+        static SET_HOOK: Once = Once::new();
+        SET_HOOK.call_once(|| {
+            panic::set_hook(Box::new(hook));
+        });
+    }
+}
+
+/// The name of the function this is expanded in, e.g.
+/// `WasmTransaction::post`, taken from the compiler so it can't go stale.
+macro_rules! function_name {
+    () => {{
+        fn f() {}
+        $crate::panic_hook::short_function_name(::std::any::type_name_of_val(&f))
+    }};
+}
+
+pub(crate) use function_name;
+
+/// `enter` for the function this is expanded in. Used by the exports that
+/// can panic, i.e. that do more than hand back a field or a constant.
+macro_rules! entry {
+    () => {
+        $crate::panic_hook::enter($crate::panic_hook::function_name!())
+    };
+}
+
+pub(crate) use entry;
+
+/// Trims the path of the `f` declared by `function_name!` down to the
+/// function, and its type for methods.
+pub(crate) fn short_function_name(path: &'static str) -> &'static str {
+    let mut path = path.strip_suffix("::f").unwrap_or(path);
+    // Async functions and blocks nest `f` in a closure
+    while let Some(outer) = path.strip_suffix("::{{closure}}") {
+        path = outer;
+    }
+    let mut segments = path.rsplitn(3, "::");
+    let function = segments.next().unwrap_or(path);
+    match segments.next() {
+        Some(parent) if parent.starts_with(char::is_uppercase) => {
+            &path[path.len() - parent.len() - 2 - function.len()..]
+        }
+        _ => function,
+    }
+}
+
+/// Marks `function` as the exported function currently running, so panic
+/// reports can name it. The previous value is restored when the guard drops.
+pub struct Entry {
+    previous: Option<&'static str>,
+}
+
+/// Called first thing in exports that can panic, through `entry!`. Once the
+/// instance is poisoned this throws a `Poisoned` error instead of returning,
+/// before the export has done anything. Exports that only read a field or a
+/// constant skip it: they can't panic, and keep working when poisoned.
+pub fn enter(function: &'static str) -> Entry {
+    if is_poisoned() {
+        wasm_bindgen::throw_val(refusal(function).into());
+    }
+    enter_even_if_poisoned(function)
+}
+
+fn enter_even_if_poisoned(function: &'static str) -> Entry {
+    set_once();
+    let previous = CURRENT_FUNCTION.with(|current| current.replace(Some(function)));
+    Entry { previous }
+}
+
+impl Drop for Entry {
+    fn drop(&mut self) {
+        CURRENT_FUNCTION.with(|current| current.set(self.previous));
+    }
+}
+
+/// Async counterpart of `enter`: `function` is marked as running whenever
+/// `future` is polled, but not while it is suspended. If the instance gets
+/// poisoned meanwhile, the future is dropped and a `Poisoned` error returned.
+pub fn instrument<T, F>(
+    function: &'static str,
+    future: F,
+) -> impl Future<Output = Result<T, JsValue>>
+where
+    F: Future<Output = Result<T, JsValue>>,
+{
+    let mut future = Some(Box::pin(future));
+    std::future::poll_fn(move |cx| {
+        if is_poisoned() {
+            future = None;
+            return Poll::Ready(Err(refusal(function).into()));
+        }
+        let _entry = enter_even_if_poisoned(function);
+        match future.as_mut() {
+            Some(future) => future.as_mut().poll(cx),
+            None => Poll::Ready(Err(refusal(function).into())),
+        }
+    })
+}
+
+fn refusal(function: &'static str) -> WasmError {
+    WasmError::new(
+        ErrorCode::Poisoned,
+        format!(
+            "{} called after a panic poisoned this instance; instantiate the module again",
+            function
+        ),
+    )
+}
+
+/// Registers a callback that receives a `PanicReport` for every panic, e.g.
+/// to forward it to telemetry. Pass undefined to remove it.
+#[wasm_bindgen(js_name = "setPanicReporter")]
+pub fn set_panic_reporter(reporter: Option<PanicReporter>) {
+    REPORTER.with(|current| *current.borrow_mut() = reporter);
+}
+
+/// True once any call into this module has panicked. A poisoned instance
+/// must be discarded and the module instantiated again: calls that could
+/// panic then throw with code `Poisoned`, while getters and `wipe` on
+/// objects holding keys keep working.
+#[wasm_bindgen(js_name = "isPoisoned")]
+pub fn is_poisoned() -> bool {
+    POISONED.load(Ordering::SeqCst)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Example;
+
+    impl Example {
+        fn method() -> &'static str {
+            function_name!()
+        }
+
+        async fn async_method() -> &'static str {
+            function_name!()
+        }
+    }
+
+    fn free_function() -> &'static str {
+        function_name!()
+    }
+
+    #[test]
+    fn test_function_name_is_the_enclosing_function() {
+        assert_eq!(Example::method(), "Example::method");
+        assert_eq!(free_function(), "free_function");
+
+        let waker = std::task::Waker::noop();
+        let mut context = std::task::Context::from_waker(waker);
+        let mut future = std::pin::pin!(Example::async_method());
+        assert_eq!(
+            future.as_mut().poll(&mut context),
+            Poll::Ready("Example::async_method")
+        );
+    }
+
+    #[test]
+    fn test_refusal_names_the_function() {
+        let error = refusal("WasmTransaction::post");
+        assert_eq!(error.code, ErrorCode::Poisoned);
+        assert_eq!(error.code.class_name(), "IronfishError");
+        assert!(error
+            .message
+            .starts_with("WasmTransaction::post called after a panic"));
+    }
 }
@@ -0,0 +1,688 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use bech32::{FromBase32, ToBase32, Variant};
+use ironfish_rust::{IncomingViewKey, SaplingKey};
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+use zeroize::{Zeroize, Zeroizing};
+
+use super::{derivation, panic_hook, ErrorCode, ViewOnlyKey, WasmError, WasmSpendingKey};
+use crate::{Key, LanguageCode};
+
+pub const ACCOUNT_BECH32_HRP: &str = "ifaccount";
+
+const JSON_VERSION: u32 = 2;
+/// Version of the wallet's `ifaccount` binary encoding.
+const BINARY_VERSION: u16 = 1;
+/// The wallet encodes with this limit rather than bech32's usual 90.
+const BECH32_LIMIT: usize = 1023;
+
+const VIEW_KEY_LENGTH: usize = 64;
+const KEY_LENGTH: usize = 32;
+
+/// The encodings accounts are exchanged in.
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AccountFormat {
+    /// The wallet's JSON export.
+    Json,
+    /// Base64 of the JSON export.
+    Base64,
+    /// The wallet's `ifaccount1...` bech32m string.
+    Bech32,
+    /// The spending key's mnemonic. Metadata travels separately.
+    Mnemonic,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct CreatedAt {
+    pub(crate) hash: String,
+    pub(crate) sequence: u32,
+}
+
+/// An account as it appears in the export formats. Keys are hex strings.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct AccountRecord {
+    #[serde(default = "json_version")]
+    pub(crate) version: u32,
+    pub(crate) name: String,
+    pub(crate) spending_key: Option<String>,
+    pub(crate) view_key: String,
+    pub(crate) incoming_view_key: String,
+    pub(crate) outgoing_view_key: String,
+    pub(crate) public_address: String,
+    #[serde(default)]
+    pub(crate) created_at: Option<CreatedAt>,
+}
+
+fn json_version() -> u32 {
+    JSON_VERSION
+}
+
+fn malformed(message: impl ToString) -> WasmError {
+    WasmError::new(ErrorCode::MalformedAccount, message)
+}
+
+impl Drop for AccountRecord {
+    fn drop(&mut self) {
+        self.spending_key.zeroize();
+    }
+}
+
+impl AccountRecord {
+    pub(crate) fn to_json(&self) -> Result<String, WasmError> {
+        serde_json::to_string(self).map_err(malformed)
+    }
+
+    pub(crate) fn from_json(json: &str) -> Result<AccountRecord, WasmError> {
+        serde_json::from_str(json).map_err(malformed)
+    }
+
+    // The wallet's `ifaccount` encoding, integers little endian:
+    //  2 binary version
+    //+ varint name length, then the UTF-8 name
+    //+ 64 view key
+    //+ 32 incoming view key
+    //+ 32 outgoing view key
+    //+ 32 public address
+    //+ 1 has spending key, then the 32 byte key if 1
+    //+ 1 has created at, then the 32 byte block hash and 4 byte sequence if 1
+    pub(crate) fn to_bytes(&self) -> Result<Zeroizing<Vec<u8>>, WasmError> {
+        let name = self.name.as_bytes();
+        // Sized up front: growing would leave a copy of the spending key behind
+        let mut bytes = Zeroizing::new(Vec::with_capacity(
+            2 + 9 + name.len() + VIEW_KEY_LENGTH + 3 * KEY_LENGTH + 2 + 2 * KEY_LENGTH + 4,
+        ));
+        bytes.extend_from_slice(&BINARY_VERSION.to_le_bytes());
+        write_varint(&mut bytes, name.len() as u64);
+        bytes.extend_from_slice(name);
+        bytes.extend(decode_hex(&self.view_key, VIEW_KEY_LENGTH, "viewKey")?);
+        bytes.extend(decode_hex(
+            &self.incoming_view_key,
+            KEY_LENGTH,
+            "incomingViewKey",
+        )?);
+        bytes.extend(decode_hex(
+            &self.outgoing_view_key,
+            KEY_LENGTH,
+            "outgoingViewKey",
+        )?);
+        bytes.extend(decode_hex(
+            &self.public_address,
+            KEY_LENGTH,
+            "publicAddress",
+        )?);
+        bytes.push(u8::from(self.spending_key.is_some()));
+        if let Some(spending_key) = &self.spending_key {
+            let spending_key = Zeroizing::new(decode_hex(spending_key, KEY_LENGTH, "spendingKey")?);
+            bytes.extend_from_slice(&spending_key);
+        }
+        bytes.push(u8::from(self.created_at.is_some()));
+        if let Some(created_at) = &self.created_at {
+            bytes.extend(decode_hex(&created_at.hash, KEY_LENGTH, "createdAt")?);
+            bytes.extend_from_slice(&created_at.sequence.to_le_bytes());
+        }
+        Ok(bytes)
+    }
+
+    pub(crate) fn from_bytes(bytes: &[u8]) -> Result<AccountRecord, WasmError> {
+        let mut reader = Reader { bytes };
+        let version = u16::from_le_bytes([reader.take(1)?[0], reader.take(1)?[0]]);
+        if version != BINARY_VERSION {
+            return Err(malformed(format!(
+                "unsupported account encoding version {}",
+                version
+            )));
+        }
+        let name_length = reader.varint()?;
+        let name_length = usize::try_from(name_length).map_err(|_| truncated())?;
+        let name = String::from_utf8(reader.take(name_length)?.to_vec())
+            .map_err(|_| malformed("account name is not valid UTF-8"))?;
+
+        let view_key = hex::encode(reader.take(VIEW_KEY_LENGTH)?);
+        let incoming_view_key = hex::encode(reader.take(KEY_LENGTH)?);
+        let outgoing_view_key = hex::encode(reader.take(KEY_LENGTH)?);
+        let public_address = hex::encode(reader.take(KEY_LENGTH)?);
+        let spending_key = match reader.flag()? {
+            false => None,
+            true => Some(hex::encode(reader.take(KEY_LENGTH)?)),
+        };
+        let created_at = match reader.flag()? {
+            false => None,
+            true => {
+                let hash = hex::encode(reader.take(KEY_LENGTH)?);
+                let mut sequence = [0u8; 4];
+                sequence.copy_from_slice(reader.take(4)?);
+                Some(CreatedAt {
+                    hash,
+                    sequence: u32::from_le_bytes(sequence),
+                })
+            }
+        };
+        if !reader.bytes.is_empty() {
+            return Err(malformed("trailing bytes after account"));
+        }
+
+        Ok(AccountRecord {
+            version: JSON_VERSION,
+            name,
+            spending_key,
+            view_key,
+            incoming_view_key,
+            outgoing_view_key,
+            public_address,
+            created_at,
+        })
+    }
+
+    /// Like the wallet, bech32m encodes the hex of the binary encoding
+    /// rather than the bytes themselves.
+    pub(crate) fn to_bech32(&self) -> Result<String, WasmError> {
+        let hex = Zeroizing::new(hex::encode(&*self.to_bytes()?));
+        let encoded = bech32::encode(
+            ACCOUNT_BECH32_HRP,
+            hex.as_bytes().to_base32(),
+            Variant::Bech32m,
+        )
+        .map_err(malformed)?;
+        if encoded.len() > BECH32_LIMIT {
+            return Err(WasmError::new(
+                ErrorCode::InvalidArgument,
+                format!("account is longer than {} characters encoded", BECH32_LIMIT),
+            )
+            .with_argument("name"));
+        }
+        Ok(encoded)
+    }
+
+    pub(crate) fn from_bech32(encoded: &str) -> Result<AccountRecord, WasmError> {
+        if encoded.len() > BECH32_LIMIT {
+            return Err(malformed(format!(
+                "longer than {} characters",
+                BECH32_LIMIT
+            )));
+        }
+        let (hrp, data, variant) = bech32::decode(encoded).map_err(malformed)?;
+        if hrp != ACCOUNT_BECH32_HRP || variant != Variant::Bech32m {
+            return Err(malformed("not an ifaccount bech32m string"));
+        }
+        let hex = Zeroizing::new(Vec::<u8>::from_base32(&data).map_err(malformed)?);
+        let bytes = Zeroizing::new(hex::decode(&*hex).map_err(malformed)?);
+        AccountRecord::from_bytes(&bytes)
+    }
+
+    pub(crate) fn to_base64(&self) -> Result<String, WasmError> {
+        Ok(BASE64.encode(self.to_json()?))
+    }
+
+    pub(crate) fn from_base64(encoded: &str) -> Result<AccountRecord, WasmError> {
+        let json = BASE64.decode(encoded).map_err(malformed)?;
+        let json = String::from_utf8(json).map_err(malformed)?;
+        AccountRecord::from_json(&json)
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, length: usize) -> Result<&'a [u8], WasmError> {
+        if self.bytes.len() < length {
+            return Err(truncated());
+        }
+        let (taken, rest) = self.bytes.split_at(length);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    fn flag(&mut self) -> Result<bool, WasmError> {
+        match self.take(1)?[0] {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(malformed("expected a 0 or 1 flag")),
+        }
+    }
+
+    /// A bitcoin style varint, as bufio writes them. Non-canonical
+    /// encodings are rejected, as bufio does.
+    fn varint(&mut self) -> Result<u64, WasmError> {
+        let (length, minimum) = match self.take(1)?[0] {
+            0xfd => (2, 0xfd),
+            0xfe => (4, 0x1_0000),
+            0xff => (8, 0x1_0000_0000),
+            value => return Ok(u64::from(value)),
+        };
+        let mut value = [0u8; 8];
+        value[..length].copy_from_slice(self.take(length)?);
+        let value = u64::from_le_bytes(value);
+        if value < minimum {
+            return Err(malformed("non-canonical varint"));
+        }
+        Ok(value)
+    }
+}
+
+fn write_varint(bytes: &mut Vec<u8>, value: u64) {
+    match value {
+        0..=0xfc => bytes.push(value as u8),
+        0xfd..=0xffff => {
+            bytes.push(0xfd);
+            bytes.extend_from_slice(&(value as u16).to_le_bytes());
+        }
+        0x1_0000..=0xffff_ffff => {
+            bytes.push(0xfe);
+            bytes.extend_from_slice(&(value as u32).to_le_bytes());
+        }
+        _ => {
+            bytes.push(0xff);
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+    }
+}
+
+fn truncated() -> WasmError {
+    malformed("account encoding is truncated")
+}
+
+fn decode_hex(value: &str, length: usize, argument: &str) -> Result<Vec<u8>, WasmError> {
+    match hex::decode(value) {
+        Ok(bytes) if bytes.len() == length => Ok(bytes),
+        _ => {
+            Err(malformed(format!("expected {} hex encoded bytes", length)).with_argument(argument))
+        }
+    }
+}
+
+/// Guesses the format of an exported account from its shape alone.
+pub(crate) fn detect_format(value: &str) -> AccountFormat {
+    let value = value.trim();
+    if value.starts_with('{') {
+        AccountFormat::Json
+    } else if value
+        .to_ascii_lowercase()
+        .starts_with(&format!("{}1", ACCOUNT_BECH32_HRP))
+    {
+        AccountFormat::Bech32
+    } else if value.split_whitespace().count() > 1 {
+        AccountFormat::Mnemonic
+    } else {
+        AccountFormat::Base64
+    }
+}
+
+/// An imported or exportable account: its keys plus the wallet metadata that
+/// travels with them.
+#[wasm_bindgen]
+pub struct WasmAccount {
+    record: AccountRecord,
+}
+
+#[wasm_bindgen]
+impl WasmAccount {
+    /// Wraps a `Key` from `generateKey` or `generatePublicAddress`.
+    #[wasm_bindgen(js_name = "fromKey")]
+    pub fn from_key(
+        key: &Key,
+        name: String,
+        created_at_sequence: Option<u32>,
+        created_at_hash: Option<String>,
+    ) -> Result<WasmAccount, JsValue> {
+        let _entry = panic_hook::entry!();
+
+        let sapling_key =
+            WasmSpendingKey::from_hex(key.spending_key_hex()?.to_string())?.sapling_key()?;
+        WasmAccount::from_sapling_key(&sapling_key, name, created_at_sequence, created_at_hash)
+    }
+
+    /// Decodes an exported account. The format is detected when not given,
+    /// as is a mnemonic's language. Mnemonics carry no metadata, so `name`
+    /// and the creation sequence are taken from the arguments; for the other
+    /// formats `name` overrides the exported one when given.
+    #[wasm_bindgen]
+    pub fn decode(
+        value: String,
+        format: Option<AccountFormat>,
+        name: Option<String>,
+        language_code: Option<LanguageCode>,
+        created_at_sequence: Option<u32>,
+        created_at_hash: Option<String>,
+    ) -> Result<WasmAccount, JsValue> {
+        let _entry = panic_hook::entry!();
+
+        let value = Zeroizing::new(value);
+        let trimmed = value.trim();
+        let format = format.unwrap_or_else(|| detect_format(trimmed));
+        let mut account = match format {
+            AccountFormat::Json => WasmAccount::checked(AccountRecord::from_json(trimmed)?)?,
+            AccountFormat::Base64 => WasmAccount::checked(AccountRecord::from_base64(trimmed)?)?,
+            AccountFormat::Bech32 => WasmAccount::checked(AccountRecord::from_bech32(trimmed)?)?,
+            AccountFormat::Mnemonic => {
+                let spending_key = derivation::master_key(trimmed, language_code)
+                    .map_err(|e| e.with_argument("value"))?;
+                let key = WasmSpendingKey::from_bytes(*spending_key)?;
+                WasmAccount::from_sapling_key(
+                    &key.sapling_key()?,
+                    name.clone().unwrap_or_default(),
+                    created_at_sequence,
+                    created_at_hash,
+                )?
+            }
+        };
+        if let Some(name) = name {
+            account.record.name = name;
+        }
+        Ok(account)
+    }
+
+    /// Encodes the account. Mnemonic output is the spending key's phrase
+    /// only, in `language_code` (English by default).
+    #[wasm_bindgen]
+    pub fn encode(
+        &self,
+        format: AccountFormat,
+        language_code: Option<LanguageCode>,
+    ) -> Result<String, JsValue> {
+        let _entry = panic_hook::entry!();
+
+        Ok(match format {
+            AccountFormat::Json => self.record.to_json()?,
+            AccountFormat::Base64 => self.record.to_base64()?,
+            AccountFormat::Bech32 => self.record.to_bech32()?,
+            AccountFormat::Mnemonic => self
+                .spending_key()?
+                .to_words(language_code.unwrap_or(LanguageCode::English))?,
+        })
+    }
+
+    /// The account as a `Key`. Throws for accounts exported without their
+    /// spending key.
+    #[wasm_bindgen(js_name = "toKey")]
+    pub fn to_key(&self) -> Result<Key, JsValue> {
+        let _entry = panic_hook::entry!();
+        let spending_key = self.record.spending_key.as_deref().ok_or_else(|| {
+            WasmError::new(ErrorCode::InvalidArgument, "account has no spending key")
+        })?;
+        crate::create_public_key_to_js(spending_key)
+    }
+
+    /// The account's view keys, for scanning without the spending key.
+    #[wasm_bindgen(js_name = "toViewOnlyKey")]
+    pub fn to_view_only_key(&self) -> Result<ViewOnlyKey, JsValue> {
+        let _entry = panic_hook::entry!();
+
+        Ok(ViewOnlyKey::from_hex_keys(
+            &self.record.view_key,
+            &self.record.incoming_view_key,
+            &self.record.outgoing_view_key,
+        )?)
+    }
+
+    #[wasm_bindgen(js_name = "spendingKey")]
+    pub fn spending_key(&self) -> Result<WasmSpendingKey, JsValue> {
+        let _entry = panic_hook::entry!();
+        match &self.record.spending_key {
+            Some(spending_key) => WasmSpendingKey::from_hex(spending_key.clone()),
+            None => Err(
+                WasmError::new(ErrorCode::InvalidArgument, "account has no spending key").into(),
+            ),
+        }
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn name(&self) -> String {
+        self.record.name.clone()
+    }
+
+    #[wasm_bindgen(setter)]
+    pub fn set_name(&mut self, name: String) {
+        self.record.name = name;
+    }
+
+    #[wasm_bindgen(getter, js_name = "viewKey")]
+    pub fn view_key(&self) -> String {
+        self.record.view_key.clone()
+    }
+
+    #[wasm_bindgen(getter, js_name = "incomingViewKey")]
+    pub fn incoming_view_key(&self) -> String {
+        self.record.incoming_view_key.clone()
+    }
+
+    #[wasm_bindgen(getter, js_name = "outgoingViewKey")]
+    pub fn outgoing_view_key(&self) -> String {
+        self.record.outgoing_view_key.clone()
+    }
+
+    #[wasm_bindgen(getter, js_name = "publicAddress")]
+    pub fn public_address(&self) -> String {
+        self.record.public_address.clone()
+    }
+
+    #[wasm_bindgen(getter, js_name = "isViewOnly")]
+    pub fn is_view_only(&self) -> bool {
+        self.record.spending_key.is_none()
+    }
+
+    #[wasm_bindgen(getter, js_name = "createdAtSequence")]
+    pub fn created_at_sequence(&self) -> Option<u32> {
+        self.record.created_at.as_ref().map(|c| c.sequence)
+    }
+
+    #[wasm_bindgen(getter, js_name = "createdAtHash")]
+    pub fn created_at_hash(&self) -> Option<String> {
+        self.record.created_at.as_ref().map(|c| c.hash.clone())
+    }
+}
+
+impl WasmAccount {
+    fn from_sapling_key(
+        key: &SaplingKey,
+        name: String,
+        created_at_sequence: Option<u32>,
+        created_at_hash: Option<String>,
+    ) -> Result<WasmAccount, JsValue> {
+        let created_at = match created_at_sequence {
+            Some(sequence) => {
+                let hash = created_at_hash.unwrap_or_else(|| hex::encode([0u8; KEY_LENGTH]));
+                decode_hex(&hash, KEY_LENGTH, "created_at_hash")?;
+                Some(CreatedAt { hash, sequence })
+            }
+            None => None,
+        };
+        Ok(WasmAccount {
+            record: AccountRecord {
+                version: JSON_VERSION,
+                name,
+                spending_key: Some(key.hex_spending_key()),
+                view_key: key.view_key().hex_key(),
+                incoming_view_key: key.incoming_view_key().hex_key(),
+                outgoing_view_key: key.outgoing_view_key().hex_key(),
+                public_address: key.public_address().hex_public_address(),
+                created_at,
+            },
+        })
+    }
+
+    /// Checks that every key in an imported record belongs to the same
+    /// account, so a corrupted export fails here rather than at spend time.
+    fn checked(record: AccountRecord) -> Result<WasmAccount, WasmError> {
+        let mismatch = |argument: &str| {
+            malformed("key does not belong to this account").with_argument(argument)
+        };
+
+        let incoming_view_key = IncomingViewKey::from_hex(&record.incoming_view_key)
+            .map_err(|e| malformed(e).with_argument("incomingViewKey"))?;
+        if incoming_view_key.public_address().hex_public_address() != record.public_address {
+            return Err(mismatch("publicAddress"));
+        }
+        if let Some(spending_key) = &record.spending_key {
+            let key = SaplingKey::from_hex(spending_key)
+                .map_err(|e| malformed(e).with_argument("spendingKey"))?;
+            if key.view_key().hex_key() != record.view_key {
+                return Err(mismatch("viewKey"));
+            }
+            if key.incoming_view_key().hex_key() != record.incoming_view_key {
+                return Err(mismatch("incomingViewKey"));
+            }
+            if key.outgoing_view_key().hex_key() != record.outgoing_view_key {
+                return Err(mismatch("outgoingViewKey"));
+            }
+        }
+        Ok(WasmAccount { record })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(spending_key: Option<&str>, created_at: Option<CreatedAt>) -> AccountRecord {
+        AccountRecord {
+            version: JSON_VERSION,
+            name: "default".to_string(),
+            spending_key: spending_key.map(str::to_string),
+            view_key: "11".repeat(VIEW_KEY_LENGTH),
+            incoming_view_key: "22".repeat(KEY_LENGTH),
+            outgoing_view_key: "33".repeat(KEY_LENGTH),
+            public_address: "44".repeat(KEY_LENGTH),
+            created_at,
+        }
+    }
+
+    #[test]
+    fn test_formats_round_trip() {
+        let created_at = CreatedAt {
+            hash: "55".repeat(KEY_LENGTH),
+            sequence: 42,
+        };
+        let spending_key = "66".repeat(KEY_LENGTH);
+        for original in [
+            record(None, None),
+            record(Some(&spending_key), Some(created_at)),
+        ] {
+            let json = original.to_json().unwrap();
+            assert_eq!(AccountRecord::from_json(&json).unwrap(), original);
+            let base64 = original.to_base64().unwrap();
+            assert_eq!(AccountRecord::from_base64(&base64).unwrap(), original);
+            let bech32 = original.to_bech32().unwrap();
+            assert!(bech32.starts_with("ifaccount1"));
+            assert_eq!(AccountRecord::from_bech32(&bech32).unwrap(), original);
+        }
+    }
+
+    // Built from the wallet's Bech32Encoder layout by a separate script, not
+    // exported by the CLI: there was no Iron Fish node to export one from.
+    #[test]
+    fn test_decodes_wallet_bech32() {
+        let encoded = concat!(
+            "ifaccount1xqcnqvpsxumrgd34xcmrvvfhx5mxxde5xqcrqvfsxgcrxvp5xq6nqd3sxu",
+            "crsvpexpsnqc3svvcxgvr9xpnrzvp3xycnyvfnxy6rzdf3xccnwvfcxyunzcf3vgckxv",
+            "tyx9jnze3jxqerzv3jxgenydpjx5ervv3hxgurywfjvyexyvnrxfjryefjvcenqve3xv",
+            "erxvenxsen2vekxvmnxwpn8yekzvmzxd3nxepnv5ekvdpsxscngv35xv6rgdp4xsmrgd",
+            "e58q6rjdrpx33rgce5vs6x2drxx5cr2vf4xg6nxdf5x56n2d34xu6nsdfex4sn2c34vv",
+            "6kgdt9x4nrvvpkxymryd3nxc6rvdfkxcmrwd3cxcunvcfkvgmxxdnyxejnve3hxqmnzd",
+            "ejxuenwdphx5mnvdehxuurwwfhvymkydmrxajrwefhvcurqwp38qersvecxsur2wpk8q",
+            "mnswpc8yuxzwrz8p3nsepcv5uxvwfs8ycnjv3exvungwf48ymrjdee8qunjwtp893rjc",
+            "eevsuk2wtxxqckzvrpx9snycfnvy6xzdtpxesnwcfcvyukzctpvfskxctyv9jkzenzxp",
+            "3rzc3jvgekydrzx43rvc3hvguxywtzv93xycnrvfjxyetzvccrzcesvvckxvnrxd3ngc",
+            "e4vvmxxdmr8p3njcmpvd3xxcmrv33k2cmxvscxgvtyxfjrxep5vs6kgdnyxajrsepev3",
+            "skgcnyvdjxger9v3nrqdpsxvcryvp363anyt",
+        );
+        let hex_range = |range: std::ops::Range<u8>| hex::encode(range.collect::<Vec<u8>>());
+        let expected = AccountRecord {
+            version: JSON_VERSION,
+            name: "default".to_string(),
+            spending_key: Some(hex_range(160..192)),
+            view_key: hex_range(0..64),
+            incoming_view_key: hex_range(64..96),
+            outgoing_view_key: hex_range(96..128),
+            public_address: hex_range(128..160),
+            created_at: Some(CreatedAt {
+                hash: hex_range(192..224),
+                sequence: 0x01020304,
+            }),
+        };
+        assert_eq!(AccountRecord::from_bech32(encoded).unwrap(), expected);
+        assert_eq!(expected.to_bech32().unwrap(), encoded);
+    }
+
+    #[test]
+    fn test_bech32_length_limit() {
+        let mut account = record(Some(&"66".repeat(KEY_LENGTH)), None);
+        account.name = "n".repeat(150);
+        assert_eq!(
+            account.to_bech32().err().unwrap().code,
+            ErrorCode::InvalidArgument
+        );
+
+        let mut long = record(None, None);
+        long.name = "n".repeat(400);
+        let bytes = long.to_bytes().unwrap();
+        assert_eq!(&bytes[2..5], &[0xfd, 0x90, 0x01]);
+        assert_eq!(AccountRecord::from_bytes(&bytes).unwrap(), long);
+        let hex = hex::encode(&*bytes);
+        let encoded = bech32::encode(
+            ACCOUNT_BECH32_HRP,
+            hex.as_bytes().to_base32(),
+            Variant::Bech32m,
+        )
+        .unwrap();
+        assert!(AccountRecord::from_bech32(&encoded).is_err());
+    }
+
+    #[test]
+    fn test_detect_format() {
+        let account = record(None, None);
+        let json = account.to_json().unwrap();
+        assert_eq!(detect_format(&json), AccountFormat::Json);
+        assert_eq!(
+            detect_format(&account.to_base64().unwrap()),
+            AccountFormat::Base64
+        );
+        assert_eq!(
+            detect_format(&format!("  {}\n", account.to_bech32().unwrap())),
+            AccountFormat::Bech32
+        );
+        assert_eq!(
+            detect_format("abandon abandon abandon art"),
+            AccountFormat::Mnemonic
+        );
+    }
+
+    #[test]
+    fn test_rejects_malformed_encodings() {
+        let bytes = record(None, None).to_bytes().unwrap();
+        for length in [0, 1, 3, bytes.len() - 1] {
+            assert!(AccountRecord::from_bytes(&bytes[..length]).is_err());
+        }
+        let mut trailing = bytes.clone();
+        trailing.push(0);
+        assert!(AccountRecord::from_bytes(&trailing).is_err());
+        let mut bad_flag = bytes.clone();
+        let last = bad_flag.len() - 1;
+        bad_flag[last] = 2;
+        assert!(AccountRecord::from_bytes(&bad_flag).is_err());
+        let mut non_canonical = bytes.clone();
+        non_canonical.splice(2..3, [0xfd, 7, 0]);
+        assert!(AccountRecord::from_bytes(&non_canonical).is_err());
+
+        let mut short_key = record(None, None);
+        short_key.view_key = "11".to_string();
+        assert_eq!(
+            short_key.to_bytes().err().unwrap().argument.as_deref(),
+            Some("viewKey")
+        );
+
+        let wrong_hrp =
+            bech32::encode("ifaddress", bytes.as_slice().to_base32(), Variant::Bech32m).unwrap();
+        assert!(AccountRecord::from_bech32(&wrong_hrp).is_err());
+        assert!(AccountRecord::from_base64("not base64!").is_err());
+    }
+}
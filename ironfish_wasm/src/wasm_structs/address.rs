@@ -0,0 +1,171 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+use bech32::{FromBase32, ToBase32, Variant};
+use ironfish_rust::PublicAddress;
+use wasm_bindgen::prelude::*;
+
+use super::{panic_hook, ErrorCode, WasmError};
+
+const PUBLIC_ADDRESS_LENGTH: usize = 32;
+
+/// The network an encoded address belongs to, named by its human-readable
+/// prefix so an address for one network is never mistaken for another's.
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Network {
+    Mainnet,
+    Testnet,
+    Devnet,
+}
+
+const NETWORKS: [Network; 3] = [Network::Mainnet, Network::Testnet, Network::Devnet];
+
+impl Network {
+    pub(crate) fn address_hrp(&self) -> &'static str {
+        match self {
+            Network::Mainnet => "iron",
+            Network::Testnet => "tiron",
+            Network::Devnet => "diron",
+        }
+    }
+
+    fn from_address_hrp(hrp: &str) -> Option<Network> {
+        NETWORKS
+            .into_iter()
+            .find(|network| network.address_hrp() == hrp)
+    }
+}
+
+fn invalid(message: impl ToString, argument: &str) -> WasmError {
+    WasmError::new(ErrorCode::InvalidPublicAddress, message).with_argument(argument)
+}
+
+pub(crate) fn encode_address_bytes(
+    bytes: &[u8; PUBLIC_ADDRESS_LENGTH],
+    network: Network,
+) -> String {
+    // Only fails for an over-long human-readable prefix, which ours aren't
+    bech32::encode(network.address_hrp(), bytes.to_base32(), Variant::Bech32m).unwrap_or_default()
+}
+
+/// The address bytes of a raw hex or bech32m encoded address, with the
+/// network for encoded ones.
+pub(crate) fn decode_address_bytes(
+    value: &str,
+    argument: &str,
+) -> Result<(Option<Network>, [u8; PUBLIC_ADDRESS_LENGTH]), WasmError> {
+    let value = value.trim();
+    let mut bytes = [0u8; PUBLIC_ADDRESS_LENGTH];
+    if value.len() == 2 * PUBLIC_ADDRESS_LENGTH && value.bytes().all(|b| b.is_ascii_hexdigit()) {
+        hex::decode_to_slice(value, &mut bytes).map_err(|e| invalid(e, argument))?;
+        return Ok((None, bytes));
+    }
+
+    let (hrp, data, variant) = bech32::decode(value).map_err(|e| match e {
+        bech32::Error::InvalidChecksum => invalid(
+            "checksum does not match; the address was mistyped or truncated",
+            argument,
+        ),
+        e => invalid(
+            format!("expected a hex or bech32m encoded address: {}", e),
+            argument,
+        ),
+    })?;
+    let network = Network::from_address_hrp(&hrp)
+        .ok_or_else(|| invalid(format!("unknown address prefix '{}'", hrp), argument))?;
+    if variant != Variant::Bech32m {
+        return Err(invalid("addresses must use bech32m, not bech32", argument));
+    }
+    let data = Vec::<u8>::from_base32(&data).map_err(|e| invalid(e, argument))?;
+    if data.len() != PUBLIC_ADDRESS_LENGTH {
+        return Err(invalid(
+            format!("expected {} address bytes", PUBLIC_ADDRESS_LENGTH),
+            argument,
+        ));
+    }
+    bytes.copy_from_slice(&data);
+    Ok((Some(network), bytes))
+}
+
+/// Parses an address given as raw hex or bech32m, for every API taking one.
+pub(crate) fn parse_public_address(
+    value: &str,
+    argument: &str,
+) -> Result<PublicAddress, WasmError> {
+    let (_, bytes) = decode_address_bytes(value, argument)?;
+    PublicAddress::new(&bytes).map_err(|e| invalid(e, argument))
+}
+
+/// Encodes an address, given as hex or already encoded, as bech32m with the
+/// network's prefix.
+#[wasm_bindgen(js_name = "encodePublicAddress")]
+pub fn encode_public_address(address: &str, network: Network) -> Result<String, JsValue> {
+    let _entry = panic_hook::entry!();
+
+    let address = parse_public_address(address, "address")?;
+    Ok(encode_address_bytes(&address.public_address(), network))
+}
+
+/// The raw hex form of an address given as hex or bech32m.
+#[wasm_bindgen(js_name = "decodePublicAddress")]
+pub fn decode_public_address(address: &str) -> Result<String, JsValue> {
+    let _entry = panic_hook::entry!();
+
+    Ok(parse_public_address(address, "address")?.hex_public_address())
+}
+
+/// The network of a bech32m address, or undefined for raw hex.
+#[wasm_bindgen(js_name = "publicAddressNetwork")]
+pub fn public_address_network(address: &str) -> Result<Option<Network>, JsValue> {
+    let _entry = panic_hook::entry!();
+
+    Ok(decode_address_bytes(address, "address")?.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BYTES: [u8; PUBLIC_ADDRESS_LENGTH] = [7; PUBLIC_ADDRESS_LENGTH];
+
+    #[test]
+    fn test_round_trip() {
+        for network in NETWORKS {
+            let encoded = encode_address_bytes(&BYTES, network);
+            assert!(encoded.starts_with(&format!("{}1", network.address_hrp())));
+            assert_eq!(
+                decode_address_bytes(&encoded, "address").unwrap(),
+                (Some(network), BYTES)
+            );
+            assert_eq!(
+                decode_address_bytes(&encoded.to_uppercase(), "address").unwrap(),
+                (Some(network), BYTES)
+            );
+        }
+        assert_eq!(
+            decode_address_bytes(&hex::encode(BYTES), "address").unwrap(),
+            (None, BYTES)
+        );
+    }
+
+    #[test]
+    fn test_rejects_typos_and_wrong_encodings() {
+        let encoded = encode_address_bytes(&BYTES, Network::Mainnet);
+        let mut typo: Vec<char> = encoded.chars().collect();
+        let last = typo.len() - 1;
+        typo[last] = if typo[last] == 'q' { 'p' } else { 'q' };
+        let typo: String = typo.into_iter().collect();
+        let error = decode_address_bytes(&typo, "owner").err().unwrap();
+        assert_eq!(error.code, ErrorCode::InvalidPublicAddress);
+        assert_eq!(error.argument.as_deref(), Some("owner"));
+
+        let bech32 = bech32::encode("iron", BYTES.to_base32(), Variant::Bech32).unwrap();
+        assert!(decode_address_bytes(&bech32, "address").is_err());
+        let other = bech32::encode("btc", BYTES.to_base32(), Variant::Bech32m).unwrap();
+        assert!(decode_address_bytes(&other, "address").is_err());
+        let short = bech32::encode("iron", [7u8; 31].to_base32(), Variant::Bech32m).unwrap();
+        assert!(decode_address_bytes(&short, "address").is_err());
+    }
+}
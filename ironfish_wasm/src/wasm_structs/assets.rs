@@ -1,16 +1,14 @@
-use ironfish_rust::{
-    assets::{
-        asset::{
-            Asset, ASSET_LENGTH as SERIALIZED_ASSET_LENGTH, ID_LENGTH, METADATA_LENGTH, NAME_LENGTH,
-        },
-        asset_identifier::NATIVE_ASSET,
+use ironfish_rust::assets::{
+    asset::{
+        Asset, ASSET_LENGTH as SERIALIZED_ASSET_LENGTH, ID_LENGTH, METADATA_LENGTH, NAME_LENGTH,
     },
-    PublicAddress,
+    asset_identifier::{AssetIdentifier, NATIVE_ASSET},
 };
 
 use wasm_bindgen::prelude::*;
 
-use super::WasmIronfishError;
+use super::address::parse_public_address;
+use super::{panic_hook, ErrorCode, WasmError, WasmIronfishError};
 
 #[wasm_bindgen]
 pub fn get_asset_id_length() -> u32 {
@@ -32,6 +30,18 @@ pub fn get_asset_length() -> u32 {
     SERIALIZED_ASSET_LENGTH as u32
 }
 
+/// Reads an asset identifier, rejecting byte strings of the wrong length
+/// rather than slicing or padding them.
+pub(crate) fn read_asset_id(bytes: &[u8], argument: &str) -> Result<AssetIdentifier, WasmError> {
+    let invalid = |message: String| {
+        WasmError::new(ErrorCode::InvalidAssetId, message).with_argument(argument)
+    };
+    let bytes: [u8; ID_LENGTH] = bytes
+        .try_into()
+        .map_err(|_| invalid(format!("expected {} bytes, got {}", ID_LENGTH, bytes.len())))?;
+    AssetIdentifier::new(bytes).map_err(|e| invalid(e.to_string()))
+}
+
 #[wasm_bindgen]
 pub struct WasmAsset {
     pub(crate) asset: Asset,
@@ -45,11 +55,31 @@ impl WasmAsset {
         name: String,
         metadata: String,
     ) -> Result<WasmAsset, JsValue> {
+        let _entry = panic_hook::entry!();
         let public_address =
-            PublicAddress::from_hex(&creator_public_address).map_err(WasmIronfishError)?;
+            parse_public_address(&creator_public_address, "creator_public_address")?;
+        for (argument, value, limit) in [
+            ("name", &name, NAME_LENGTH),
+            ("metadata", &metadata, METADATA_LENGTH),
+        ] {
+            if value.len() > limit {
+                return Err(WasmError::new(
+                    ErrorCode::InvalidArgument,
+                    format!(
+                        "{} is {} bytes, at most {} fit in an asset",
+                        argument,
+                        value.len(),
+                        limit
+                    ),
+                )
+                .with_argument(argument)
+                .into());
+            }
+        }
 
         Ok(WasmAsset {
-            asset: Asset::new(public_address, &name, &metadata).map_err(WasmIronfishError)?,
+            asset: Asset::new(public_address, &name, &metadata)
+                .map_err(|e| WasmError::new(ErrorCode::InvalidArgument, e))?,
         })
     }
 
@@ -85,6 +115,7 @@ impl WasmAsset {
 
     #[wasm_bindgen]
     pub fn serialize(&self) -> Result<Vec<u8>, JsValue> {
+        let _entry = panic_hook::entry!();
         let mut cursor: std::io::Cursor<Vec<u8>> = std::io::Cursor::new(vec![]);
         self.asset.write(&mut cursor).map_err(WasmIronfishError)?;
 
@@ -93,8 +124,10 @@ impl WasmAsset {
 
     #[wasm_bindgen]
     pub fn deserialize(bytes: &[u8]) -> Result<WasmAsset, JsValue> {
+        let _entry = panic_hook::entry!();
         let mut cursor: std::io::Cursor<&[u8]> = std::io::Cursor::new(bytes);
-        let asset = Asset::read(&mut cursor).map_err(WasmIronfishError)?;
+        let asset = Asset::read(&mut cursor)
+            .map_err(|e| WasmError::new(ErrorCode::MalformedAsset, e).with_argument("bytes"))?;
 
         Ok(WasmAsset { asset })
     }
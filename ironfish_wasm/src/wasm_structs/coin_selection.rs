@@ -0,0 +1,478 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+use std::collections::BTreeMap;
+
+use ironfish_rust::assets::asset::ID_LENGTH;
+use ironfish_rust::assets::asset_identifier::NATIVE_ASSET;
+use ironfish_rust::Note;
+use js_sys::{Array, Uint8Array};
+use rand_core::{OsRng, RngCore};
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+
+use super::assets::read_asset_id;
+use super::transaction::index_out_of_bounds;
+use super::witness::JsWitness;
+use super::{panic_hook, ErrorCode, WasmError, WasmNote, WasmTransaction};
+
+/// Most spends a transaction may have, as enforced by the wallet.
+pub(crate) const MAX_INPUTS: usize = 10;
+
+/// How notes are chosen when more than one set would pay the targets.
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CoinSelectionStrategy {
+    /// Fewest inputs: the largest notes first, oldest first among equals.
+    LargestFirst,
+    /// The smallest note that covers what is left, else the largest note,
+    /// repeated. Leaves little change and keeps large notes whole.
+    MinimizeChange,
+    /// Notes in random order, so spends don't reveal the wallet's policy.
+    Random,
+}
+
+/// What coin selection needs to know about a candidate note.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct Coin {
+    pub(crate) value: u64,
+    pub(crate) asset_id: [u8; ID_LENGTH],
+    pub(crate) sequence: u32,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct Selection {
+    /// Indices of the chosen coins, grouped by asset.
+    pub(crate) inputs: Vec<usize>,
+    /// Value left over per asset once the targets are paid.
+    pub(crate) change: BTreeMap<[u8; ID_LENGTH], u64>,
+}
+
+fn pick(
+    coins: &[Coin],
+    mut pool: Vec<usize>,
+    target: u64,
+    strategy: CoinSelectionStrategy,
+    rng: &mut impl RngCore,
+) -> Vec<usize> {
+    match strategy {
+        CoinSelectionStrategy::LargestFirst => pool.sort_by(|&a, &b| {
+            (coins[b].value, coins[a].sequence).cmp(&(coins[a].value, coins[b].sequence))
+        }),
+        CoinSelectionStrategy::Random => {
+            for i in (1..pool.len()).rev() {
+                let j = (rng.next_u64() % (i as u64 + 1)) as usize;
+                pool.swap(i, j);
+            }
+        }
+        CoinSelectionStrategy::MinimizeChange => {
+            pool.sort_by_key(|&i| (coins[i].value, coins[i].sequence));
+            let mut picked = Vec::new();
+            let mut remaining = target;
+            while remaining > 0 && !pool.is_empty() {
+                let covering = pool.partition_point(|&i| coins[i].value < remaining);
+                let position = covering.min(pool.len() - 1);
+                let coin = pool.remove(position);
+                remaining = remaining.saturating_sub(coins[coin].value);
+                picked.push(coin);
+            }
+            return picked;
+        }
+    }
+
+    let mut picked = Vec::new();
+    let mut total = 0u64;
+    for coin in pool {
+        if total >= target {
+            break;
+        }
+        total = total.saturating_add(coins[coin].value);
+        picked.push(coin);
+    }
+    picked
+}
+
+/// Chooses coins paying every target, using at most `max_inputs` of them.
+/// Should `strategy` need too many inputs, the fewest possible are used
+/// instead.
+pub(crate) fn select_coins(
+    coins: &[Coin],
+    targets: &BTreeMap<[u8; ID_LENGTH], u64>,
+    max_inputs: usize,
+    strategy: CoinSelectionStrategy,
+    rng: &mut impl RngCore,
+) -> Result<Selection, WasmError> {
+    let mut pools = Vec::new();
+    for (asset_id, &target) in targets.iter().filter(|(_, &target)| target > 0) {
+        let pool: Vec<usize> = (0..coins.len())
+            .filter(|&i| coins[i].asset_id == *asset_id && coins[i].value > 0)
+            .collect();
+        let available = pool
+            .iter()
+            .fold(0u64, |total, &i| total.saturating_add(coins[i].value));
+        if available < target {
+            return Err(WasmError::new(
+                ErrorCode::InsufficientBalance,
+                format!("needs {}, only {} is spendable", target, available),
+            )
+            .with_asset_id(asset_id));
+        }
+        pools.push((*asset_id, pool, target));
+    }
+
+    let choose = |strategy, rng: &mut _| {
+        pools
+            .iter()
+            .map(|(asset_id, pool, target)| {
+                (*asset_id, pick(coins, pool.clone(), *target, strategy, rng))
+            })
+            .collect::<Vec<_>>()
+    };
+    let count = |picked: &[([u8; ID_LENGTH], Vec<usize>)]| -> usize {
+        picked.iter().map(|(_, inputs)| inputs.len()).sum()
+    };
+    let mut picked = choose(strategy, rng);
+    if count(&picked) > max_inputs && strategy != CoinSelectionStrategy::LargestFirst {
+        picked = choose(CoinSelectionStrategy::LargestFirst, rng);
+    }
+    if count(&picked) > max_inputs {
+        let (asset_id, inputs) = picked
+            .iter()
+            .max_by_key(|(_, inputs)| inputs.len())
+            .map(|(asset_id, inputs)| (*asset_id, inputs.len()))
+            .unwrap_or_default();
+        return Err(WasmError::new(
+            ErrorCode::InputLimitExceeded,
+            format!(
+                "needs {} notes in total, {} of this asset, but at most {} can be spent at once",
+                count(&picked),
+                inputs,
+                max_inputs
+            ),
+        )
+        .with_asset_id(&asset_id));
+    }
+
+    let mut selection = Selection {
+        inputs: Vec::new(),
+        change: BTreeMap::new(),
+    };
+    for ((asset_id, inputs), (_, _, target)) in picked.into_iter().zip(&pools) {
+        let total = inputs
+            .iter()
+            .fold(0u64, |total, &i| total.saturating_add(coins[i].value));
+        if total > *target {
+            selection.change.insert(asset_id, total - target);
+        }
+        selection.inputs.extend(inputs);
+    }
+    Ok(selection)
+}
+
+#[derive(Clone)]
+pub(crate) struct Candidate {
+    pub(crate) note: Note,
+    // Kept untyped since extern types aren't Clone
+    pub(crate) witness: JsValue,
+    pub(crate) position: u64,
+    pub(crate) sequence: u32,
+}
+
+impl Candidate {
+    pub(crate) fn witness(&self) -> JsWitness {
+        self.witness.clone().unchecked_into()
+    }
+
+    pub(crate) fn coin(&self) -> Coin {
+        Coin {
+            value: self.note.value(),
+            asset_id: *self.note.asset_id().as_bytes(),
+            sequence: self.sequence,
+        }
+    }
+}
+
+/// Chooses which unspent notes pay a set of per-asset targets plus a fee in
+/// the native asset.
+#[wasm_bindgen]
+pub struct CoinSelector {
+    strategy: CoinSelectionStrategy,
+    max_inputs: usize,
+    min_confirmations: u32,
+    candidates: Vec<Candidate>,
+    targets: BTreeMap<[u8; ID_LENGTH], u64>,
+}
+
+#[wasm_bindgen]
+impl CoinSelector {
+    #[wasm_bindgen(constructor)]
+    pub fn new(strategy: CoinSelectionStrategy) -> CoinSelector {
+        CoinSelector {
+            strategy,
+            max_inputs: MAX_INPUTS,
+            min_confirmations: 0,
+            candidates: Vec::new(),
+            targets: BTreeMap::new(),
+        }
+    }
+
+    /// Most notes to spend, 10 unless changed.
+    #[wasm_bindgen(getter, js_name = "maxInputs")]
+    pub fn max_inputs(&self) -> usize {
+        self.max_inputs
+    }
+
+    #[wasm_bindgen(setter, js_name = "maxInputs")]
+    pub fn set_max_inputs(&mut self, max_inputs: usize) {
+        self.max_inputs = max_inputs;
+    }
+
+    /// Blocks that must have been added on top of a note's block before it
+    /// is spent. Only applied when `select` is given the head sequence.
+    #[wasm_bindgen(getter, js_name = "minConfirmations")]
+    pub fn min_confirmations(&self) -> u32 {
+        self.min_confirmations
+    }
+
+    #[wasm_bindgen(setter, js_name = "minConfirmations")]
+    pub fn set_min_confirmations(&mut self, min_confirmations: u32) {
+        self.min_confirmations = min_confirmations;
+    }
+
+    /// Offers an unspent note, with its witness, its position in the note
+    /// tree and the sequence of the block it was added in.
+    #[wasm_bindgen(js_name = "addCandidate")]
+    pub fn add_candidate(
+        &mut self,
+        note: &WasmNote,
+        witness: JsWitness,
+        position: u64,
+        sequence: u32,
+    ) {
+        self.candidates.push(Candidate {
+            note: note.note.clone(),
+            witness: witness.into(),
+            position,
+            sequence,
+        });
+    }
+
+    /// Adds `value` of an asset to what must be paid.
+    #[wasm_bindgen(js_name = "addTarget")]
+    pub fn add_target(&mut self, asset_id: &[u8], value: u64) -> Result<(), JsValue> {
+        let _entry = panic_hook::entry!();
+
+        let asset_id = *read_asset_id(asset_id, "asset_id")?.as_bytes();
+        add_target(&mut self.targets, asset_id, value)?;
+        Ok(())
+    }
+
+    /// Chooses notes paying the targets and `fee`. When `head_sequence` is
+    /// given, notes without `minConfirmations` confirmations are skipped.
+    #[wasm_bindgen]
+    pub fn select(&self, fee: u64, head_sequence: Option<u32>) -> Result<WasmSpendSet, JsValue> {
+        let _entry = panic_hook::entry!();
+
+        let mut targets = self.targets.clone();
+        add_target(&mut targets, *NATIVE_ASSET.as_bytes(), fee)?;
+        let candidates: Vec<&Candidate> = self
+            .candidates
+            .iter()
+            .filter(|candidate| match head_sequence {
+                Some(head) => head.saturating_sub(candidate.sequence) >= self.min_confirmations,
+                None => true,
+            })
+            .collect();
+        let coins: Vec<Coin> = candidates
+            .iter()
+            .map(|candidate| candidate.coin())
+            .collect();
+        let selection = select_coins(&coins, &targets, self.max_inputs, self.strategy, &mut OsRng)?;
+
+        Ok(WasmSpendSet {
+            spends: selection
+                .inputs
+                .iter()
+                .map(|&i| candidates[i].clone())
+                .collect(),
+            change: selection.change,
+        })
+    }
+}
+
+pub(crate) fn add_target(
+    targets: &mut BTreeMap<[u8; ID_LENGTH], u64>,
+    asset_id: [u8; ID_LENGTH],
+    value: u64,
+) -> Result<(), WasmError> {
+    let target = targets.entry(asset_id).or_default();
+    *target = target.checked_add(value).ok_or_else(|| {
+        WasmError::new(ErrorCode::InvalidArgument, "targets overflow a u64")
+            .with_argument("value")
+            .with_asset_id(&asset_id)
+    })?;
+    Ok(())
+}
+
+/// Notes chosen by a `CoinSelector`, and the change each asset leaves.
+#[wasm_bindgen]
+pub struct WasmSpendSet {
+    pub(crate) spends: Vec<Candidate>,
+    pub(crate) change: BTreeMap<[u8; ID_LENGTH], u64>,
+}
+
+#[wasm_bindgen]
+impl WasmSpendSet {
+    #[wasm_bindgen(getter)]
+    pub fn length(&self) -> usize {
+        self.spends.len()
+    }
+
+    #[wasm_bindgen]
+    pub fn note(&self, index: usize) -> Result<WasmNote, JsValue> {
+        Ok(WasmNote {
+            note: self.spend(index)?.note.clone(),
+        })
+    }
+
+    #[wasm_bindgen]
+    pub fn witness(&self, index: usize) -> Result<JsWitness, JsValue> {
+        Ok(self.spend(index)?.witness())
+    }
+
+    #[wasm_bindgen]
+    pub fn position(&self, index: usize) -> Result<u64, JsValue> {
+        Ok(self.spend(index)?.position)
+    }
+
+    /// Change left in `asset_id` once its target is paid.
+    #[wasm_bindgen]
+    pub fn change(&self, asset_id: &[u8]) -> u64 {
+        asset_id
+            .try_into()
+            .ok()
+            .and_then(|asset_id: [u8; ID_LENGTH]| self.change.get(&asset_id).copied())
+            .unwrap_or(0)
+    }
+
+    /// Assets with change, by id.
+    #[wasm_bindgen(getter, js_name = "changeAssetIds")]
+    pub fn change_asset_ids(&self) -> Array {
+        self.change
+            .keys()
+            .map(|asset_id| JsValue::from(Uint8Array::from(&asset_id[..])))
+            .collect()
+    }
+
+    /// Spends every chosen note in `transaction`.
+    #[wasm_bindgen(js_name = "addToTransaction")]
+    pub fn add_to_transaction(&self, transaction: &mut WasmTransaction) -> Result<(), JsValue> {
+        let _entry = panic_hook::entry!();
+
+        for spend in &self.spends {
+            let note = WasmNote {
+                note: spend.note.clone(),
+            };
+            transaction.spend(&note, spend.witness())?;
+        }
+        Ok(())
+    }
+}
+
+impl WasmSpendSet {
+    fn spend(&self, index: usize) -> Result<&Candidate, WasmError> {
+        self.spends
+            .get(index)
+            .ok_or_else(|| index_out_of_bounds(index, self.spends.len()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::*;
+
+    const IRON: [u8; ID_LENGTH] = [1; ID_LENGTH];
+    const TOKEN: [u8; ID_LENGTH] = [2; ID_LENGTH];
+
+    fn coins(values: &[(u64, [u8; ID_LENGTH])]) -> Vec<Coin> {
+        values
+            .iter()
+            .enumerate()
+            .map(|(sequence, &(value, asset_id))| Coin {
+                value,
+                asset_id,
+                sequence: sequence as u32,
+            })
+            .collect()
+    }
+
+    fn select(
+        coins: &[Coin],
+        targets: &[([u8; ID_LENGTH], u64)],
+        strategy: CoinSelectionStrategy,
+    ) -> Result<Selection, WasmError> {
+        let targets = targets.iter().copied().collect();
+        select_coins(
+            coins,
+            &targets,
+            MAX_INPUTS,
+            strategy,
+            &mut StdRng::seed_from_u64(7),
+        )
+    }
+
+    #[test]
+    fn test_strategies() {
+        let coins = coins(&[(5, IRON), (50, IRON), (20, IRON), (12, IRON), (100, TOKEN)]);
+
+        let selection = select(&coins, &[(IRON, 60)], CoinSelectionStrategy::LargestFirst).unwrap();
+        assert_eq!(selection.inputs, vec![1, 2]);
+        assert_eq!(selection.change.get(&IRON), Some(&10));
+
+        let selection =
+            select(&coins, &[(IRON, 17)], CoinSelectionStrategy::MinimizeChange).unwrap();
+        assert_eq!(selection.inputs, vec![2]);
+        assert_eq!(selection.change.get(&IRON), Some(&3));
+
+        let selection =
+            select(&coins, &[(IRON, 62)], CoinSelectionStrategy::MinimizeChange).unwrap();
+        assert_eq!(selection.inputs, vec![1, 3]);
+        assert!(selection.change.is_empty());
+
+        let selection = select(
+            &coins,
+            &[(IRON, 85), (TOKEN, 1)],
+            CoinSelectionStrategy::Random,
+        )
+        .unwrap();
+        let total: u64 = selection.inputs.iter().map(|&i| coins[i].value).sum();
+        assert_eq!(total, 187);
+        assert_eq!(selection.change.get(&IRON), Some(&2));
+        assert_eq!(selection.change.get(&TOKEN), Some(&99));
+    }
+
+    #[test]
+    fn test_input_limit_and_balance() {
+        let mut values = vec![(1, IRON); 20];
+        values.push((15, IRON));
+        let coins = coins(&values);
+
+        let selection = select(&coins, &[(IRON, 20)], CoinSelectionStrategy::Random).unwrap();
+        assert!(selection.inputs.len() <= MAX_INPUTS);
+        assert!(selection.inputs.contains(&20));
+
+        let error = select(&coins, &[(IRON, 30)], CoinSelectionStrategy::LargestFirst)
+            .err()
+            .unwrap();
+        assert_eq!(error.code, ErrorCode::InputLimitExceeded);
+
+        let error = select(&coins, &[(IRON, 36)], CoinSelectionStrategy::LargestFirst)
+            .err()
+            .unwrap();
+        assert_eq!(error.code, ErrorCode::InsufficientBalance);
+        assert_eq!(error.asset_id, Some(hex::encode(IRON)));
+    }
+}
@@ -0,0 +1,313 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+//! Merging many small notes into few large ones.
+//!
+//! Notes of each asset are merged smallest first, as many per transaction as
+//! the input limit allows. A consolidation has spends and no outputs, so its
+//! change to the spender is the merged note. Notes of other assets than the
+//! native one also spend a native note for the fee, which comes back as
+//! change less the fee.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use ironfish_rust::assets::asset::ID_LENGTH;
+use ironfish_rust::assets::asset_identifier::NATIVE_ASSET;
+use js_sys::Array;
+use wasm_bindgen::prelude::*;
+
+use super::coin_selection::{Candidate, Coin, MAX_INPUTS};
+use super::planning::{
+    consolidation_fee, spending_transaction, take_transactions, PlannedTransaction,
+};
+use super::transaction::index_out_of_bounds;
+use super::witness::JsWitness;
+use super::{panic_hook, WasmNote, WasmSpendingKey, WasmTransaction};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct ConsolidationPlan {
+    pub(crate) transactions: Vec<PlannedTransaction>,
+    pub(crate) notes_after: usize,
+}
+
+/// Plans consolidations of every note worth less than `min_value`, or of
+/// every note when it isn't given. Batches whose merged value would not
+/// cover their fee are left alone.
+pub(crate) fn plan_consolidation(
+    coins: &[Coin],
+    max_inputs: usize,
+    min_value: Option<u64>,
+    version: u8,
+    fee_rate_per_kb: u64,
+) -> ConsolidationPlan {
+    let native = *NATIVE_ASSET.as_bytes();
+    let mut by_asset: BTreeMap<[u8; ID_LENGTH], Vec<usize>> = BTreeMap::new();
+    for (index, coin) in coins.iter().enumerate() {
+        let dust = match min_value {
+            Some(min_value) => coin.value < min_value,
+            None => true,
+        };
+        if dust {
+            by_asset.entry(coin.asset_id).or_default().push(index);
+        }
+    }
+    // Other assets first, so their fees can come from native notes that
+    // aren't being merged
+    let mut assets: Vec<[u8; ID_LENGTH]> = by_asset.keys().copied().collect();
+    assets.sort_by_key(|asset_id| *asset_id == native);
+
+    let mut used = BTreeSet::new();
+    let mut transactions = Vec::new();
+    let mut notes_after = coins.len();
+    for asset_id in assets {
+        let is_native = asset_id == native;
+        let mut notes: Vec<usize> = by_asset[&asset_id]
+            .iter()
+            .copied()
+            .filter(|index| !used.contains(index))
+            .collect();
+        notes.sort_by_key(|&i| (coins[i].value, coins[i].sequence));
+        let batch_size = max_inputs.saturating_sub(usize::from(!is_native));
+        if batch_size < 2 {
+            continue;
+        }
+
+        for batch in notes.chunks(batch_size).filter(|batch| batch.len() >= 2) {
+            let fee = consolidation_fee(batch.len(), is_native, version, fee_rate_per_kb);
+            let merged = batch
+                .iter()
+                .fold(0u64, |total, &i| total.saturating_add(coins[i].value));
+            let mut inputs = batch.to_vec();
+            let mut outputs = 1;
+            if is_native {
+                if merged <= fee {
+                    continue;
+                }
+            } else {
+                // The largest spare native note pays, so dust isn't wasted on fees
+                let Some(payer) = (0..coins.len())
+                    .filter(|i| !used.contains(i))
+                    .filter(|&i| coins[i].asset_id == native && coins[i].value >= fee)
+                    .max_by_key(|&i| coins[i].value)
+                else {
+                    break;
+                };
+                inputs.push(payer);
+                if coins[payer].value > fee {
+                    outputs += 1;
+                }
+            }
+            used.extend(inputs.iter().copied());
+            notes_after = notes_after + outputs - inputs.len();
+            transactions.push(PlannedTransaction { inputs, fee });
+        }
+    }
+
+    ConsolidationPlan {
+        transactions,
+        notes_after,
+    }
+}
+
+/// Plans transactions merging a wallet's small notes, so later payments
+/// stay under the input limit and cheap.
+#[wasm_bindgen]
+pub struct ConsolidationPlanner {
+    fee_rate_per_kb: u64,
+    max_inputs: usize,
+    min_value: Option<u64>,
+    candidates: Vec<Candidate>,
+}
+
+#[wasm_bindgen]
+impl ConsolidationPlanner {
+    #[wasm_bindgen(constructor)]
+    pub fn new(fee_rate_per_kb: u64) -> ConsolidationPlanner {
+        ConsolidationPlanner {
+            fee_rate_per_kb,
+            max_inputs: MAX_INPUTS,
+            min_value: None,
+            candidates: Vec::new(),
+        }
+    }
+
+    /// Most notes a consolidation may spend, 10 unless changed.
+    #[wasm_bindgen(getter, js_name = "maxInputs")]
+    pub fn max_inputs(&self) -> usize {
+        self.max_inputs
+    }
+
+    #[wasm_bindgen(setter, js_name = "maxInputs")]
+    pub fn set_max_inputs(&mut self, max_inputs: usize) {
+        self.max_inputs = max_inputs;
+    }
+
+    /// Only notes worth less than this are merged. Every note is when unset.
+    #[wasm_bindgen(getter, js_name = "minValue")]
+    pub fn min_value(&self) -> Option<u64> {
+        self.min_value
+    }
+
+    #[wasm_bindgen(setter, js_name = "minValue")]
+    pub fn set_min_value(&mut self, min_value: Option<u64>) {
+        self.min_value = min_value;
+    }
+
+    /// Adds an owned, unspent note of any asset, as for
+    /// `CoinSelector.addCandidate`.
+    #[wasm_bindgen(js_name = "addNote")]
+    pub fn add_note(&mut self, note: &WasmNote, witness: JsWitness, position: u64, sequence: u32) {
+        self.candidates.push(Candidate {
+            note: note.note.clone(),
+            witness: witness.into(),
+            position,
+            sequence,
+        });
+    }
+
+    /// Builds the consolidations for `spender`, as transactions of
+    /// `version`.
+    #[wasm_bindgen]
+    pub fn plan(
+        &self,
+        spender: &WasmSpendingKey,
+        version: u8,
+    ) -> Result<WasmConsolidationPlan, JsValue> {
+        let _entry = panic_hook::entry!();
+
+        let coins: Vec<Coin> = self.candidates.iter().map(Candidate::coin).collect();
+        let plan = plan_consolidation(
+            &coins,
+            self.max_inputs,
+            self.min_value,
+            version,
+            self.fee_rate_per_kb,
+        );
+        let mut transactions = Vec::with_capacity(plan.transactions.len());
+        for planned in &plan.transactions {
+            transactions.push(spending_transaction(
+                spender,
+                version,
+                &self.candidates,
+                &planned.inputs,
+            )?);
+        }
+
+        Ok(WasmConsolidationPlan {
+            transactions: Some(transactions),
+            fees: plan
+                .transactions
+                .iter()
+                .map(|planned| planned.fee)
+                .collect(),
+            notes_before: coins.len(),
+            notes_after: plan.notes_after,
+        })
+    }
+}
+
+/// Consolidation transactions, independent of each other. Post each with
+/// its fee and change going back to the spender.
+#[wasm_bindgen]
+pub struct WasmConsolidationPlan {
+    // None once taken
+    transactions: Option<Vec<WasmTransaction>>,
+    fees: Vec<u64>,
+    notes_before: usize,
+    notes_after: usize,
+}
+
+#[wasm_bindgen]
+impl WasmConsolidationPlan {
+    #[wasm_bindgen(getter)]
+    pub fn length(&self) -> usize {
+        self.fees.len()
+    }
+
+    /// The transactions, moved out of the plan, so a second call throws
+    /// with code `UseAfterWipe`.
+    #[wasm_bindgen(js_name = "takeTransactions")]
+    pub fn take_transactions(&mut self) -> Result<Array, JsValue> {
+        take_transactions(&mut self.transactions)
+    }
+
+    /// The fee to post transaction `index` with.
+    #[wasm_bindgen]
+    pub fn fee(&self, index: usize) -> Result<u64, JsValue> {
+        self.fees
+            .get(index)
+            .copied()
+            .ok_or_else(|| index_out_of_bounds(index, self.fees.len()).into())
+    }
+
+    #[wasm_bindgen(getter, js_name = "totalFee")]
+    pub fn total_fee(&self) -> u64 {
+        let _entry = panic_hook::entry!();
+        self.fees.iter().sum()
+    }
+
+    /// Notes the wallet holds now.
+    #[wasm_bindgen(getter, js_name = "notesBefore")]
+    pub fn notes_before(&self) -> usize {
+        self.notes_before
+    }
+
+    /// Notes the wallet will hold once every consolidation is mined.
+    #[wasm_bindgen(getter, js_name = "notesAfter")]
+    pub fn notes_after(&self) -> usize {
+        self.notes_after
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TOKEN: [u8; ID_LENGTH] = [2; ID_LENGTH];
+
+    fn coin(value: u64, asset_id: [u8; ID_LENGTH]) -> Coin {
+        Coin {
+            value,
+            asset_id,
+            sequence: 0,
+        }
+    }
+
+    #[test]
+    fn test_merges_dust_in_batches() {
+        let native = *NATIVE_ASSET.as_bytes();
+        let mut coins = vec![coin(1_000, native); 23];
+        coins.push(coin(1_000_000, native));
+        let plan = plan_consolidation(&coins, MAX_INPUTS, Some(10_000), 2, 1);
+
+        // 23 dust notes: batches of 10, 10 and 3
+        assert_eq!(plan.transactions.len(), 3);
+        assert_eq!(plan.notes_after, 4);
+        assert!(plan
+            .transactions
+            .iter()
+            .all(|planned| !planned.inputs.contains(&23)));
+    }
+
+    #[test]
+    fn test_other_assets_pay_fees_in_native() {
+        let native = *NATIVE_ASSET.as_bytes();
+        let mut coins = vec![coin(5, TOKEN); 4];
+        coins.push(coin(1_000_000, native));
+        let plan = plan_consolidation(&coins, MAX_INPUTS, None, 2, 1);
+
+        assert_eq!(plan.transactions.len(), 1);
+        assert_eq!(plan.transactions[0].inputs, vec![0, 1, 2, 3, 4]);
+        assert_eq!(plan.notes_after, 2);
+    }
+
+    #[test]
+    fn test_skips_notes_worth_less_than_their_fee() {
+        let native = *NATIVE_ASSET.as_bytes();
+        let coins = vec![coin(1, native); 5];
+        let plan = plan_consolidation(&coins, MAX_INPUTS, None, 2, 10);
+        assert!(plan.transactions.is_empty());
+        assert_eq!(plan.notes_after, 5);
+    }
+}
@@ -0,0 +1,270 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+use std::collections::BTreeMap;
+
+use ironfish_rust::assets::asset::{Asset, ID_LENGTH};
+use ironfish_rust::assets::asset_identifier::NATIVE_ASSET;
+use ironfish_rust::{Note, PublicAddress};
+
+use super::fees::TransactionShape;
+use super::{ErrorCode, WasmError};
+
+#[derive(Clone)]
+pub(crate) struct SpendRecord {
+    pub(crate) note: Note,
+    /// Position of the note in the note commitment tree.
+    pub(crate) position: u64,
+    /// Root hash and size of the tree the note's witness was taken from.
+    pub(crate) root_hash: Vec<u8>,
+    pub(crate) tree_size: u32,
+}
+
+#[derive(Clone)]
+pub(crate) struct MintRecord {
+    pub(crate) asset: Asset,
+    pub(crate) value: u64,
+    pub(crate) transfer_ownership_to: Option<PublicAddress>,
+}
+
+/// Value of one asset moving through a transaction.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(crate) struct AssetTotals {
+    pub(crate) spent: u64,
+    pub(crate) minted: u64,
+    pub(crate) output: u64,
+    pub(crate) burned: u64,
+}
+
+impl AssetTotals {
+    pub(crate) fn total_in(&self) -> u64 {
+        self.spent.saturating_add(self.minted)
+    }
+
+    pub(crate) fn total_out(&self) -> u64 {
+        self.output.saturating_add(self.burned)
+    }
+
+    /// Value in less value out, before the fee.
+    pub(crate) fn balance(&self) -> i128 {
+        i128::from(self.total_in()) - i128::from(self.total_out())
+    }
+}
+
+/// One asset's totals and the part of the fee paid in it, which is all of
+/// it for the native asset and none of it otherwise.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct AssetBalance {
+    pub(crate) asset_id: [u8; ID_LENGTH],
+    pub(crate) totals: AssetTotals,
+    pub(crate) fee: u64,
+}
+
+impl AssetBalance {
+    /// Value left over for change, negative when in doesn't cover out and
+    /// the fee.
+    pub(crate) fn change(&self) -> i128 {
+        self.totals.balance() - i128::from(self.fee)
+    }
+
+    /// Value missing for out and the fee to be covered.
+    pub(crate) fn shortfall(&self) -> u64 {
+        u64::try_from((-self.change()).max(0)).unwrap_or(u64::MAX)
+    }
+
+    pub(crate) fn shortfall_error(&self) -> WasmError {
+        WasmError::new(
+            ErrorCode::InsufficientBalance,
+            format!(
+                "needs {}, only {} is spent or minted",
+                self.totals.total_out().saturating_add(self.fee),
+                self.totals.total_in()
+            ),
+        )
+        .with_asset_id(&self.asset_id)
+    }
+}
+
+/// What has been added to a proposed transaction, which keeps it to itself.
+#[derive(Clone, Default)]
+pub(crate) struct TransactionContents {
+    pub(crate) version: u8,
+    pub(crate) expiration: u32,
+    pub(crate) spends: Vec<SpendRecord>,
+    pub(crate) outputs: Vec<Note>,
+    pub(crate) mints: Vec<MintRecord>,
+    pub(crate) burns: Vec<([u8; ID_LENGTH], u64)>,
+}
+
+impl TransactionContents {
+    pub(crate) fn new(version: u8) -> TransactionContents {
+        TransactionContents {
+            version,
+            ..TransactionContents::default()
+        }
+    }
+
+    /// Value spent, minted, output and burned, per asset.
+    pub(crate) fn asset_totals(&self) -> BTreeMap<[u8; ID_LENGTH], AssetTotals> {
+        let mut totals: BTreeMap<[u8; ID_LENGTH], AssetTotals> = BTreeMap::new();
+        for spend in &self.spends {
+            let entry = totals.entry(*spend.note.asset_id().as_bytes()).or_default();
+            entry.spent = entry.spent.saturating_add(spend.note.value());
+        }
+        for mint in &self.mints {
+            let entry = totals.entry(*mint.asset.id().as_bytes()).or_default();
+            entry.minted = entry.minted.saturating_add(mint.value);
+        }
+        for note in &self.outputs {
+            let entry = totals.entry(*note.asset_id().as_bytes()).or_default();
+            entry.output = entry.output.saturating_add(note.value());
+        }
+        for (asset_id, value) in &self.burns {
+            let entry = totals.entry(*asset_id).or_default();
+            entry.burned = entry.burned.saturating_add(*value);
+        }
+        totals
+    }
+
+    /// Spent and minted value less output and burned value, per asset,
+    /// before the fee. `post` sends whatever is positive back as change.
+    pub(crate) fn value_balances(&self) -> BTreeMap<[u8; ID_LENGTH], i128> {
+        self.asset_totals()
+            .into_iter()
+            .map(|(asset_id, totals)| (asset_id, totals.balance()))
+            .collect()
+    }
+
+    /// Each asset's balance once `intended_transaction_fee` is paid. The
+    /// native asset is included whenever there is a fee, moved or not.
+    pub(crate) fn asset_balances(&self, intended_transaction_fee: u64) -> Vec<AssetBalance> {
+        let native = *NATIVE_ASSET.as_bytes();
+        let mut totals = self.asset_totals();
+        if intended_transaction_fee > 0 {
+            totals.entry(native).or_default();
+        }
+        totals
+            .into_iter()
+            .map(|(asset_id, totals)| AssetBalance {
+                asset_id,
+                totals,
+                fee: if asset_id == native {
+                    intended_transaction_fee
+                } else {
+                    0
+                },
+            })
+            .collect()
+    }
+
+    /// Fails as `post` would when some asset can't cover its outputs, burns
+    /// and fee, naming the first such asset and what it is short.
+    pub(crate) fn check_balance(&self, intended_transaction_fee: u64) -> Result<(), WasmError> {
+        let short = self
+            .asset_balances(intended_transaction_fee)
+            .into_iter()
+            .find(|balance| balance.shortfall() > 0);
+        match short {
+            None => Ok(()),
+            Some(balance) => Err(balance.shortfall_error()),
+        }
+    }
+
+    /// The transaction as `post` would build it with this fee, change
+    /// outputs included.
+    pub(crate) fn shape(&self, intended_transaction_fee: u64) -> TransactionShape {
+        let change_outputs = self
+            .asset_balances(intended_transaction_fee)
+            .iter()
+            .filter(|balance| balance.change() > 0)
+            .count();
+        TransactionShape {
+            version: self.version,
+            spends: self.spends.len(),
+            outputs: self.outputs.len() + change_outputs,
+            mints: self.mints.len(),
+            ownership_transfers: self
+                .mints
+                .iter()
+                .filter(|mint| mint.transfer_ownership_to.is_some())
+                .count(),
+            burns: self.burns.len(),
+        }
+    }
+
+    /// The fee paying for the transaction at `fee_rate_per_kb`. When the
+    /// native surplus covers the fee without change but not with it, the
+    /// whole surplus is the fee, since change would make it too small.
+    pub(crate) fn fee(&self, fee_rate_per_kb: u64) -> u64 {
+        let surplus = self
+            .value_balances()
+            .get(NATIVE_ASSET.as_bytes())
+            .copied()
+            .unwrap_or(0);
+        let without_change = self.shape(u64::MAX).fee(fee_rate_per_kb);
+        let with_change = self.shape(0).fee(fee_rate_per_kb);
+        if surplus > i128::from(with_change) {
+            with_change
+        } else if surplus >= i128::from(without_change) {
+            surplus as u64
+        } else {
+            without_change
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fee_without_native_surplus() {
+        let mut contents = TransactionContents::new(2);
+        contents.burns.push(([3; ID_LENGTH], 10));
+
+        // Nothing is left over, so no change outputs are added
+        let shape = contents.shape(0);
+        assert_eq!(shape.outputs, 0);
+        assert_eq!(shape.burns, 1);
+        assert_eq!(contents.value_balances()[&[3; ID_LENGTH]], -10);
+        assert_eq!(contents.fee(1000), shape.size() as u64);
+    }
+
+    #[test]
+    fn test_asset_totals() {
+        let native = *NATIVE_ASSET.as_bytes();
+        let mut contents = TransactionContents::new(2);
+        contents.burns.push(([3; ID_LENGTH], 10));
+        contents.burns.push(([3; ID_LENGTH], 5));
+        contents.burns.push((native, 1));
+
+        let totals = contents.asset_totals();
+        assert_eq!(totals.len(), 2);
+        assert_eq!(totals[&[3; ID_LENGTH]].burned, 15);
+        assert_eq!(totals[&[3; ID_LENGTH]].total_out(), 15);
+        assert_eq!(totals[&native].balance(), -1);
+    }
+
+    #[test]
+    fn test_balance_shortfall() {
+        let native = *NATIVE_ASSET.as_bytes();
+        let mut contents = TransactionContents::new(2);
+        contents.burns.push(([3; ID_LENGTH], 10));
+
+        // The native asset is only short once there's a fee to pay
+        assert_eq!(contents.asset_balances(0).len(), 1);
+        let balances = contents.asset_balances(7);
+        assert_eq!(balances.len(), 2);
+        let balance = |asset_id| {
+            let balance = balances.iter().find(|b| b.asset_id == asset_id).unwrap();
+            (balance.fee, balance.shortfall())
+        };
+        assert_eq!(balance(native), (7, 7));
+        assert_eq!(balance([3; ID_LENGTH]), (0, 10));
+
+        let error = contents.check_balance(0).err().unwrap();
+        assert_eq!(error.code, ErrorCode::InsufficientBalance);
+        assert_eq!(error.asset_id, Some(hex::encode([3; ID_LENGTH])));
+    }
+}
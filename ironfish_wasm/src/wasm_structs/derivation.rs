@@ -0,0 +1,99 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+//! Several accounts from one mnemonic.
+//!
+//! The mnemonic encodes a 32 byte master spending key, which is account 0,
+//! so existing backups keep restoring the account they were made for.
+//! Account `i > 0` has the spending key
+//!
+//! ```text
+//! BLAKE2b-256(key = master, personal = "Ironfish account", data = u32_le(i))
+//! ```
+//!
+//! The derivation is hardened: it is keyed with the master spending key, so
+//! view keys and addresses of one account reveal nothing about another.
+
+use ironfish_rust::keys::SPEND_KEY_SIZE;
+use zeroize::Zeroizing;
+
+use super::mnemonic::{parse_phrase, spending_key_bytes};
+use super::WasmError;
+use crate::LanguageCode;
+
+const ACCOUNT_PERSONALIZATION: &[u8; 16] = b"Ironfish account";
+
+/// The master spending key a mnemonic encodes, validated as `words_to_key`
+/// does.
+pub(crate) fn master_key(
+    words: &str,
+    language_code: Option<LanguageCode>,
+) -> Result<Zeroizing<[u8; SPEND_KEY_SIZE]>, WasmError> {
+    let (language_code, phrase) = parse_phrase(words, language_code)?;
+    spending_key_bytes(&phrase, language_code)
+}
+
+/// The spending key of account `index` under `master`.
+pub(crate) fn derive_spending_key(
+    master: &[u8; SPEND_KEY_SIZE],
+    index: u32,
+) -> Zeroizing<[u8; SPEND_KEY_SIZE]> {
+    let mut key = Zeroizing::new(*master);
+    if index != 0 {
+        let hash = blake2b_simd::Params::new()
+            .hash_length(SPEND_KEY_SIZE)
+            .key(master)
+            .personal(ACCOUNT_PERSONALIZATION)
+            .hash(&index.to_le_bytes());
+        key.copy_from_slice(hash.as_bytes());
+    }
+    key
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_vectors() {
+        let vectors: [([u8; 32], u32, &str); 5] = [
+            (
+                [0; 32],
+                0,
+                "0000000000000000000000000000000000000000000000000000000000000000",
+            ),
+            (
+                [0; 32],
+                1,
+                "d1b0a257f7930774898cf2b91dac5103556def1fe9102a948cec701cd9d8daca",
+            ),
+            (
+                [0; 32],
+                2,
+                "b355562d4d0b18061376c904fec69e472b5f5997e66c8135935d8e187f361595",
+            ),
+            (
+                [0; 32],
+                u32::MAX,
+                "e58a7fd0b40ca7f5349c8a4136914f9769483e7c4734de65040cdb93e7dd98c2",
+            ),
+            (
+                [0x11; 32],
+                1,
+                "bea68837e73b7d0d44f00f312b5287975341dbcf6ace35609a7a228ddc7b63e6",
+            ),
+        ];
+        for (master, index, expected) in vectors {
+            assert_eq!(hex::encode(*derive_spending_key(&master, index)), expected);
+        }
+    }
+
+    #[test]
+    fn test_master_key_from_mnemonic() {
+        let mut words = vec!["abandon"; 23];
+        words.push("art");
+        let master = master_key(&words.join(" "), None).unwrap();
+        assert_eq!(*master, [0; 32]);
+    }
+}
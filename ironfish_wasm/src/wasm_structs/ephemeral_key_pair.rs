@@ -1,6 +1,11 @@
 use wasm_bindgen::prelude::*;
 use js_sys::Uint8Array;
 
+use super::panic_hook;
+
+/// 32 byte secret scalar followed by the 32 byte public point.
+pub(crate) const EPHEMERAL_KEY_PAIR_LENGTH: usize = 64;
+
 #[wasm_bindgen]
 pub struct WasmEphemeralKeyPair {
     pub(crate) key_pair_bytes: Vec<u8>,
@@ -10,6 +15,7 @@ pub struct WasmEphemeralKeyPair {
 impl WasmEphemeralKeyPair {
     #[wasm_bindgen]
     pub fn from_array(v: JsValue) -> Self {
+        let _entry = panic_hook::entry!();
         let array = Uint8Array::new(&v);
         Self { key_pair_bytes: array.to_vec() }
     }
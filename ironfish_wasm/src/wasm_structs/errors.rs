@@ -1,16 +1,412 @@
 use ironfish_rust::errors::IronfishError;
+use std::fmt;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
 
 pub struct WasmIoError(pub std::io::Error);
 pub struct WasmIronfishError(pub IronfishError);
 
+#[wasm_bindgen(typescript_custom_section)]
+const IIRONFISH_ERROR: &'static str = r#"
+/**
+ * Everything thrown is an `Error` with the properties below. `IronfishError`
+ * is a union discriminated on `name`, and each member narrows `code` to the
+ * codes of its class, so checking either narrows the other:
+ *
+ *     if (isIronfishError(e) && e.name === 'BalanceError') e.assetId;
+ */
+interface IronfishErrorProperties extends Error {
+    argument?: string;
+    index?: number;
+    assetId?: string;
+    suggestions?: string[];
+}
+
+interface GeneralError extends IronfishErrorProperties {
+    name: 'IronfishError';
+    code:
+        | ErrorCode.Unknown
+        | ErrorCode.Io
+        | ErrorCode.Poisoned
+        | ErrorCode.UseAfterWipe
+        | ErrorCode.OperationInFlight;
+}
+
+interface AbortError extends IronfishErrorProperties {
+    name: 'AbortError';
+    code: ErrorCode.Aborted;
+}
+
+interface ArgumentError extends IronfishErrorProperties {
+    name: 'ArgumentError';
+    code:
+        | ErrorCode.InvalidArgument
+        | ErrorCode.InvalidPublicAddress
+        | ErrorCode.InvalidSpendingKey
+        | ErrorCode.InvalidViewKey
+        | ErrorCode.InvalidAssetId
+        | ErrorCode.InvalidTransactionVersion
+        | ErrorCode.InvalidMnemonic
+        | ErrorCode.IndexOutOfBounds
+        | ErrorCode.WrongPassword
+        | ErrorCode.UnknownMnemonicWord
+        | ErrorCode.InvalidMnemonicLength
+        | ErrorCode.InvalidMnemonicChecksum
+        | ErrorCode.WrongNetwork;
+    /** Unset when the problem isn't with one argument in particular. */
+    argument?: string;
+}
+
+interface SerializationError extends IronfishErrorProperties {
+    name: 'SerializationError';
+    code:
+        | ErrorCode.MalformedNote
+        | ErrorCode.MalformedProof
+        | ErrorCode.MalformedTransaction
+        | ErrorCode.MalformedAsset
+        | ErrorCode.MalformedWitness
+        | ErrorCode.MalformedKeystore
+        | ErrorCode.MalformedAccount
+        | ErrorCode.MalformedShare
+        | ErrorCode.MalformedSignature
+        | ErrorCode.MalformedPaymentRequest
+        | ErrorCode.MalformedPaymentList;
+}
+
+interface BalanceError extends IronfishErrorProperties {
+    name: 'BalanceError';
+    code: ErrorCode.InsufficientBalance | ErrorCode.InputLimitExceeded;
+    /** Unset for limits, such as on inputs, that aren't about one asset. */
+    assetId?: string;
+}
+
+interface TransactionError extends IronfishErrorProperties {
+    name: 'TransactionError';
+    code:
+        | ErrorCode.TransactionBuildFailed
+        | ErrorCode.ProvingFailed
+        | ErrorCode.DuplicateSpend
+        | ErrorCode.WitnessMismatch
+        | ErrorCode.TransactionExpired
+        | ErrorCode.InvalidMint
+        | ErrorCode.InvalidBurn
+        | ErrorCode.InvalidMemo
+        | ErrorCode.OutputLimitExceeded;
+}
+
+type IronfishError =
+    | GeneralError
+    | AbortError
+    | ArgumentError
+    | SerializationError
+    | BalanceError
+    | TransactionError;
+
+/** Whether `error` was thrown by this module, as an `IronfishError`. */
+export function isIronfishError(error: unknown): error is IronfishError;
+"#;
+
+/// Every `name` an error thrown by this module can have.
+const ERROR_CLASSES: [&str; 6] = [
+    "IronfishError",
+    "AbortError",
+    "ArgumentError",
+    "SerializationError",
+    "BalanceError",
+    "TransactionError",
+];
+
+/// Whether `error` is one this module threw. Its TypeScript signature, a
+/// type guard, is declared with the `IronfishError` union above.
+#[wasm_bindgen(js_name = "isIronfishError", skip_typescript)]
+pub fn is_ironfish_error(error: &JsValue) -> bool {
+    let Some(error) = error.dyn_ref::<js_sys::Error>() else {
+        return false;
+    };
+    let name = String::from(error.name());
+    let code = js_sys::Reflect::get(error, &"code".into()).unwrap_or(JsValue::UNDEFINED);
+    ERROR_CLASSES.contains(&name.as_str()) && code.as_f64().is_some()
+}
+
+/// Stable identifiers for every error this module throws. The numeric values
+/// are part of the public API: append new codes, never renumber.
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ErrorCode {
+    Unknown = 0,
+    Io = 1,
+    Aborted = 2,
+    Poisoned = 3,
+    InvalidArgument = 100,
+    InvalidPublicAddress = 101,
+    InvalidSpendingKey = 102,
+    InvalidViewKey = 103,
+    InvalidAssetId = 104,
+    InvalidTransactionVersion = 105,
+    InvalidMnemonic = 106,
+    IndexOutOfBounds = 107,
+    WrongPassword = 108,
+    UnknownMnemonicWord = 109,
+    InvalidMnemonicLength = 110,
+    InvalidMnemonicChecksum = 111,
+    WrongNetwork = 112,
+    MalformedNote = 200,
+    MalformedProof = 201,
+    MalformedTransaction = 202,
+    MalformedAsset = 203,
+    MalformedWitness = 204,
+    MalformedKeystore = 205,
+    MalformedAccount = 206,
+    MalformedShare = 207,
+    MalformedSignature = 208,
+    MalformedPaymentRequest = 209,
+    MalformedPaymentList = 210,
+    InsufficientBalance = 300,
+    InputLimitExceeded = 301,
+    TransactionBuildFailed = 400,
+    ProvingFailed = 401,
+    DuplicateSpend = 402,
+    WitnessMismatch = 403,
+    TransactionExpired = 404,
+    InvalidMint = 405,
+    InvalidBurn = 406,
+    InvalidMemo = 407,
+    OutputLimitExceeded = 408,
+    UseAfterWipe = 500,
+    OperationInFlight = 501,
+}
+
+impl ErrorCode {
+    /// Name of the JS error class this code belongs to.
+    pub fn class_name(&self) -> &'static str {
+        match self {
+            ErrorCode::Unknown
+            | ErrorCode::Io
+            | ErrorCode::Poisoned
+            | ErrorCode::UseAfterWipe
+            | ErrorCode::OperationInFlight => "IronfishError",
+            ErrorCode::Aborted => "AbortError",
+            ErrorCode::InvalidArgument
+            | ErrorCode::InvalidPublicAddress
+            | ErrorCode::InvalidSpendingKey
+            | ErrorCode::InvalidViewKey
+            | ErrorCode::InvalidAssetId
+            | ErrorCode::InvalidTransactionVersion
+            | ErrorCode::InvalidMnemonic
+            | ErrorCode::IndexOutOfBounds
+            | ErrorCode::WrongPassword
+            | ErrorCode::UnknownMnemonicWord
+            | ErrorCode::InvalidMnemonicLength
+            | ErrorCode::InvalidMnemonicChecksum
+            | ErrorCode::WrongNetwork => "ArgumentError",
+            ErrorCode::MalformedNote
+            | ErrorCode::MalformedProof
+            | ErrorCode::MalformedTransaction
+            | ErrorCode::MalformedAsset
+            | ErrorCode::MalformedWitness
+            | ErrorCode::MalformedKeystore
+            | ErrorCode::MalformedAccount
+            | ErrorCode::MalformedShare
+            | ErrorCode::MalformedSignature
+            | ErrorCode::MalformedPaymentRequest
+            | ErrorCode::MalformedPaymentList => "SerializationError",
+            ErrorCode::InsufficientBalance | ErrorCode::InputLimitExceeded => "BalanceError",
+            ErrorCode::TransactionBuildFailed
+            | ErrorCode::ProvingFailed
+            | ErrorCode::DuplicateSpend
+            | ErrorCode::WitnessMismatch
+            | ErrorCode::TransactionExpired
+            | ErrorCode::InvalidMint
+            | ErrorCode::InvalidBurn
+            | ErrorCode::InvalidMemo
+            | ErrorCode::OutputLimitExceeded => "TransactionError",
+        }
+    }
+}
+
+/// An error carrying a stable code plus whatever context is known about the
+/// failing input. Converts into a JS `Error` whose `name` is the error class
+/// and which has `code`, `argument`, `index`, `assetId` and `suggestions`
+/// properties.
+#[derive(Clone, Debug)]
+pub struct WasmError {
+    pub code: ErrorCode,
+    pub message: String,
+    pub argument: Option<String>,
+    pub index: Option<usize>,
+    pub asset_id: Option<String>,
+    pub suggestions: Vec<String>,
+}
+
+impl WasmError {
+    pub fn new(code: ErrorCode, message: impl ToString) -> Self {
+        WasmError {
+            code,
+            message: message.to_string(),
+            argument: None,
+            index: None,
+            asset_id: None,
+            suggestions: Vec::new(),
+        }
+    }
+
+    /// Name of the exported function's argument that was rejected.
+    pub fn with_argument(mut self, argument: &str) -> Self {
+        self.argument = Some(argument.to_string());
+        self
+    }
+
+    /// Position of the rejected element when the argument is a list.
+    pub fn with_index(mut self, index: usize) -> Self {
+        self.index = Some(index);
+        self
+    }
+
+    pub fn with_asset_id(mut self, asset_id: &[u8]) -> Self {
+        self.asset_id = Some(hex::encode(asset_id));
+        self
+    }
+
+    /// Likely intended values for the rejected input, closest first.
+    pub fn with_suggestions(mut self, suggestions: Vec<String>) -> Self {
+        self.suggestions = suggestions;
+        self
+    }
+}
+
+impl fmt::Display for WasmError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.argument {
+            Some(argument) => write!(f, "{}: {}", argument, self.message),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+impl From<WasmError> for JsValue {
+    fn from(e: WasmError) -> Self {
+        let error = js_sys::Error::new(&e.to_string());
+        error.set_name(e.code.class_name());
+
+        let set = |key: &str, value: JsValue| {
+            let _ = js_sys::Reflect::set(&error, &key.into(), &value);
+        };
+        set("code", JsValue::from(e.code as u32));
+        if let Some(argument) = e.argument {
+            set("argument", argument.into());
+        }
+        if let Some(index) = e.index {
+            set("index", JsValue::from(index as u32));
+        }
+        if let Some(asset_id) = e.asset_id {
+            set("assetId", asset_id.into());
+        }
+        if !e.suggestions.is_empty() {
+            let suggestions: js_sys::Array = e.suggestions.iter().map(JsValue::from).collect();
+            set("suggestions", suggestions.into());
+        }
+
+        error.into()
+    }
+}
+
+impl From<WasmIoError> for WasmError {
+    fn from(e: WasmIoError) -> Self {
+        WasmError::new(ErrorCode::Io, e.0)
+    }
+}
+
+impl From<WasmIronfishError> for WasmError {
+    fn from(e: WasmIronfishError) -> Self {
+        WasmError::new(ErrorCode::Unknown, e.0)
+    }
+}
+
 impl From<WasmIoError> for wasm_bindgen::JsValue {
     fn from(e: WasmIoError) -> Self {
-        js_sys::Error::new(&e.0.to_string()).into()
+        WasmError::from(e).into()
     }
 }
 
 impl From<WasmIronfishError> for wasm_bindgen::JsValue {
     fn from(e: WasmIronfishError) -> Self {
-        js_sys::Error::new(&e.0.to_string()).into()
+        WasmError::from(e).into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_typescript_union_matches_classes() {
+        let codes = [
+            ErrorCode::Unknown,
+            ErrorCode::Io,
+            ErrorCode::Aborted,
+            ErrorCode::Poisoned,
+            ErrorCode::InvalidArgument,
+            ErrorCode::InvalidPublicAddress,
+            ErrorCode::InvalidSpendingKey,
+            ErrorCode::InvalidViewKey,
+            ErrorCode::InvalidAssetId,
+            ErrorCode::InvalidTransactionVersion,
+            ErrorCode::InvalidMnemonic,
+            ErrorCode::IndexOutOfBounds,
+            ErrorCode::WrongPassword,
+            ErrorCode::UnknownMnemonicWord,
+            ErrorCode::InvalidMnemonicLength,
+            ErrorCode::InvalidMnemonicChecksum,
+            ErrorCode::WrongNetwork,
+            ErrorCode::MalformedNote,
+            ErrorCode::MalformedProof,
+            ErrorCode::MalformedTransaction,
+            ErrorCode::MalformedAsset,
+            ErrorCode::MalformedWitness,
+            ErrorCode::MalformedKeystore,
+            ErrorCode::MalformedAccount,
+            ErrorCode::MalformedShare,
+            ErrorCode::MalformedSignature,
+            ErrorCode::MalformedPaymentRequest,
+            ErrorCode::MalformedPaymentList,
+            ErrorCode::InsufficientBalance,
+            ErrorCode::InputLimitExceeded,
+            ErrorCode::TransactionBuildFailed,
+            ErrorCode::ProvingFailed,
+            ErrorCode::DuplicateSpend,
+            ErrorCode::WitnessMismatch,
+            ErrorCode::TransactionExpired,
+            ErrorCode::InvalidMint,
+            ErrorCode::InvalidBurn,
+            ErrorCode::InvalidMemo,
+            ErrorCode::OutputLimitExceeded,
+            ErrorCode::UseAfterWipe,
+            ErrorCode::OperationInFlight,
+        ];
+        // The custom section const is consumed by wasm_bindgen, so read it
+        // from the source
+        let source = include_str!("errors.rs");
+        let typescript = &source[..source.find("/// Every `name`").unwrap()];
+        let interfaces: Vec<&str> = typescript.split("\ninterface ").collect();
+        for code in codes {
+            let class = code.class_name();
+            assert!(ERROR_CLASSES.contains(&class));
+            let member = format!("ErrorCode.{:?}", code);
+            let declared_in: Vec<&&str> = interfaces
+                .iter()
+                .filter(|interface| {
+                    interface
+                        .split(|c: char| c.is_whitespace() || c == '|' || c == ';')
+                        .any(|token| token == member)
+                })
+                .collect();
+            assert_eq!(declared_in.len(), 1, "{:?} is declared once", code);
+            assert!(
+                declared_in[0].contains(&format!("name: '{}';", class)),
+                "{:?} is declared in {}",
+                code,
+                class
+            );
+        }
     }
 }
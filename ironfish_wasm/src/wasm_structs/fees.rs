@@ -0,0 +1,127 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+//! Serialized transaction sizes, from the same constants the serializer
+//! uses, and the fees they cost at a given rate.
+//!
+//! ```text
+//!   1 version
+//!  32 spend, output, mint and burn counts, 8 bytes each
+//!   8 fee
+//!   4 expiration
+//!  32 randomized public key
+//!     spends:  proof, value commitment, randomized public key, root hash,
+//!              tree size, nullifier, authorizing signature
+//!     outputs: proof, encrypted note
+//!     mints:   proof, asset, value, [v2: owner, transfer option], signature
+//!     burns:   asset id, value
+//!  64 binding signature
+//! ```
+
+use ironfish_rust::assets::asset::{ASSET_LENGTH, ID_LENGTH};
+use ironfish_rust::note::AMOUNT_VALUE_SIZE;
+use ironfish_rust::transaction::outputs::PROOF_SIZE;
+use ironfish_rust::transaction::{
+    TRANSACTION_EXPIRATION_SIZE, TRANSACTION_FEE_SIZE, TRANSACTION_PUBLIC_KEY_SIZE,
+    TRANSACTION_SIGNATURE_SIZE,
+};
+
+use super::get_encrypted_note_length;
+
+const VERSION_SIZE: usize = 1;
+const COUNT_SIZE: usize = 8;
+const POINT_SIZE: usize = 32;
+const TREE_SIZE_SIZE: usize = 4;
+const NULLIFIER_SIZE: usize = 32;
+const SIGNATURE_SIZE: usize = 64;
+
+/// What a transaction contains, as far as its size is concerned.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(crate) struct TransactionShape {
+    pub(crate) version: u8,
+    pub(crate) spends: usize,
+    pub(crate) outputs: usize,
+    pub(crate) mints: usize,
+    /// Mints that also transfer ownership of their asset, counted in `mints`.
+    pub(crate) ownership_transfers: usize,
+    pub(crate) burns: usize,
+}
+
+pub(crate) fn spend_size() -> usize {
+    PROOF_SIZE as usize
+        + POINT_SIZE // value commitment
+        + POINT_SIZE // randomized public key
+        + POINT_SIZE // root hash
+        + TREE_SIZE_SIZE
+        + NULLIFIER_SIZE
+        + SIGNATURE_SIZE
+}
+
+pub(crate) fn output_size() -> usize {
+    PROOF_SIZE as usize + get_encrypted_note_length() as usize
+}
+
+fn mint_size(version: u8) -> usize {
+    let owner = if version >= 2 { POINT_SIZE + 1 } else { 0 };
+    PROOF_SIZE as usize + ASSET_LENGTH + AMOUNT_VALUE_SIZE + owner + SIGNATURE_SIZE
+}
+
+fn burn_size() -> usize {
+    ID_LENGTH + AMOUNT_VALUE_SIZE
+}
+
+impl TransactionShape {
+    pub(crate) fn size(&self) -> usize {
+        VERSION_SIZE
+            + 4 * COUNT_SIZE
+            + TRANSACTION_FEE_SIZE
+            + TRANSACTION_EXPIRATION_SIZE
+            + TRANSACTION_PUBLIC_KEY_SIZE
+            + self.spends * spend_size()
+            + self.outputs * output_size()
+            + self.mints * mint_size(self.version)
+            + self.ownership_transfers * POINT_SIZE
+            + self.burns * burn_size()
+            + TRANSACTION_SIGNATURE_SIZE
+    }
+
+    pub(crate) fn fee(&self, fee_rate_per_kb: u64) -> u64 {
+        fee_for_size(self.size(), fee_rate_per_kb)
+    }
+}
+
+/// The fee for `size` bytes at `fee_rate_per_kb`, rounded up to whole ore.
+pub(crate) fn fee_for_size(size: usize, fee_rate_per_kb: u64) -> u64 {
+    (size as u64).saturating_mul(fee_rate_per_kb).div_ceil(1000)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sizes() {
+        assert_eq!(spend_size(), 388);
+        let empty = TransactionShape {
+            version: 1,
+            ..TransactionShape::default()
+        };
+        let payment = TransactionShape {
+            spends: 2,
+            outputs: 2,
+            ..empty
+        };
+        assert_eq!(payment.size(), empty.size() + 2 * 388 + 2 * output_size());
+        let mint = |version| TransactionShape {
+            version,
+            mints: 1,
+            ..TransactionShape::default()
+        };
+        assert_eq!(mint(2).size() - mint(1).size(), 33);
+
+        assert_eq!(fee_for_size(1000, 1), 1);
+        assert_eq!(fee_for_size(1001, 1), 2);
+        assert_eq!(fee_for_size(0, 10), 0);
+    }
+}
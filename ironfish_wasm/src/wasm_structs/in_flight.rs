@@ -0,0 +1,162 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+//! Ownership of a value shared between a JS handle and the async calls it
+//! starts. An async call takes the value out for as long as it runs, so
+//! calls made through the handle meanwhile fail instead of changing it
+//! between two steps of the async one.
+
+use std::cell::{RefCell, RefMut};
+use std::rc::Rc;
+
+use super::{ErrorCode, WasmError};
+
+enum State<T> {
+    Ready(T),
+    /// Taken by the named async call until it settles.
+    InFlight(&'static str),
+    Wiped,
+}
+
+pub(crate) struct Slot<T> {
+    state: Rc<RefCell<State<T>>>,
+}
+
+impl<T> Slot<T> {
+    pub(crate) fn new(value: T) -> Slot<T> {
+        Slot {
+            state: Rc::new(RefCell::new(State::Ready(value))),
+        }
+    }
+
+    /// The value, unless it is wiped or an async call has it.
+    pub(crate) fn get_mut(&self) -> Result<RefMut<'_, T>, WasmError> {
+        RefMut::filter_map(self.state.borrow_mut(), |state| match state {
+            State::Ready(value) => Some(value),
+            _ => None,
+        })
+        .map_err(|state| unavailable(&*state))
+    }
+
+    pub(crate) fn is_wiped(&self) -> bool {
+        matches!(*self.state.borrow(), State::Wiped)
+    }
+
+    /// Drops the value. An async call that has it drops it at its next step.
+    pub(crate) fn wipe(&self) {
+        *self.state.borrow_mut() = State::Wiped;
+    }
+
+    /// Takes the value out for the async call `operation`. It goes back in
+    /// when the returned `Taken` is dropped, unless wiped meanwhile.
+    pub(crate) fn take(&self, operation: &'static str) -> Result<Taken<T>, WasmError> {
+        let mut state = self.state.borrow_mut();
+        match std::mem::replace(&mut *state, State::InFlight(operation)) {
+            State::Ready(value) => Ok(Taken {
+                state: self.state.clone(),
+                value: Some(value),
+            }),
+            other => {
+                *state = other;
+                Err(unavailable(&*state))
+            }
+        }
+    }
+}
+
+pub(crate) struct Taken<T> {
+    state: Rc<RefCell<State<T>>>,
+    value: Option<T>,
+}
+
+impl<T> Taken<T> {
+    /// The value, unless the slot it was taken from was wiped meanwhile.
+    pub(crate) fn get_mut(&mut self) -> Result<&mut T, WasmError> {
+        if matches!(*self.state.borrow(), State::Wiped) {
+            self.value = None;
+        }
+        self.value
+            .as_mut()
+            .ok_or_else(|| unavailable(&State::<T>::Wiped))
+    }
+
+    /// Drops the value and wipes the slot, for when a failed call left the
+    /// value unfit for further use.
+    pub(crate) fn wipe(&mut self) {
+        self.value = None;
+        *self.state.borrow_mut() = State::Wiped;
+    }
+}
+
+impl<T> Drop for Taken<T> {
+    fn drop(&mut self) {
+        let mut state = self.state.borrow_mut();
+        if let (State::InFlight(_), Some(value)) = (&*state, self.value.take()) {
+            *state = State::Ready(value);
+        }
+    }
+}
+
+fn unavailable<T>(state: &State<T>) -> WasmError {
+    match state {
+        State::InFlight(operation) => WasmError::new(
+            ErrorCode::OperationInFlight,
+            format!("transaction is in use by {} until it settles", operation),
+        ),
+        _ => WasmError::new(ErrorCode::UseAfterWipe, "transaction has been wiped"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_taken_value_is_unavailable_until_dropped() {
+        let slot = Slot::new(1);
+        let mut taken = slot.take("post_async").ok().unwrap();
+        *taken.get_mut().ok().unwrap() += 1;
+
+        assert_eq!(
+            slot.get_mut().err().unwrap().code,
+            ErrorCode::OperationInFlight
+        );
+        assert_eq!(
+            slot.take("build_circuits_async").err().unwrap().code,
+            ErrorCode::OperationInFlight
+        );
+        assert!(!slot.is_wiped());
+
+        drop(taken);
+        assert_eq!(*slot.get_mut().ok().unwrap(), 2);
+    }
+
+    #[test]
+    fn test_wipe_reaches_taken_value() {
+        let slot = Slot::new(1);
+        let mut taken = slot.take("post_async").ok().unwrap();
+        slot.wipe();
+
+        assert_eq!(taken.get_mut().err().unwrap().code, ErrorCode::UseAfterWipe);
+        drop(taken);
+        assert!(slot.is_wiped());
+        assert_eq!(slot.get_mut().err().unwrap().code, ErrorCode::UseAfterWipe);
+        assert_eq!(
+            slot.take("post_async").err().unwrap().code,
+            ErrorCode::UseAfterWipe
+        );
+    }
+
+    #[test]
+    fn test_failed_call_wipes_taken_value() {
+        let slot = Slot::new(1);
+        let mut taken = slot.take("post_async").ok().unwrap();
+        taken.wipe();
+        assert_eq!(taken.get_mut().err().unwrap().code, ErrorCode::UseAfterWipe);
+
+        drop(taken);
+        assert!(slot.is_wiped());
+        assert_eq!(slot.get_mut().err().unwrap().code, ErrorCode::UseAfterWipe);
+    }
+}
@@ -0,0 +1,496 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+use argon2::{Algorithm, Argon2, Params, Version};
+use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+use chacha20poly1305::{XChaCha20Poly1305, XNonce};
+use rand_core::{OsRng, RngCore};
+use wasm_bindgen::prelude::*;
+use zeroize::{Zeroize, Zeroizing};
+
+use super::{panic_hook, ErrorCode, WasmError, WasmSpendingKey};
+use crate::{Key, LanguageCode};
+
+//  4 magic "IFKS"
+//+ 1 format version
+//+ 1 secret kind
+//+ 1 mnemonic language
+//+ 1 kdf
+//+ 4 argon2 memory cost in KiB
+//+ 4 argon2 iterations
+//+ 4 argon2 parallelism
+//+ 16 salt
+//+ 24 nonce
+//= 60 byte header, authenticated as associated data, then the ciphertext
+const MAGIC: &[u8; 4] = b"IFKS";
+const FORMAT_VERSION: u8 = 1;
+const KDF_ARGON2ID: u8 = 1;
+const SALT_LENGTH: usize = 16;
+const NONCE_LENGTH: usize = 24;
+const HEADER_LENGTH: usize = 4 + 1 + 1 + 1 + 1 + 4 + 4 + 4 + SALT_LENGTH + NONCE_LENGTH;
+
+// OWASP's recommended argon2id parameters. Decryption refuses anything above
+// the maximums so a crafted keystore can't make us allocate without bound.
+const DEFAULT_MEMORY_KIB: u32 = 19 * 1024;
+const DEFAULT_ITERATIONS: u32 = 2;
+const MAX_MEMORY_KIB: u32 = 1024 * 1024;
+const MAX_ITERATIONS: u32 = 64;
+const MAX_PARALLELISM: u32 = 16;
+
+/// What a keystore holds. The discriminants are stored in the header.
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum KeystoreKind {
+    SpendingKey = 0,
+    Mnemonic = 1,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct KdfParams {
+    memory_kib: u32,
+    iterations: u32,
+    parallelism: u32,
+}
+
+impl Default for KdfParams {
+    fn default() -> Self {
+        KdfParams {
+            memory_kib: DEFAULT_MEMORY_KIB,
+            iterations: DEFAULT_ITERATIONS,
+            parallelism: 1,
+        }
+    }
+}
+
+impl KdfParams {
+    fn validate(&self) -> Result<Params, WasmError> {
+        if self.memory_kib > MAX_MEMORY_KIB
+            || self.iterations > MAX_ITERATIONS
+            || self.parallelism > MAX_PARALLELISM
+        {
+            return Err(WasmError::new(
+                ErrorCode::InvalidArgument,
+                "key derivation parameters exceed the supported maximum",
+            ));
+        }
+        Params::new(self.memory_kib, self.iterations, self.parallelism, Some(32))
+            .map_err(|e| WasmError::new(ErrorCode::InvalidArgument, e))
+    }
+
+    fn derive_key(&self, password: &[u8], salt: &[u8]) -> Result<Zeroizing<[u8; 32]>, WasmError> {
+        let argon2 = Argon2::new(Algorithm::Argon2id, Version::V0x13, self.validate()?);
+        let mut key = Zeroizing::new([0u8; 32]);
+        argon2
+            .hash_password_into(password, salt, key.as_mut())
+            .map_err(|e| WasmError::new(ErrorCode::InvalidArgument, e))?;
+        Ok(key)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct KeystoreHeader {
+    version: u8,
+    kind: KeystoreKind,
+    language: u8,
+    kdf: KdfParams,
+    salt: [u8; SALT_LENGTH],
+    nonce: [u8; NONCE_LENGTH],
+}
+
+impl KeystoreHeader {
+    fn write(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(HEADER_LENGTH);
+        bytes.extend_from_slice(MAGIC);
+        bytes.push(self.version);
+        bytes.push(self.kind as u8);
+        bytes.push(self.language);
+        bytes.push(KDF_ARGON2ID);
+        bytes.extend_from_slice(&self.kdf.memory_kib.to_le_bytes());
+        bytes.extend_from_slice(&self.kdf.iterations.to_le_bytes());
+        bytes.extend_from_slice(&self.kdf.parallelism.to_le_bytes());
+        bytes.extend_from_slice(&self.salt);
+        bytes.extend_from_slice(&self.nonce);
+        bytes
+    }
+
+    fn read(bytes: &[u8]) -> Result<KeystoreHeader, WasmError> {
+        let malformed = |message: &str| WasmError::new(ErrorCode::MalformedKeystore, message);
+        if bytes.len() < HEADER_LENGTH || &bytes[0..4] != MAGIC {
+            return Err(malformed("not an Iron Fish keystore"));
+        }
+        if bytes[4] != FORMAT_VERSION {
+            return Err(malformed(&format!(
+                "unsupported keystore version {}",
+                bytes[4]
+            )));
+        }
+        let kind = match bytes[5] {
+            0 => KeystoreKind::SpendingKey,
+            1 => KeystoreKind::Mnemonic,
+            _ => return Err(malformed("unknown secret kind")),
+        };
+        if bytes[7] != KDF_ARGON2ID {
+            return Err(malformed("unknown key derivation function"));
+        }
+        let u32_at = |offset: usize| {
+            let mut word = [0u8; 4];
+            word.copy_from_slice(&bytes[offset..offset + 4]);
+            u32::from_le_bytes(word)
+        };
+        let mut salt = [0u8; SALT_LENGTH];
+        salt.copy_from_slice(&bytes[20..20 + SALT_LENGTH]);
+        let mut nonce = [0u8; NONCE_LENGTH];
+        nonce.copy_from_slice(&bytes[20 + SALT_LENGTH..HEADER_LENGTH]);
+        let kdf = KdfParams {
+            memory_kib: u32_at(8),
+            iterations: u32_at(12),
+            parallelism: u32_at(16),
+        };
+        // Checked here too, since out of range parameters in a stored header
+        // mean the keystore is corrupt rather than that an argument is wrong
+        kdf.validate().map_err(|e| malformed(&e.message))?;
+
+        Ok(KeystoreHeader {
+            version: bytes[4],
+            kind,
+            language: bytes[6],
+            kdf,
+            salt,
+            nonce,
+        })
+    }
+}
+
+/// Encrypts `secret` under `password`, returning the header followed by the
+/// ciphertext.
+pub(crate) fn seal(
+    kind: KeystoreKind,
+    language: u8,
+    secret: &[u8],
+    password: &[u8],
+    kdf: KdfParams,
+) -> Result<Vec<u8>, WasmError> {
+    let mut header = KeystoreHeader {
+        version: FORMAT_VERSION,
+        kind,
+        language,
+        kdf,
+        salt: [0; SALT_LENGTH],
+        nonce: [0; NONCE_LENGTH],
+    };
+    OsRng.fill_bytes(&mut header.salt);
+    OsRng.fill_bytes(&mut header.nonce);
+
+    let key = kdf.derive_key(password, &header.salt)?;
+    let mut bytes = header.write();
+    let ciphertext = XChaCha20Poly1305::new(key.as_ref().into())
+        .encrypt(
+            XNonce::from_slice(&header.nonce),
+            Payload {
+                msg: secret,
+                aad: &bytes,
+            },
+        )
+        .map_err(|_| WasmError::new(ErrorCode::Unknown, "encryption failed"))?;
+    bytes.extend_from_slice(&ciphertext);
+    Ok(bytes)
+}
+
+/// Decrypts a keystore produced by `seal`. A wrong password and a tampered
+/// keystore are indistinguishable, and both fail the AEAD tag check.
+pub(crate) fn open(
+    bytes: &[u8],
+    password: &[u8],
+) -> Result<(KeystoreHeader, Zeroizing<Vec<u8>>), WasmError> {
+    let header = KeystoreHeader::read(bytes)?;
+    let key = header.kdf.derive_key(password, &header.salt)?;
+    let plaintext = XChaCha20Poly1305::new(key.as_ref().into())
+        .decrypt(
+            XNonce::from_slice(&header.nonce),
+            Payload {
+                msg: &bytes[HEADER_LENGTH..],
+                aad: &bytes[..HEADER_LENGTH],
+            },
+        )
+        .map_err(|_| {
+            WasmError::new(
+                ErrorCode::WrongPassword,
+                "wrong password, or the keystore has been modified",
+            )
+            .with_argument("password")
+        })?;
+    Ok((header, Zeroizing::new(plaintext)))
+}
+
+/// A spending key or mnemonic encrypted with a password, using argon2id to
+/// derive the key for XChaCha20-Poly1305. `serialize()` gives the bytes to
+/// store; `new WasmKeystore(bytes)` reads them back.
+#[wasm_bindgen]
+pub struct WasmKeystore {
+    header: KeystoreHeader,
+    bytes: Vec<u8>,
+}
+
+#[wasm_bindgen]
+impl WasmKeystore {
+    #[wasm_bindgen(constructor)]
+    pub fn new(bytes: &[u8]) -> Result<WasmKeystore, JsValue> {
+        let _entry = panic_hook::entry!();
+
+        let header = KeystoreHeader::read(bytes).map_err(|e| e.with_argument("bytes"))?;
+        Ok(WasmKeystore {
+            header,
+            bytes: bytes.to_vec(),
+        })
+    }
+
+    /// Encrypts a spending key handle. `memory_kib` and `iterations` default
+    /// to argon2id's recommended 19 MiB and 2 passes.
+    #[wasm_bindgen(js_name = "fromSpendingKey")]
+    pub fn from_spending_key(
+        key: &WasmSpendingKey,
+        mut password: String,
+        memory_kib: Option<u32>,
+        iterations: Option<u32>,
+    ) -> Result<WasmKeystore, JsValue> {
+        let _entry = panic_hook::entry!();
+
+        let secret = Zeroizing::new(key.sapling_key()?.spending_key());
+        let bytes = seal(
+            KeystoreKind::SpendingKey,
+            0,
+            secret.as_ref(),
+            password.as_bytes(),
+            kdf_params(memory_kib, iterations),
+        );
+        password.zeroize();
+        WasmKeystore::new(&bytes?)
+    }
+
+    /// Encrypts the spending key of a `Key` from `generateKey` or
+    /// `generatePublicAddress`.
+    #[wasm_bindgen(js_name = "fromKey")]
+    pub fn from_key(
+        key: &Key,
+        password: String,
+        memory_kib: Option<u32>,
+        iterations: Option<u32>,
+    ) -> Result<WasmKeystore, JsValue> {
+        let _entry = panic_hook::entry!();
+        let key = WasmSpendingKey::from_hex(key.spending_key_hex()?.to_string())?;
+        WasmKeystore::from_spending_key(&key, password, memory_kib, iterations)
+    }
+
+    /// Encrypts a mnemonic phrase. The phrase is checked, and its language
+    /// recorded, so it can be turned back into a key after decryption.
+    #[wasm_bindgen(js_name = "fromMnemonic")]
+    pub fn from_mnemonic(
+        words: String,
+        language_code: LanguageCode,
+        mut password: String,
+        memory_kib: Option<u32>,
+        iterations: Option<u32>,
+    ) -> Result<WasmKeystore, JsValue> {
+        let _entry = panic_hook::entry!();
+
+        let words = Zeroizing::new(words);
+        let language = language_code as u8;
+        let checked = WasmSpendingKey::from_phrase(&words, language_code);
+        let bytes = checked.map(|_| {
+            seal(
+                KeystoreKind::Mnemonic,
+                language,
+                words.as_bytes(),
+                password.as_bytes(),
+                kdf_params(memory_kib, iterations),
+            )
+        });
+        password.zeroize();
+        WasmKeystore::new(&bytes??)
+    }
+
+    #[wasm_bindgen]
+    pub fn serialize(&self) -> Vec<u8> {
+        self.bytes.clone()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn version(&self) -> u8 {
+        self.header.version
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn kind(&self) -> KeystoreKind {
+        self.header.kind
+    }
+
+    #[wasm_bindgen(getter, js_name = "memoryKib")]
+    pub fn memory_kib(&self) -> u32 {
+        self.header.kdf.memory_kib
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn iterations(&self) -> u32 {
+        self.header.kdf.iterations
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn parallelism(&self) -> u32 {
+        self.header.kdf.parallelism
+    }
+
+    /// Decrypts into a spending key handle, deriving it from the phrase for
+    /// mnemonic keystores. Throws a `WrongPassword` error on a bad password.
+    #[wasm_bindgen(js_name = "toSpendingKey")]
+    pub fn to_spending_key(&self, mut password: String) -> Result<WasmSpendingKey, JsValue> {
+        let _entry = panic_hook::entry!();
+
+        let opened = open(&self.bytes, password.as_bytes());
+        password.zeroize();
+        let (header, plaintext) = opened?;
+        match header.kind {
+            KeystoreKind::SpendingKey => WasmSpendingKey::from_hex(hex::encode(&*plaintext)),
+            KeystoreKind::Mnemonic => {
+                let words = std::str::from_utf8(&plaintext).map_err(|_| {
+                    WasmError::new(ErrorCode::MalformedKeystore, "mnemonic is not valid UTF-8")
+                })?;
+                let language_code = language_code(header.language)?;
+                Ok(WasmSpendingKey::from_phrase(words, language_code)?)
+            }
+        }
+    }
+
+    /// Decrypts into the same `Key` that `generatePublicAddress` returns.
+    #[wasm_bindgen(js_name = "toKey")]
+    pub fn to_key(&self, password: String) -> Result<Key, JsValue> {
+        let _entry = panic_hook::entry!();
+        let mut spending_key = self.to_spending_key(password)?.to_hex()?;
+        let key = crate::create_public_key_to_js(&spending_key);
+        spending_key.zeroize();
+        key
+    }
+
+    /// Decrypts the phrase of a mnemonic keystore.
+    #[wasm_bindgen(js_name = "toMnemonic")]
+    pub fn to_mnemonic(&self, mut password: String) -> Result<String, JsValue> {
+        let _entry = panic_hook::entry!();
+
+        if self.header.kind != KeystoreKind::Mnemonic {
+            return Err(WasmError::new(
+                ErrorCode::InvalidArgument,
+                "keystore holds a spending key, not a mnemonic",
+            )
+            .into());
+        }
+        let opened = open(&self.bytes, password.as_bytes());
+        password.zeroize();
+        let (_, plaintext) = opened?;
+        Ok(String::from_utf8(plaintext.to_vec()).map_err(|_| {
+            WasmError::new(ErrorCode::MalformedKeystore, "mnemonic is not valid UTF-8")
+        })?)
+    }
+}
+
+fn kdf_params(memory_kib: Option<u32>, iterations: Option<u32>) -> KdfParams {
+    let defaults = KdfParams::default();
+    KdfParams {
+        memory_kib: memory_kib.unwrap_or(defaults.memory_kib),
+        iterations: iterations.unwrap_or(defaults.iterations),
+        parallelism: defaults.parallelism,
+    }
+}
+
+fn language_code(language: u8) -> Result<LanguageCode, WasmError> {
+    Ok(match language {
+        0 => LanguageCode::English,
+        1 => LanguageCode::ChineseSimplified,
+        2 => LanguageCode::ChineseTraditional,
+        3 => LanguageCode::French,
+        4 => LanguageCode::Italian,
+        5 => LanguageCode::Japanese,
+        6 => LanguageCode::Korean,
+        7 => LanguageCode::Spanish,
+        _ => {
+            return Err(WasmError::new(
+                ErrorCode::MalformedKeystore,
+                "unknown mnemonic language",
+            ))
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Cheap parameters so the tests don't spend seconds in argon2.
+    const TEST_KDF: KdfParams = KdfParams {
+        memory_kib: 64,
+        iterations: 1,
+        parallelism: 1,
+    };
+
+    #[test]
+    fn test_seal_open_round_trip() {
+        let secret = [7u8; 32];
+        let bytes = seal(KeystoreKind::SpendingKey, 0, &secret, b"hunter2", TEST_KDF).unwrap();
+        assert_eq!(bytes.len(), HEADER_LENGTH + secret.len() + 16);
+
+        let (header, plaintext) = open(&bytes, b"hunter2").unwrap();
+        assert_eq!(&plaintext[..], &secret[..]);
+        assert_eq!(header.version, FORMAT_VERSION);
+        assert_eq!(header.kind, KeystoreKind::SpendingKey);
+        assert_eq!(header.kdf, TEST_KDF);
+    }
+
+    #[test]
+    fn test_wrong_password_is_detected() {
+        let bytes = seal(KeystoreKind::Mnemonic, 3, b"words", b"right", TEST_KDF).unwrap();
+        let error = open(&bytes, b"wrong").err().unwrap();
+        assert_eq!(error.code, ErrorCode::WrongPassword);
+    }
+
+    #[test]
+    fn test_header_is_authenticated() {
+        let mut bytes = seal(KeystoreKind::Mnemonic, 0, b"words", b"pw", TEST_KDF).unwrap();
+        // Flip the language byte: still parses, but must fail to decrypt
+        bytes[6] = 1;
+        let error = open(&bytes, b"pw").err().unwrap();
+        assert_eq!(error.code, ErrorCode::WrongPassword);
+    }
+
+    #[test]
+    fn test_rejects_malformed_headers() {
+        let bytes = seal(KeystoreKind::SpendingKey, 0, &[1; 32], b"pw", TEST_KDF).unwrap();
+
+        assert!(KeystoreHeader::read(&bytes[..HEADER_LENGTH - 1]).is_err());
+
+        let mut future_version = bytes.clone();
+        future_version[4] = FORMAT_VERSION + 1;
+        let error = open(&future_version, b"pw").err().unwrap();
+        assert_eq!(error.code, ErrorCode::MalformedKeystore);
+
+        let mut huge_memory = bytes.clone();
+        huge_memory[8..12].copy_from_slice(&u32::MAX.to_le_bytes());
+        let error = open(&huge_memory, b"pw").err().unwrap();
+        assert_eq!(error.code, ErrorCode::MalformedKeystore);
+
+        let mut no_lanes = bytes;
+        no_lanes[16..20].copy_from_slice(&0u32.to_le_bytes());
+        let error = open(&no_lanes, b"pw").err().unwrap();
+        assert_eq!(error.code, ErrorCode::MalformedKeystore);
+    }
+
+    #[test]
+    fn test_rejects_out_of_range_arguments() {
+        let huge_memory = KdfParams {
+            memory_kib: MAX_MEMORY_KIB + 1,
+            ..TEST_KDF
+        };
+        let error = seal(KeystoreKind::SpendingKey, 0, &[1; 32], b"pw", huge_memory)
+            .err()
+            .unwrap();
+        assert_eq!(error.code, ErrorCode::InvalidArgument);
+    }
+}
@@ -0,0 +1,262 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+use bip39::{Language, Mnemonic};
+use ironfish_rust::keys::SPEND_KEY_SIZE;
+use unicode_normalization::UnicodeNormalization;
+use zeroize::Zeroizing;
+
+use super::{ErrorCode, WasmError};
+use crate::LanguageCode;
+
+/// A 32 byte spending key is 256 bits of entropy plus an 8 bit checksum,
+/// 11 bits per word.
+pub(crate) const SPENDING_KEY_WORDS: usize = 24;
+
+/// Order matters: when two wordlists match equally well, the earlier wins.
+const LANGUAGES: [LanguageCode; 8] = [
+    LanguageCode::English,
+    LanguageCode::Spanish,
+    LanguageCode::French,
+    LanguageCode::Italian,
+    LanguageCode::Japanese,
+    LanguageCode::Korean,
+    LanguageCode::ChineseSimplified,
+    LanguageCode::ChineseTraditional,
+];
+
+const MAX_SUGGESTIONS: usize = 3;
+const MAX_SUGGESTION_DISTANCE: usize = 2;
+
+/// Splits a phrase into words the way BIP39 compares them: any whitespace
+/// separates words, and each word is NFKD normalized and lowercased.
+fn normalize(phrase: &str) -> Zeroizing<Vec<String>> {
+    let mut words = Zeroizing::new(Vec::new());
+    for word in phrase.split_whitespace() {
+        // Growing a string leaves the old buffer behind unzeroized. NFKD at
+        // most triples a wordlist word, e.g. a Korean syllable into jamo.
+        let mut normalized = String::with_capacity(word.len() * 3);
+        normalized.extend(word.nfkd().flat_map(char::to_lowercase));
+        words.push(normalized);
+    }
+    words
+}
+
+fn is_word(language: Language, word: &str) -> bool {
+    language.wordmap().get_bits(word).is_ok()
+}
+
+/// The wordlist containing the most words of the phrase, if any does.
+pub(crate) fn detect_language(phrase: &str) -> Option<LanguageCode> {
+    let words = normalize(phrase);
+    let mut best = None;
+    let mut best_count = 0;
+    for code in LANGUAGES {
+        let language = Language::from(code);
+        let count = words.iter().filter(|w| is_word(language, w)).count();
+        if count > best_count {
+            best = Some(code);
+            best_count = count;
+        }
+    }
+    best
+}
+
+/// Dictionary words closest to `word`: those sharing its first four letters,
+/// which BIP39 guarantees are unique, then those within a small edit
+/// distance.
+pub(crate) fn suggestions(language: Language, word: &str) -> Vec<String> {
+    let all_words = language.wordlist().get_words_by_prefix("");
+    let prefix: Zeroizing<String> = Zeroizing::new(word.chars().take(4).collect());
+
+    let mut candidates: Vec<(usize, &str)> = all_words
+        .iter()
+        .filter_map(|candidate| {
+            if prefix.chars().count() == 4 && candidate.starts_with(prefix.as_str()) {
+                return Some((0, *candidate));
+            }
+            let distance = edit_distance(word, candidate);
+            (distance <= MAX_SUGGESTION_DISTANCE).then_some((distance, *candidate))
+        })
+        .collect();
+    candidates.sort();
+    candidates
+        .into_iter()
+        .take(MAX_SUGGESTIONS)
+        .map(|(_, candidate)| candidate.to_string())
+        .collect()
+}
+
+/// Levenshtein distance, counting an adjacent transposition as one edit.
+/// `a` is the word typed, so its copy is zeroized.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Zeroizing<Vec<char>> = Zeroizing::new(a.chars().collect());
+    let b: Vec<char> = b.chars().collect();
+    let mut rows = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in rows.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, cell) in rows[0].iter_mut().enumerate() {
+        *cell = j;
+    }
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            let mut distance = (rows[i - 1][j] + 1)
+                .min(rows[i][j - 1] + 1)
+                .min(rows[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                distance = distance.min(rows[i - 2][j - 2] + 1);
+            }
+            rows[i][j] = distance;
+        }
+    }
+    rows[a.len()][b.len()]
+}
+
+/// Validates a spending key mnemonic, detecting its language when not given.
+/// Returns the language and the normalized phrase. Errors name the first
+/// problem found: an unknown word (with its index and suggestions), the
+/// wrong number of words, or a checksum mismatch.
+pub(crate) fn parse_phrase(
+    phrase: &str,
+    language_code: Option<LanguageCode>,
+) -> Result<(LanguageCode, Zeroizing<String>), WasmError> {
+    let words = normalize(phrase);
+    if words.is_empty() {
+        return Err(WasmError::new(
+            ErrorCode::InvalidMnemonicLength,
+            format!("expected {} words, got 0", SPENDING_KEY_WORDS),
+        )
+        .with_argument("words"));
+    }
+
+    let language_code = match language_code.or_else(|| detect_language(phrase)) {
+        Some(language_code) => language_code,
+        None => {
+            return Err(WasmError::new(
+                ErrorCode::InvalidMnemonic,
+                "no word is in any supported wordlist",
+            )
+            .with_argument("words"))
+        }
+    };
+    let language = Language::from(language_code);
+
+    if let Some(index) = words.iter().position(|word| !is_word(language, word)) {
+        return Err(WasmError::new(
+            ErrorCode::UnknownMnemonicWord,
+            format!("word {} is not in the wordlist", index + 1),
+        )
+        .with_argument("words")
+        .with_index(index)
+        .with_suggestions(suggestions(language, &words[index])));
+    }
+
+    if words.len() != SPENDING_KEY_WORDS {
+        return Err(WasmError::new(
+            ErrorCode::InvalidMnemonicLength,
+            format!("expected {} words, got {}", SPENDING_KEY_WORDS, words.len()),
+        )
+        .with_argument("words"));
+    }
+
+    let phrase = Zeroizing::new(words.join(" "));
+    Mnemonic::validate(&phrase, language).map_err(|_| {
+        WasmError::new(
+            ErrorCode::InvalidMnemonicChecksum,
+            "checksum does not match; a word may be wrong or out of order",
+        )
+        .with_argument("words")
+    })?;
+    Ok((language_code, phrase))
+}
+
+/// The spending key a phrase encodes. `SaplingKey::from_words` takes the
+/// phrase by value and drops it unzeroized, so callers go through this
+/// instead; bip39's `Mnemonic` zeroizes its own copies.
+pub(crate) fn spending_key_bytes(
+    phrase: &str,
+    language_code: LanguageCode,
+) -> Result<Zeroizing<[u8; SPEND_KEY_SIZE]>, WasmError> {
+    let mnemonic = Mnemonic::from_phrase(phrase, Language::from(language_code))
+        .map_err(|e| WasmError::new(ErrorCode::InvalidMnemonic, e).with_argument("words"))?;
+    if mnemonic.entropy().len() != SPEND_KEY_SIZE {
+        return Err(WasmError::new(
+            ErrorCode::InvalidMnemonicLength,
+            "mnemonic is not a spending key",
+        )
+        .with_argument("words"));
+    }
+    let mut key = Zeroizing::new([0u8; SPEND_KEY_SIZE]);
+    key.copy_from_slice(mnemonic.entropy());
+    Ok(key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn valid_phrase() -> String {
+        let mut words = vec!["abandon"; SPENDING_KEY_WORDS - 1];
+        words.push("art");
+        words.join(" ")
+    }
+
+    #[test]
+    fn test_parses_valid_phrase() {
+        let messy = format!("  {}\n", valid_phrase().to_uppercase().replace(' ', "\t "));
+        let (language, phrase) = parse_phrase(&messy, None).unwrap();
+        assert!(matches!(language, LanguageCode::English));
+        assert_eq!(*phrase, valid_phrase());
+    }
+
+    #[test]
+    fn test_reports_unknown_word() {
+        let phrase = valid_phrase().replacen("abandon", "abandn", 3);
+        let error = parse_phrase(&phrase, Some(LanguageCode::English))
+            .err()
+            .unwrap();
+        assert_eq!(error.code, ErrorCode::UnknownMnemonicWord);
+        assert_eq!(error.index, Some(0));
+        assert_eq!(
+            error.suggestions.first().map(String::as_str),
+            Some("abandon")
+        );
+    }
+
+    #[test]
+    fn test_reports_word_count_and_checksum() {
+        let short = ["abandon"; 12].join(" ");
+        let error = parse_phrase(&short, None).err().unwrap();
+        assert_eq!(error.code, ErrorCode::InvalidMnemonicLength);
+
+        let bad_checksum = ["abandon"; SPENDING_KEY_WORDS].join(" ");
+        let error = parse_phrase(&bad_checksum, None).err().unwrap();
+        assert_eq!(error.code, ErrorCode::InvalidMnemonicChecksum);
+
+        let error = parse_phrase("qqqq zzzz", None).err().unwrap();
+        assert_eq!(error.code, ErrorCode::InvalidMnemonic);
+    }
+
+    #[test]
+    fn test_spending_key_bytes() {
+        let key = spending_key_bytes(&valid_phrase(), LanguageCode::English).unwrap();
+        assert_eq!(*key, [0u8; SPEND_KEY_SIZE]);
+
+        let twelve_words = format!("{} about", ["abandon"; 11].join(" "));
+        let error = spending_key_bytes(&twelve_words, LanguageCode::English)
+            .err()
+            .unwrap();
+        assert_eq!(error.code, ErrorCode::InvalidMnemonicLength);
+    }
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(edit_distance("abandon", "abandon"), 0);
+        assert_eq!(edit_distance("abandn", "abandon"), 1);
+        assert_eq!(edit_distance("abadnon", "abandon"), 1);
+        assert_eq!(edit_distance("", "art"), 3);
+    }
+}
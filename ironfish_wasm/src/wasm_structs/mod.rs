@@ -26,3 +26,55 @@ pub use proof::*;
 
 mod ephemeral_key_pair;
 pub use ephemeral_key_pair::*;
+
+mod spending_key;
+pub use spending_key::*;
+
+pub(crate) mod keystore;
+pub use keystore::*;
+
+pub(crate) mod address;
+pub use address::*;
+
+mod account;
+pub use account::*;
+
+mod view_only_key;
+pub use view_only_key::*;
+
+pub(crate) mod shamir;
+pub use shamir::*;
+
+mod payment_uri;
+pub use payment_uri::*;
+
+mod network;
+pub use network::*;
+
+mod coin_selection;
+pub use coin_selection::*;
+
+mod planning;
+pub use planning::*;
+
+mod consolidation;
+pub use consolidation::*;
+
+mod pending;
+pub use pending::*;
+
+mod payment_batch;
+pub use payment_batch::*;
+
+mod progress;
+pub use progress::ProgressCallback;
+
+pub(crate) mod contents;
+pub(crate) mod derivation;
+pub(crate) mod fees;
+pub(crate) mod in_flight;
+pub(crate) mod mnemonic;
+pub(crate) mod proving;
+pub(crate) mod signing;
+pub(crate) mod tasks;
+pub(crate) mod validation;
@@ -0,0 +1,336 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+use ironfish_rust::assets::asset_identifier::NATIVE_ASSET;
+use ironfish_rust::transaction::TransactionVersion;
+use js_sys::Array;
+use wasm_bindgen::prelude::*;
+
+use super::address::{decode_address_bytes, encode_address_bytes, Network};
+use super::{panic_hook, ErrorCode, WasmError, WasmPaymentRequest};
+
+/// Prefix of a transaction serialized with the id of the network it was
+/// built for, followed by that id as a little endian u32.
+const TAGGED_TRANSACTION_MAGIC: &[u8; 4] = b"IFTX";
+const TAGGED_TRANSACTION_HEADER_LENGTH: usize = TAGGED_TRANSACTION_MAGIC.len() + 4;
+
+/// The consensus limit on a serialized block, and so on any transaction in
+/// one. Every network uses the same limit.
+pub(crate) const MAX_TRANSACTION_SIZE: usize = 512 * 1024;
+
+/// The sequence of the genesis block. Some version is always active from it.
+const GENESIS_SEQUENCE: u32 = 1;
+
+/// Which chain transactions and addresses are for: the native asset, the
+/// transaction version to build at a given sequence, and address prefixes.
+/// Mainnet and testnet are fixed; devnets can choose their network id and
+/// when each transaction version activates.
+#[wasm_bindgen]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct NetworkConfig {
+    network: Network,
+    network_id: u32,
+    // (sequence, version) pairs, ascending in both, the first at genesis
+    activations: Vec<(u32, u8)>,
+}
+
+#[wasm_bindgen]
+impl NetworkConfig {
+    #[wasm_bindgen]
+    pub fn mainnet() -> NetworkConfig {
+        NetworkConfig {
+            network: Network::Mainnet,
+            network_id: 1,
+            activations: vec![(1, 1), (503_338, 2)],
+        }
+    }
+
+    #[wasm_bindgen]
+    pub fn testnet() -> NetworkConfig {
+        NetworkConfig {
+            network: Network::Testnet,
+            network_id: 0,
+            activations: vec![(1, 1), (419_000, 2)],
+        }
+    }
+
+    /// A local network, building the latest transaction version from genesis
+    /// until told otherwise with `activateVersion`.
+    #[wasm_bindgen]
+    pub fn devnet(network_id: Option<u32>) -> NetworkConfig {
+        NetworkConfig {
+            network: Network::Devnet,
+            network_id: network_id.unwrap_or(2),
+            activations: vec![(1, TransactionVersion::latest() as u8)],
+        }
+    }
+
+    /// Makes transactions expected at `sequence` or later use `version`.
+    /// Only devnets can be changed, versions must activate in order, and
+    /// some version must stay active from genesis: to move the only one
+    /// later, first activate an older version at genesis.
+    #[wasm_bindgen(js_name = "activateVersion")]
+    pub fn activate_version(&mut self, version: u8, sequence: u32) -> Result<(), JsValue> {
+        let _entry = panic_hook::entry!();
+        Ok(self.activate(version, sequence)?)
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn network(&self) -> Network {
+        self.network
+    }
+
+    #[wasm_bindgen(getter, js_name = "networkId")]
+    pub fn network_id(&self) -> u32 {
+        self.network_id
+    }
+
+    #[wasm_bindgen(getter, js_name = "nativeAssetId")]
+    pub fn native_asset_id(&self) -> Vec<u8> {
+        NATIVE_ASSET.as_bytes().to_vec()
+    }
+
+    /// Most bytes a serialized transaction may take, for
+    /// `PaymentPlanner.maxSize`.
+    #[wasm_bindgen(getter, js_name = "maxTransactionSize")]
+    pub fn max_transaction_size(&self) -> usize {
+        MAX_TRANSACTION_SIZE
+    }
+
+    #[wasm_bindgen(getter, js_name = "addressPrefix")]
+    pub fn address_prefix(&self) -> String {
+        self.network.address_hrp().to_string()
+    }
+
+    /// The most recently activated version, for when the chain head is not
+    /// known.
+    #[wasm_bindgen(getter, js_name = "defaultTransactionVersion")]
+    pub fn default_transaction_version(&self) -> u8 {
+        self.activations.last().map_or(1, |(_, version)| *version)
+    }
+
+    /// The version a transaction mined at `sequence` must have. Sequences
+    /// before genesis get the genesis version.
+    #[wasm_bindgen(js_name = "transactionVersion")]
+    pub fn transaction_version(&self, sequence: u32) -> u8 {
+        let sequence = sequence.max(GENESIS_SEQUENCE);
+        self.activations
+            .iter()
+            .take_while(|(activation, _)| *activation <= sequence)
+            .last()
+            .map_or(1, |(_, version)| *version)
+    }
+
+    /// Activations as `[sequence, version]` pairs, oldest first.
+    #[wasm_bindgen(getter)]
+    pub fn activations(&self) -> Array {
+        self.activations
+            .iter()
+            .map(|(sequence, version)| -> JsValue {
+                Array::of2(&(*sequence).into(), &(*version).into()).into()
+            })
+            .collect()
+    }
+
+    /// Encodes an address, given as hex or bech32m, with this network's
+    /// prefix. Addresses encoded for another network are rejected.
+    #[wasm_bindgen(js_name = "encodeAddress")]
+    pub fn encode_address(&self, address: &str) -> Result<String, JsValue> {
+        let _entry = panic_hook::entry!();
+
+        let bytes = self.address_bytes(address, "address")?;
+        Ok(encode_address_bytes(&bytes, self.network))
+    }
+
+    /// Parses a payment request, rejecting addresses encoded for another
+    /// network.
+    #[wasm_bindgen(js_name = "parsePaymentRequest")]
+    pub fn parse_payment_request(
+        &self,
+        uri: &str,
+        known_asset_ids: Option<Array>,
+    ) -> Result<WasmPaymentRequest, JsValue> {
+        let _entry = panic_hook::entry!();
+
+        let request = WasmPaymentRequest::parse(uri, known_asset_ids)?;
+        for (index, payment) in request.request.payments.iter().enumerate() {
+            self.check_network(payment.network, "address")
+                .map_err(|e| e.with_index(index))?;
+        }
+        Ok(request)
+    }
+
+    /// Encodes a payment request with this network's address prefix.
+    #[wasm_bindgen(js_name = "paymentRequestUri")]
+    pub fn payment_request_uri(&self, request: &WasmPaymentRequest) -> Result<String, JsValue> {
+        let _entry = panic_hook::entry!();
+
+        for (index, payment) in request.request.payments.iter().enumerate() {
+            self.check_network(payment.network, "request")
+                .map_err(|e| e.with_index(index))?;
+        }
+        Ok(request.request.to_uri(Some(self.network)))
+    }
+}
+
+impl NetworkConfig {
+    fn check_network(&self, network: Option<Network>, argument: &str) -> Result<(), WasmError> {
+        match network {
+            Some(network) if network != self.network => Err(WasmError::new(
+                ErrorCode::WrongNetwork,
+                format!("address is for {:?}, not {:?}", network, self.network),
+            )
+            .with_argument(argument)),
+            _ => Ok(()),
+        }
+    }
+
+    fn address_bytes(&self, address: &str, argument: &str) -> Result<[u8; 32], WasmError> {
+        let (network, bytes) = decode_address_bytes(address, argument)?;
+        self.check_network(network, argument)?;
+        Ok(bytes)
+    }
+
+    fn activate(&mut self, version: u8, sequence: u32) -> Result<(), WasmError> {
+        if self.network != Network::Devnet {
+            return Err(WasmError::new(
+                ErrorCode::InvalidArgument,
+                "only devnet activations can be changed",
+            ));
+        }
+        if version == 0 || version > TransactionVersion::latest() as u8 {
+            return Err(WasmError::new(
+                ErrorCode::InvalidTransactionVersion,
+                format!("unknown transaction version {}", version),
+            )
+            .with_argument("version"));
+        }
+
+        let mut activations: Vec<(u32, u8)> = self
+            .activations
+            .iter()
+            .copied()
+            .filter(|(_, activated)| *activated != version)
+            .collect();
+        activations.push((sequence, version));
+        activations.sort_unstable();
+        if activations.windows(2).any(|pair| pair[0].1 > pair[1].1) {
+            return Err(WasmError::new(
+                ErrorCode::InvalidArgument,
+                format!(
+                    "version {} can't activate at {}, out of order with other versions",
+                    version, sequence
+                ),
+            )
+            .with_argument("sequence"));
+        }
+        if activations[0].0 > GENESIS_SEQUENCE {
+            return Err(WasmError::new(
+                ErrorCode::InvalidArgument,
+                format!(
+                    "version {} can't activate at {}, no version would be active from genesis",
+                    version, sequence
+                ),
+            )
+            .with_argument("sequence"));
+        }
+        self.activations = activations;
+        Ok(())
+    }
+
+    /// The version to build with when the chain head is at `head_sequence`,
+    /// or the default one when it isn't known.
+    pub(crate) fn version_for_head(&self, head_sequence: Option<u32>) -> u8 {
+        match head_sequence {
+            Some(head) => self.transaction_version(head.saturating_add(1)),
+            None => self.default_transaction_version(),
+        }
+    }
+}
+
+/// Prefixes serialized transaction bytes with the network they are for.
+pub(crate) fn tag_transaction(network_id: u32, transaction: &[u8]) -> Vec<u8> {
+    let mut tagged = Vec::with_capacity(TAGGED_TRANSACTION_HEADER_LENGTH + transaction.len());
+    tagged.extend_from_slice(TAGGED_TRANSACTION_MAGIC);
+    tagged.extend_from_slice(&network_id.to_le_bytes());
+    tagged.extend_from_slice(transaction);
+    tagged
+}
+
+/// The transaction bytes of a tagged transaction, if it is for `network_id`.
+pub(crate) fn untag_transaction(network_id: u32, tagged: &[u8]) -> Result<&[u8], WasmError> {
+    if tagged.len() < TAGGED_TRANSACTION_HEADER_LENGTH
+        || &tagged[..TAGGED_TRANSACTION_MAGIC.len()] != TAGGED_TRANSACTION_MAGIC
+    {
+        return Err(WasmError::new(
+            ErrorCode::MalformedTransaction,
+            "transaction is not tagged with a network",
+        )
+        .with_argument("bytes"));
+    }
+    let mut id = [0u8; 4];
+    id.copy_from_slice(&tagged[TAGGED_TRANSACTION_MAGIC.len()..TAGGED_TRANSACTION_HEADER_LENGTH]);
+    let id = u32::from_le_bytes(id);
+    if id != network_id {
+        return Err(WasmError::new(
+            ErrorCode::WrongNetwork,
+            format!(
+                "transaction was built for network {}, not {}",
+                id, network_id
+            ),
+        )
+        .with_argument("bytes"));
+    }
+    Ok(&tagged[TAGGED_TRANSACTION_HEADER_LENGTH..])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_version_selection() {
+        let mainnet = NetworkConfig::mainnet();
+        assert_eq!(mainnet.transaction_version(1), 1);
+        assert_eq!(mainnet.transaction_version(503_337), 1);
+        assert_eq!(mainnet.transaction_version(503_338), 2);
+        assert_eq!(mainnet.version_for_head(Some(503_337)), 2);
+        assert_eq!(mainnet.version_for_head(None), 2);
+        assert_eq!(mainnet.transaction_version(0), 1);
+
+        let mut devnet = NetworkConfig::devnet(Some(42));
+        assert_eq!(devnet.network_id(), 42);
+        assert_eq!(devnet.transaction_version(1), 2);
+        devnet.activate_version(1, 1).unwrap();
+        devnet.activate_version(2, 100).unwrap();
+        assert_eq!(devnet.activations, vec![(1, 1), (100, 2)]);
+        assert_eq!(devnet.transaction_version(99), 1);
+        assert_eq!(devnet.transaction_version(100), 2);
+    }
+
+    #[test]
+    fn test_keeps_a_version_active_from_genesis() {
+        let mut devnet = NetworkConfig::devnet(None);
+        let error = devnet.activate(2, 100).err().unwrap();
+        assert_eq!(error.code, ErrorCode::InvalidArgument);
+        assert_eq!(error.argument.as_deref(), Some("sequence"));
+        assert_eq!(devnet.activations, vec![(1, 2)]);
+        assert_eq!(devnet.transaction_version(0), 2);
+        assert_eq!(devnet.transaction_version(50), 2);
+    }
+
+    #[test]
+    fn test_tagging() {
+        let tagged = tag_transaction(1, b"transaction");
+        assert_eq!(untag_transaction(1, &tagged).unwrap(), b"transaction");
+        assert_eq!(
+            untag_transaction(0, &tagged).err().unwrap().code,
+            ErrorCode::WrongNetwork
+        );
+        assert_eq!(
+            untag_transaction(1, b"transaction").err().unwrap().code,
+            ErrorCode::MalformedTransaction
+        );
+    }
+}
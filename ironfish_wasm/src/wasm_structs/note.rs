@@ -1,4 +1,6 @@
-use super::{panic_hook, WasmIronfishError};
+use super::address::parse_public_address;
+use super::assets::read_asset_id;
+use super::{panic_hook, ErrorCode, WasmError, WasmIronfishError};
 use ironfish_rust::{
     assets::asset::ID_LENGTH as ASSET_ID_LENGTH,
     keys::PUBLIC_ADDRESS_SIZE,
@@ -49,8 +51,27 @@ pub struct WasmNote {
     pub(crate) note: Note,
 }
 
+/// Rejects memos a note can't hold whole, rather than letting `Note::new`
+/// cut them short.
+pub(crate) fn check_memo(memo: &str) -> Result<(), WasmError> {
+    if memo.len() > MEMO_SIZE {
+        return Err(WasmError::new(
+            ErrorCode::InvalidMemo,
+            format!(
+                "memo is {} bytes, at most {} fit in a note",
+                memo.len(),
+                MEMO_SIZE
+            ),
+        )
+        .with_argument("memo"));
+    }
+    Ok(())
+}
+
 #[wasm_bindgen]
 impl WasmNote {
+    /// Throws with code `InvalidMemo` when `memo` is longer than
+    /// `get_memo_length()` bytes.
     #[wasm_bindgen(constructor)]
     pub fn new(
         owner: &str,
@@ -59,14 +80,12 @@ impl WasmNote {
         asset_id: &[u8],
         sender: &str,
     ) -> Result<WasmNote, JsValue> {
-        panic_hook::set_once();
-
-        let owner_address =
-            ironfish_rust::PublicAddress::from_hex(owner).map_err(WasmIronfishError)?;
-        let sender = ironfish_rust::PublicAddress::from_hex(sender).map_err(WasmIronfishError)?;
-        let mut asset_id_bytes = [0; ASSET_ID_LENGTH];
-        asset_id_bytes.clone_from_slice(&asset_id[0..ASSET_ID_LENGTH]);
-        let asset_id = asset_id_bytes.try_into().map_err(WasmIronfishError)?;
+        let _entry = panic_hook::entry!();
+
+        let owner_address = parse_public_address(owner, "owner")?;
+        let sender = parse_public_address(sender, "sender")?;
+        let asset_id = read_asset_id(asset_id, "asset_id")?;
+        check_memo(memo)?;
         Ok(WasmNote {
             note: Note::new(owner_address, value, memo, asset_id, sender),
         })
@@ -74,15 +93,17 @@ impl WasmNote {
 
     #[wasm_bindgen]
     pub fn deserialize(bytes: &[u8]) -> Result<WasmNote, JsValue> {
-        panic_hook::set_once();
+        let _entry = panic_hook::entry!();
 
         let cursor: std::io::Cursor<&[u8]> = std::io::Cursor::new(bytes);
-        let note = Note::read(cursor).map_err(WasmIronfishError)?;
+        let note = Note::read(cursor)
+            .map_err(|e| WasmError::new(ErrorCode::MalformedNote, e).with_argument("bytes"))?;
         Ok(WasmNote { note })
     }
 
     #[wasm_bindgen]
     pub fn serialize(&self) -> Result<Vec<u8>, JsValue> {
+        let _entry = panic_hook::entry!();
         let mut cursor: std::io::Cursor<Vec<u8>> = std::io::Cursor::new(vec![]);
         self.note.write(&mut cursor).map_err(WasmIronfishError)?;
         Ok(cursor.into_inner())
@@ -92,6 +113,7 @@ impl WasmNote {
     /// This hash is what gets used for the leaf nodes in a Merkle Tree.
     #[wasm_bindgen(getter)]
     pub fn hash(&self) -> Vec<u8> {
+        let _entry = panic_hook::entry!();
         self.note.commitment().to_vec()
     }
 
@@ -135,7 +157,10 @@ impl WasmNote {
     /// 'nullifier set', preventing double-spend.
     #[wasm_bindgen]
     pub fn nullifier(&self, owner_view_key: &str, position: u64) -> Result<Vec<u8>, JsValue> {
-        let view_key = ViewKey::from_hex(owner_view_key).map_err(WasmIronfishError)?;
+        let _entry = panic_hook::entry!();
+        let view_key = ViewKey::from_hex(owner_view_key).map_err(|e| {
+            WasmError::new(ErrorCode::InvalidViewKey, e).with_argument("owner_view_key")
+        })?;
         Ok(self.note.nullifier(&view_key, position).to_vec())
     }
 }
@@ -10,8 +10,20 @@ use ironfish_rust::MerkleNote;
 use ironfish_rust::MerkleNoteHash;
 use ironfish_rust::OutgoingViewKey;
 use wasm_bindgen::prelude::*;
+use web_sys::AbortSignal;
 
-use super::{panic_hook, WasmIronfishError, WasmNote};
+use super::progress::{ProgressCallback, ProgressPhase, ProgressReporter};
+use super::tasks::checkpoint;
+use super::{panic_hook, ErrorCode, WasmError, WasmIronfishError, WasmNote};
+use crate::logger;
+
+/// Number of notes the batch decryption functions process between progress
+/// reports, and between yields to the event loop for the async variant.
+const DECRYPT_BATCH_SIZE: usize = 64;
+
+/// Merkle tree depths are encoded in 6 bits of the Pedersen hash
+/// personalization, which asserts on anything larger.
+const MAX_MERKLE_DEPTH: usize = 62;
 
 #[wasm_bindgen]
 pub fn get_note_encryption_key_length() -> u32 {
@@ -42,15 +54,17 @@ pub struct WasmNoteEncrypted {
 impl WasmNoteEncrypted {
     #[wasm_bindgen(constructor)]
     pub fn new(bytes: &[u8]) -> Result<WasmNoteEncrypted, JsValue> {
-        panic_hook::set_once();
+        let _entry = panic_hook::entry!();
 
         let mut cursor: std::io::Cursor<&[u8]> = std::io::Cursor::new(bytes);
-        let note = MerkleNote::read(&mut cursor).map_err(WasmIronfishError)?;
+        let note = MerkleNote::read(&mut cursor)
+            .map_err(|e| WasmError::new(ErrorCode::MalformedNote, e).with_argument("bytes"))?;
         Ok(WasmNoteEncrypted { note })
     }
 
     #[wasm_bindgen]
     pub fn serialize(&self) -> Result<Vec<u8>, JsValue> {
+        let _entry = panic_hook::entry!();
         let mut cursor: std::io::Cursor<Vec<u8>> = std::io::Cursor::new(vec![]);
         self.note.write(&mut cursor).map_err(WasmIronfishError)?;
         Ok(cursor.into_inner())
@@ -63,6 +77,7 @@ impl WasmNoteEncrypted {
 
     #[wasm_bindgen(js_name = "merkleHash")]
     pub fn merkle_hash(&self) -> Result<Vec<u8>, JsValue> {
+        let _entry = panic_hook::entry!();
         let mut cursor: Vec<u8> = Vec::with_capacity(32);
         self.note
             .merkle_hash()
@@ -75,10 +90,21 @@ impl WasmNoteEncrypted {
     /// new parent
     #[wasm_bindgen(js_name = "combineHash")]
     pub fn combine_hash(depth: usize, left: &[u8], right: &[u8]) -> Result<Vec<u8>, JsValue> {
+        let _entry = panic_hook::entry!();
+        if depth > MAX_MERKLE_DEPTH {
+            return Err(WasmError::new(
+                ErrorCode::IndexOutOfBounds,
+                format!("depth must be at most {}", MAX_MERKLE_DEPTH),
+            )
+            .with_argument("depth")
+            .into());
+        }
         let mut left_hash_reader: std::io::Cursor<&[u8]> = std::io::Cursor::new(left);
         let mut right_hash_reader: std::io::Cursor<&[u8]> = std::io::Cursor::new(right);
-        let left_hash = MerkleNoteHash::read(&mut left_hash_reader).map_err(WasmIronfishError)?;
-        let right_hash = MerkleNoteHash::read(&mut right_hash_reader).map_err(WasmIronfishError)?;
+        let left_hash = MerkleNoteHash::read(&mut left_hash_reader)
+            .map_err(|e| WasmError::new(ErrorCode::InvalidArgument, e).with_argument("left"))?;
+        let right_hash = MerkleNoteHash::read(&mut right_hash_reader)
+            .map_err(|e| WasmError::new(ErrorCode::InvalidArgument, e).with_argument("right"))?;
 
         let mut cursor: Vec<u8> = Vec::with_capacity(32);
 
@@ -96,7 +122,10 @@ impl WasmNoteEncrypted {
     /// Returns undefined if the note was unable to be decrypted with the given key.
     #[wasm_bindgen(js_name = "decryptNoteForOwner")]
     pub fn decrypt_note_for_owner(&self, owner_hex_key: &str) -> Result<Option<WasmNote>, JsValue> {
-        let owner_view_key = IncomingViewKey::from_hex(owner_hex_key).map_err(WasmIronfishError)?;
+        let _entry = panic_hook::entry!();
+        let owner_view_key = IncomingViewKey::from_hex(owner_hex_key).map_err(|e| {
+            WasmError::new(ErrorCode::InvalidViewKey, e).with_argument("owner_hex_key")
+        })?;
         Ok(match self.note.decrypt_note_for_owner(&owner_view_key) {
             Ok(n) => Some(WasmNote { note: { n } }),
             Err(_) => None,
@@ -109,8 +138,10 @@ impl WasmNoteEncrypted {
         &self,
         spender_hex_key: &str,
     ) -> Result<Option<WasmNote>, JsValue> {
-        let spender_view_key =
-            OutgoingViewKey::from_hex(spender_hex_key).map_err(WasmIronfishError)?;
+        let _entry = panic_hook::entry!();
+        let spender_view_key = OutgoingViewKey::from_hex(spender_hex_key).map_err(|e| {
+            WasmError::new(ErrorCode::InvalidViewKey, e).with_argument("spender_hex_key")
+        })?;
 
         Ok(
             match self.note.decrypt_note_for_spender(&spender_view_key) {
@@ -121,6 +152,108 @@ impl WasmNoteEncrypted {
     }
 }
 
+/// Decrypts each serialized note in `serialized_notes` with the given incoming
+/// view key. Returns an array holding, in input order, a `WasmNote` for each
+/// note owned by the key and undefined for the rest.
+#[wasm_bindgen(js_name = "decryptNotesForOwner")]
+pub fn decrypt_notes_for_owner(
+    serialized_notes: js_sys::Array,
+    owner_hex_key: &str,
+    progress: Option<ProgressCallback>,
+) -> Result<js_sys::Array, JsValue> {
+    let _entry = panic_hook::entry!();
+
+    let owner_view_key = IncomingViewKey::from_hex(owner_hex_key)
+        .map_err(|e| WasmError::new(ErrorCode::InvalidViewKey, e).with_argument("owner_hex_key"))?;
+    decrypt_notes(&serialized_notes, &owner_view_key, progress)
+}
+
+/// Async counterpart of `decryptNotesForOwner`, yielding to the event loop
+/// every few dozen notes so long scans don't freeze the page.
+#[wasm_bindgen(js_name = "decryptNotesForOwnerAsync")]
+pub async fn decrypt_notes_for_owner_async(
+    serialized_notes: js_sys::Array,
+    owner_hex_key: String,
+    signal: Option<AbortSignal>,
+    progress: Option<ProgressCallback>,
+) -> Result<js_sys::Array, JsValue> {
+    panic_hook::instrument(panic_hook::function_name!(), async move {
+        let owner_view_key = IncomingViewKey::from_hex(&owner_hex_key).map_err(|e| {
+            WasmError::new(ErrorCode::InvalidViewKey, e).with_argument("owner_hex_key")
+        })?;
+        decrypt_notes_async(&serialized_notes, &owner_view_key, signal, progress).await
+    })
+    .await
+}
+
+pub(crate) fn decrypt_notes(
+    serialized_notes: &js_sys::Array,
+    owner_view_key: &IncomingViewKey,
+    progress: Option<ProgressCallback>,
+) -> Result<js_sys::Array, JsValue> {
+    let _span = logger::span("decrypt_notes");
+
+    let progress = ProgressReporter::new(progress);
+    let total = serialized_notes.length() as usize;
+    let decrypted = js_sys::Array::new();
+    for (index, serialized_note) in serialized_notes.iter().enumerate() {
+        if index % DECRYPT_BATCH_SIZE == 0 {
+            progress.report(ProgressPhase::Scan, index, total)?;
+        }
+        decrypted.push(&decrypt_serialized_note(
+            &serialized_note,
+            owner_view_key,
+            index,
+        )?);
+    }
+    progress.report(ProgressPhase::Scan, total, total)?;
+    Ok(decrypted)
+}
+
+pub(crate) async fn decrypt_notes_async(
+    serialized_notes: &js_sys::Array,
+    owner_view_key: &IncomingViewKey,
+    signal: Option<AbortSignal>,
+    progress: Option<ProgressCallback>,
+) -> Result<js_sys::Array, JsValue> {
+    let _span = logger::span("decrypt_notes");
+
+    let progress = ProgressReporter::new(progress);
+    let total = serialized_notes.length() as usize;
+    let decrypted = js_sys::Array::new();
+    for (index, serialized_note) in serialized_notes.iter().enumerate() {
+        if index % DECRYPT_BATCH_SIZE == 0 {
+            progress.report(ProgressPhase::Scan, index, total)?;
+            checkpoint(&signal).await?;
+        }
+        decrypted.push(&decrypt_serialized_note(
+            &serialized_note,
+            owner_view_key,
+            index,
+        )?);
+    }
+    progress.report(ProgressPhase::Scan, total, total)?;
+    Ok(decrypted)
+}
+
+fn decrypt_serialized_note(
+    serialized_note: &JsValue,
+    owner_view_key: &IncomingViewKey,
+    index: usize,
+) -> Result<JsValue, JsValue> {
+    let bytes = js_sys::Uint8Array::new(serialized_note).to_vec();
+    let mut cursor: std::io::Cursor<&[u8]> = std::io::Cursor::new(&bytes);
+    let note = MerkleNote::read(&mut cursor).map_err(|e| {
+        WasmError::new(ErrorCode::MalformedNote, e)
+            .with_argument("serialized_notes")
+            .with_index(index)
+    })?;
+    Ok(match note.decrypt_note_for_owner(owner_view_key) {
+        Ok(n) => WasmNote { note: n }.into(),
+        Err(_) => JsValue::UNDEFINED,
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use ironfish_rust::assets::asset_identifier::AssetIdentifier;
@@ -0,0 +1,252 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+//! Paying many recipients at once, from a JSON list of payments:
+//!
+//! ```text
+//! [
+//!   { "address": "<hex or bech32>", "amount": "150000000" },
+//!   { "address": "...", "amount": 5, "assetId": "<hex>", "memo": "thanks" }
+//! ]
+//! ```
+//!
+//! Amounts are in ore, as numbers or as strings for those too large for a
+//! JS number. The asset defaults to the native one and the memo to empty.
+
+use ironfish_rust::assets::asset_identifier::NATIVE_ASSET;
+use js_sys::Array;
+use serde::Deserialize;
+use wasm_bindgen::prelude::*;
+
+use super::address::parse_public_address;
+use super::assets::read_asset_id;
+use super::note::check_memo;
+use super::payment_uri::Payment;
+use super::{panic_hook, ErrorCode, WasmError, WasmNote, WasmTransaction};
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RawPayment {
+    address: String,
+    // Any JSON value, so a bad amount is reported as the amount's problem
+    amount: serde_json::Value,
+    asset_id: Option<String>,
+    memo: Option<String>,
+}
+
+/// Checks every field of row `index`, returning each problem found.
+fn parse_row(value: serde_json::Value, index: usize) -> Result<Payment, Vec<WasmError>> {
+    let raw: RawPayment = serde_json::from_value(value)
+        .map_err(|e| vec![WasmError::new(ErrorCode::MalformedPaymentList, e).with_index(index)])?;
+    let mut errors = Vec::new();
+
+    let amount = match &raw.amount {
+        serde_json::Value::Number(amount) => amount.as_u64(),
+        serde_json::Value::String(text) => text.parse::<u64>().ok(),
+        _ => None,
+    };
+    let amount = match amount {
+        Some(amount) if amount > 0 => Some(amount),
+        _ => {
+            errors.push(
+                WasmError::new(
+                    ErrorCode::InvalidArgument,
+                    "amount must be a positive whole number of ore",
+                )
+                .with_argument("amount")
+                .with_index(index),
+            );
+            None
+        }
+    };
+
+    if let Err(error) = parse_public_address(&raw.address, "address") {
+        errors.push(error.with_index(index));
+    }
+
+    let asset_id = match raw.asset_id {
+        None => Some(NATIVE_ASSET.as_bytes().to_vec()),
+        Some(hex_id) => match hex::decode(hex_id)
+            .map_err(|e| WasmError::new(ErrorCode::InvalidAssetId, e).with_argument("assetId"))
+            .and_then(|bytes| read_asset_id(&bytes, "assetId").map(|_| bytes))
+        {
+            Ok(bytes) => Some(bytes),
+            Err(error) => {
+                errors.push(error.with_index(index));
+                None
+            }
+        },
+    };
+
+    let memo = raw.memo.unwrap_or_default();
+    if let Err(error) = check_memo(&memo) {
+        errors.push(error.with_index(index));
+    }
+
+    match asset_id {
+        Some(asset_id) if errors.is_empty() => {
+            Payment::new(&raw.address, amount, &asset_id, memo, None, index).map_err(|e| vec![e])
+        }
+        _ => Err(errors),
+    }
+}
+
+/// Parses a JSON list of payments, checking every row. Fails only when the
+/// list itself isn't a JSON array; problems with rows are collected.
+pub(crate) fn parse_payment_list(json: &str) -> Result<WasmPaymentBatch, WasmError> {
+    let values: Vec<serde_json::Value> = serde_json::from_str(json)
+        .map_err(|e| WasmError::new(ErrorCode::MalformedPaymentList, e).with_argument("json"))?;
+
+    let mut rows = Vec::with_capacity(values.len());
+    let mut errors = Vec::new();
+    for (index, value) in values.into_iter().enumerate() {
+        match parse_row(value, index) {
+            Ok(row) => rows.push(row),
+            Err(row_errors) => errors.extend(row_errors),
+        }
+    }
+    Ok(WasmPaymentBatch { rows, errors })
+}
+
+/// Payments from a JSON list, checked and waiting to be added to
+/// transactions. A batch with errors is never added to a transaction.
+#[wasm_bindgen]
+pub struct WasmPaymentBatch {
+    pub(crate) rows: Vec<Payment>,
+    pub(crate) errors: Vec<WasmError>,
+}
+
+#[wasm_bindgen]
+impl WasmPaymentBatch {
+    #[wasm_bindgen(js_name = "fromJson")]
+    pub fn from_json(json: &str) -> Result<WasmPaymentBatch, JsValue> {
+        let _entry = panic_hook::entry!();
+        Ok(parse_payment_list(json)?)
+    }
+
+    /// Payments still to be added.
+    #[wasm_bindgen(getter)]
+    pub fn length(&self) -> usize {
+        self.rows.len()
+    }
+
+    #[wasm_bindgen(getter, js_name = "isValid")]
+    pub fn is_valid(&self) -> bool {
+        self.errors.is_empty()
+    }
+
+    /// Every problem with the list as an `IronfishError`, whose `index` is
+    /// the row it was found in.
+    #[wasm_bindgen(getter)]
+    pub fn errors(&self) -> Array {
+        self.errors
+            .iter()
+            .map(|error| JsValue::from(error.clone()))
+            .collect()
+    }
+
+    /// Adds outputs for as many payments as fit in `transaction` under
+    /// `max_outputs`, counting the outputs it already has and a change
+    /// output for every asset it spends more of than it pays out. Returns
+    /// the payments that didn't fit, to be added to the next transaction,
+    /// and throws with code `OutputLimitExceeded` when none fit. Nothing is
+    /// added if any payment can't be made into a note.
+    #[wasm_bindgen(js_name = "addToTransaction")]
+    pub fn add_to_transaction(
+        &self,
+        transaction: &mut WasmTransaction,
+        sender: &str,
+        max_outputs: Option<usize>,
+    ) -> Result<WasmPaymentBatch, JsValue> {
+        let _entry = panic_hook::entry!();
+
+        if let Some(error) = self.errors.first() {
+            return Err(error.clone().into());
+        }
+        let sender = parse_public_address(sender, "sender")?.public_address();
+        let notes = self
+            .rows
+            .iter()
+            .enumerate()
+            .map(|(index, payment)| payment.to_note(&sender, index))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut added = 0;
+        for note in notes {
+            if let Some(max_outputs) = max_outputs {
+                // Paying out can only use up change, never add to it
+                let outputs = transaction.outputs_with_change()?;
+                if outputs >= max_outputs {
+                    if added == 0 {
+                        return Err(WasmError::new(
+                            ErrorCode::OutputLimitExceeded,
+                            format!(
+                                "has {} outputs with change, no payment fits under {}",
+                                outputs, max_outputs
+                            ),
+                        )
+                        .with_argument("max_outputs")
+                        .into());
+                    }
+                    break;
+                }
+            }
+            transaction.output(&WasmNote { note })?;
+            added += 1;
+        }
+
+        Ok(WasmPaymentBatch {
+            rows: self.rows[added..].to_vec(),
+            errors: Vec::new(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_collects_errors_from_every_row() {
+        let json = r#"[
+            { "address": "nope", "amount": 0, "memo": "this memo is far too long to fit a note" },
+            { "address": "nope", "amount": "12", "assetId": "zz" },
+            { "amount": 5 },
+            { "address": "nope", "amount": 1.5 }
+        ]"#;
+        let batch = parse_payment_list(json).ok().unwrap();
+        assert!(batch.rows.is_empty());
+
+        let found: Vec<(Option<usize>, Option<String>)> = batch
+            .errors
+            .iter()
+            .map(|error| (error.index, error.argument.clone()))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                (Some(0), Some("amount".to_string())),
+                (Some(0), Some("address".to_string())),
+                (Some(0), Some("memo".to_string())),
+                (Some(1), Some("address".to_string())),
+                (Some(1), Some("assetId".to_string())),
+                (Some(2), None),
+                (Some(3), Some("amount".to_string())),
+                (Some(3), Some("address".to_string())),
+            ]
+        );
+        assert_eq!(batch.errors[2].code, ErrorCode::InvalidMemo);
+        assert_eq!(batch.errors[5].code, ErrorCode::MalformedPaymentList);
+        assert_eq!(batch.errors[6].code, ErrorCode::InvalidArgument);
+    }
+
+    #[test]
+    fn test_rejects_lists_that_are_not_arrays() {
+        for json in ["", "{}", "[1, 2"] {
+            let error = parse_payment_list(json).err().unwrap();
+            assert_eq!(error.code, ErrorCode::MalformedPaymentList);
+            assert_eq!(error.argument.as_deref(), Some("json"));
+        }
+    }
+}
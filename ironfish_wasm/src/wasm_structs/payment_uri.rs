@@ -0,0 +1,645 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+//! `ironfish:` payment request URIs.
+//!
+//! ```text
+//! ironfish:<address>?amount=1.5&asset=<hex id>&memo=<percent encoded>&expiration=<sequence>
+//! ```
+//!
+//! Further recipients repeat the payment parameters with an index suffix, so
+//! `address.1`, `amount.1`, `asset.1` and `memo.1` describe a second payment.
+//! Indices start at 1 and may not skip; the first payment's address may be
+//! given as the path or as `address`. Amounts are in coins with up to 8
+//! decimal places, the asset defaults to the native one and `expiration`
+//! applies to the whole transaction. Unknown parameters are ignored unless
+//! prefixed with `req-`, which marks them as ones a wallet must understand.
+
+use std::collections::BTreeMap;
+
+use ironfish_rust::assets::asset::ID_LENGTH;
+use ironfish_rust::assets::asset_identifier::NATIVE_ASSET;
+use ironfish_rust::{Note, PublicAddress};
+use js_sys::{Array, Uint8Array};
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+
+use super::address::{decode_address_bytes, encode_address_bytes, parse_public_address, Network};
+use super::assets::read_asset_id;
+use super::note::check_memo;
+use super::transaction::index_out_of_bounds;
+use super::{panic_hook, ErrorCode, WasmError, WasmNote, WasmTransaction};
+
+const SCHEME: &str = "ironfish";
+const DECIMALS: usize = 8;
+const ORE_PER_COIN: u64 = 100_000_000;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct Payment {
+    pub(crate) network: Option<Network>,
+    pub(crate) address: [u8; 32],
+    pub(crate) amount: Option<u64>,
+    pub(crate) asset_id: [u8; ID_LENGTH],
+    pub(crate) memo: String,
+}
+
+impl Payment {
+    /// Validates one payment. Errors name the offending parameter and carry
+    /// the payment's index.
+    pub(crate) fn new(
+        address: &str,
+        amount: Option<u64>,
+        asset_id: &[u8],
+        memo: String,
+        known_asset_ids: Option<&[[u8; ID_LENGTH]]>,
+        index: usize,
+    ) -> Result<Payment, WasmError> {
+        let (network, address) =
+            decode_address_bytes(address, "address").map_err(|e| e.with_index(index))?;
+        PublicAddress::new(&address).map_err(|e| {
+            WasmError::new(ErrorCode::InvalidPublicAddress, e)
+                .with_argument("address")
+                .with_index(index)
+        })?;
+
+        let asset = read_asset_id(asset_id, "asset").map_err(|e| e.with_index(index))?;
+        let asset_id: [u8; ID_LENGTH] = *asset.as_bytes();
+        let known = asset_id == *NATIVE_ASSET.as_bytes()
+            || match known_asset_ids {
+                Some(known) => known.contains(&asset_id),
+                None => true,
+            };
+        if !known {
+            return Err(WasmError::new(ErrorCode::InvalidAssetId, "unknown asset")
+                .with_argument("asset")
+                .with_index(index)
+                .with_asset_id(&asset_id));
+        }
+
+        check_memo(&memo).map_err(|e| e.with_index(index))?;
+
+        Ok(Payment {
+            network,
+            address,
+            amount,
+            asset_id,
+            memo,
+        })
+    }
+
+    pub(crate) fn to_note(&self, sender: &[u8; 32], index: usize) -> Result<Note, WasmError> {
+        let amount = self.amount.ok_or_else(|| {
+            WasmError::new(ErrorCode::InvalidArgument, "payment has no amount")
+                .with_argument("amount")
+                .with_index(index)
+        })?;
+        let owner = PublicAddress::new(&self.address)
+            .map_err(|e| WasmError::new(ErrorCode::InvalidPublicAddress, e).with_index(index))?;
+        let sender = PublicAddress::new(sender).map_err(|e| {
+            WasmError::new(ErrorCode::InvalidPublicAddress, e).with_argument("sender")
+        })?;
+        let asset_id = read_asset_id(&self.asset_id, "asset")?;
+        Ok(Note::new(
+            owner,
+            amount,
+            self.memo.as_str(),
+            asset_id,
+            sender,
+        ))
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub(crate) struct PaymentRequest {
+    pub(crate) payments: Vec<Payment>,
+    pub(crate) expiration: Option<u32>,
+}
+
+#[derive(Default)]
+struct Fields {
+    address: Option<String>,
+    amount: Option<String>,
+    asset: Option<String>,
+    memo: Option<String>,
+}
+
+fn malformed(message: impl ToString) -> WasmError {
+    WasmError::new(ErrorCode::MalformedPaymentRequest, message).with_argument("uri")
+}
+
+fn percent_decode(value: &str) -> Result<String, WasmError> {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let byte = bytes
+                .get(i + 1..i + 3)
+                .and_then(|hex| std::str::from_utf8(hex).ok())
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+                .ok_or_else(|| malformed("'%' must be followed by two hex digits"))?;
+            decoded.push(byte);
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8(decoded).map_err(|_| malformed("percent encoded text is not UTF-8"))
+}
+
+fn percent_encode(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        if byte.is_ascii_alphanumeric() || b"-._~".contains(&byte) {
+            encoded.push(byte as char);
+        } else {
+            encoded.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    encoded
+}
+
+/// Parses a decimal coin amount into ore.
+fn parse_amount(value: &str) -> Option<u64> {
+    let (whole, fraction) = value.split_once('.').unwrap_or((value, ""));
+    if whole.is_empty() && fraction.is_empty()
+        || fraction.len() > DECIMALS
+        || !whole
+            .bytes()
+            .chain(fraction.bytes())
+            .all(|b| b.is_ascii_digit())
+    {
+        return None;
+    }
+    let whole: u64 = if whole.is_empty() {
+        0
+    } else {
+        whole.parse().ok()?
+    };
+    let fraction: u64 = format!("{:0<width$}", fraction, width = DECIMALS)
+        .parse()
+        .ok()?;
+    whole.checked_mul(ORE_PER_COIN)?.checked_add(fraction)
+}
+
+fn format_amount(amount: u64) -> String {
+    let whole = amount / ORE_PER_COIN;
+    let fraction = amount % ORE_PER_COIN;
+    if fraction == 0 {
+        return whole.to_string();
+    }
+    let fraction = format!("{:0width$}", fraction, width = DECIMALS);
+    format!("{}.{}", whole, fraction.trim_end_matches('0'))
+}
+
+impl PaymentRequest {
+    pub(crate) fn parse(
+        uri: &str,
+        known_asset_ids: Option<&[[u8; ID_LENGTH]]>,
+    ) -> Result<PaymentRequest, WasmError> {
+        let uri = uri.trim();
+        let (scheme, rest) = uri
+            .split_once(':')
+            .ok_or_else(|| malformed("expected an ironfish: URI"))?;
+        if !scheme.eq_ignore_ascii_case(SCHEME) {
+            return Err(malformed(format!(
+                "expected an ironfish: URI, got {}:",
+                scheme
+            )));
+        }
+        let (path, query) = rest.split_once('?').unwrap_or((rest, ""));
+
+        let mut fields: BTreeMap<usize, Fields> = BTreeMap::new();
+        if !path.is_empty() {
+            fields.entry(0).or_default().address = Some(percent_decode(path)?);
+        }
+        let mut expiration = None;
+        for pair in query.split('&').filter(|pair| !pair.is_empty()) {
+            let (key, value) = pair
+                .split_once('=')
+                .ok_or_else(|| malformed(format!("parameter '{}' has no value", pair)))?;
+            let value = percent_decode(value)?;
+            let (name, index) = match key.split_once('.') {
+                None => (key, 0),
+                Some((name, index)) => {
+                    let valid =
+                        !index.starts_with('0') && index.bytes().all(|b| b.is_ascii_digit());
+                    match index.parse::<usize>() {
+                        Ok(index) if valid => (name, index),
+                        _ => {
+                            return Err(malformed(format!("invalid parameter index in '{}'", key)))
+                        }
+                    }
+                }
+            };
+
+            if name == "expiration" && index == 0 {
+                if expiration.is_some() {
+                    return Err(malformed("duplicate parameter 'expiration'"));
+                }
+                expiration = Some(value.parse::<u32>().map_err(|_| {
+                    malformed("expiration must be a block sequence").with_argument("expiration")
+                })?);
+                continue;
+            }
+            // Matched before looking up the payment, so that an ignored
+            // parameter like `label.1` doesn't make up a payment 1
+            let field: fn(&mut Fields) -> &mut Option<String> = match name {
+                "address" => |entry| &mut entry.address,
+                "amount" => |entry| &mut entry.amount,
+                "asset" => |entry| &mut entry.asset,
+                "memo" => |entry| &mut entry.memo,
+                _ if name.starts_with("req-") => {
+                    return Err(malformed(format!(
+                        "unsupported required parameter '{}'",
+                        key
+                    )))
+                }
+                _ => continue,
+            };
+            let slot = field(fields.entry(index).or_default());
+            if slot.is_some() {
+                return Err(malformed(format!("duplicate parameter '{}'", key)));
+            }
+            *slot = Some(value);
+        }
+
+        if fields.is_empty() {
+            return Err(malformed("no payments"));
+        }
+        let mut payments = Vec::with_capacity(fields.len());
+        for (expected, (index, fields)) in fields.into_iter().enumerate() {
+            if index != expected {
+                return Err(
+                    malformed(format!("payment {} is missing", expected)).with_index(expected)
+                );
+            }
+            let address = fields.address.ok_or_else(|| {
+                malformed("payment has no address")
+                    .with_argument("address")
+                    .with_index(index)
+            })?;
+            let amount = match fields.amount {
+                None => None,
+                Some(amount) => Some(parse_amount(&amount).ok_or_else(|| {
+                    malformed(format!("invalid amount '{}'", amount))
+                        .with_argument("amount")
+                        .with_index(index)
+                })?),
+            };
+            let asset_id = match fields.asset {
+                None => NATIVE_ASSET.as_bytes().to_vec(),
+                Some(asset) => hex::decode(&asset).map_err(|e| {
+                    WasmError::new(ErrorCode::InvalidAssetId, e)
+                        .with_argument("asset")
+                        .with_index(index)
+                })?,
+            };
+            let memo = fields.memo.unwrap_or_default();
+            payments.push(Payment::new(
+                &address,
+                amount,
+                &asset_id,
+                memo,
+                known_asset_ids,
+                index,
+            )?);
+        }
+        Ok(PaymentRequest {
+            payments,
+            expiration,
+        })
+    }
+
+    /// Encodes the request, with addresses for `network` when given, else for
+    /// the network they were parsed with, else as raw hex.
+    pub(crate) fn to_uri(&self, network: Option<Network>) -> String {
+        let mut path = String::new();
+        let mut params = Vec::new();
+        for (index, payment) in self.payments.iter().enumerate() {
+            let address = match network.or(payment.network) {
+                Some(network) => encode_address_bytes(&payment.address, network),
+                None => hex::encode(payment.address),
+            };
+            let suffix = if index == 0 {
+                path = address;
+                String::new()
+            } else {
+                params.push(format!("address.{}={}", index, address));
+                format!(".{}", index)
+            };
+            if let Some(amount) = payment.amount {
+                params.push(format!("amount{}={}", suffix, format_amount(amount)));
+            }
+            if payment.asset_id != *NATIVE_ASSET.as_bytes() {
+                params.push(format!("asset{}={}", suffix, hex::encode(payment.asset_id)));
+            }
+            if !payment.memo.is_empty() {
+                params.push(format!("memo{}={}", suffix, percent_encode(&payment.memo)));
+            }
+        }
+        if let Some(expiration) = self.expiration {
+            params.push(format!("expiration={}", expiration));
+        }
+
+        let mut uri = format!("{}:{}", SCHEME, path);
+        if !params.is_empty() {
+            uri.push('?');
+            uri.push_str(&params.join("&"));
+        }
+        uri
+    }
+
+    fn payment(&self, index: usize) -> Result<&Payment, WasmError> {
+        self.payments
+            .get(index)
+            .ok_or_else(|| index_out_of_bounds(index, self.payments.len()))
+    }
+
+    fn to_notes(&self, sender: &str) -> Result<Vec<Note>, WasmError> {
+        let sender = parse_public_address(sender, "sender")?.public_address();
+        self.payments
+            .iter()
+            .enumerate()
+            .map(|(index, payment)| payment.to_note(&sender, index))
+            .collect()
+    }
+}
+
+fn read_known_asset_ids(
+    known_asset_ids: Option<Array>,
+) -> Result<Option<Vec<[u8; ID_LENGTH]>>, WasmError> {
+    let Some(known_asset_ids) = known_asset_ids else {
+        return Ok(None);
+    };
+    known_asset_ids
+        .iter()
+        .enumerate()
+        .map(|(index, value)| {
+            let bytes = value.dyn_into::<Uint8Array>().map_err(|_| {
+                WasmError::new(ErrorCode::InvalidArgument, "expected a Uint8Array")
+                    .with_argument("known_asset_ids")
+                    .with_index(index)
+            })?;
+            let asset = read_asset_id(&bytes.to_vec(), "known_asset_ids")
+                .map_err(|e| e.with_index(index))?;
+            Ok(*asset.as_bytes())
+        })
+        .collect::<Result<Vec<_>, _>>()
+        .map(Some)
+}
+
+/// A validated payment request, parsed from or encoded to an `ironfish:`
+/// URI. Each payment becomes one note, so a request can be added to a
+/// transaction directly.
+#[wasm_bindgen]
+#[derive(Default)]
+pub struct WasmPaymentRequest {
+    pub(crate) request: PaymentRequest,
+}
+
+#[wasm_bindgen]
+impl WasmPaymentRequest {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> WasmPaymentRequest {
+        WasmPaymentRequest::default()
+    }
+
+    /// Parses and validates a URI. When `known_asset_ids` is given, payments
+    /// of any other asset than those and the native one are rejected. Errors
+    /// carry the `index` of the offending payment and name its parameter.
+    #[wasm_bindgen]
+    pub fn parse(uri: &str, known_asset_ids: Option<Array>) -> Result<WasmPaymentRequest, JsValue> {
+        let _entry = panic_hook::entry!();
+
+        let known_asset_ids = read_known_asset_ids(known_asset_ids)?;
+        Ok(WasmPaymentRequest {
+            request: PaymentRequest::parse(uri, known_asset_ids.as_deref())?,
+        })
+    }
+
+    /// Appends a payment, validated as a parsed one is. The asset defaults to
+    /// the native one.
+    #[wasm_bindgen(js_name = "addPayment")]
+    pub fn add_payment(
+        &mut self,
+        address: &str,
+        amount: Option<u64>,
+        asset_id: Option<Vec<u8>>,
+        memo: Option<String>,
+    ) -> Result<(), JsValue> {
+        let _entry = panic_hook::entry!();
+
+        let asset_id = asset_id.unwrap_or_else(|| NATIVE_ASSET.as_bytes().to_vec());
+        let payment = Payment::new(
+            address,
+            amount,
+            &asset_id,
+            memo.unwrap_or_default(),
+            None,
+            self.request.payments.len(),
+        )?;
+        self.request.payments.push(payment);
+        Ok(())
+    }
+
+    /// Encodes the request. Addresses use `network`'s prefix when given,
+    /// otherwise the prefix they were parsed with, otherwise raw hex.
+    #[wasm_bindgen(js_name = "toUri")]
+    pub fn to_uri(&self, network: Option<Network>) -> String {
+        let _entry = panic_hook::entry!();
+        self.request.to_uri(network)
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn length(&self) -> usize {
+        self.request.payments.len()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn expiration(&self) -> Option<u32> {
+        self.request.expiration
+    }
+
+    #[wasm_bindgen(setter)]
+    pub fn set_expiration(&mut self, expiration: Option<u32>) {
+        self.request.expiration = expiration;
+    }
+
+    /// Recipient of a payment, as raw hex.
+    #[wasm_bindgen]
+    pub fn address(&self, index: usize) -> Result<String, JsValue> {
+        Ok(hex::encode(self.request.payment(index)?.address))
+    }
+
+    /// Amount of a payment in ore, or undefined when the payer chooses it.
+    #[wasm_bindgen]
+    pub fn amount(&self, index: usize) -> Result<Option<u64>, JsValue> {
+        Ok(self.request.payment(index)?.amount)
+    }
+
+    #[wasm_bindgen(js_name = "assetId")]
+    pub fn asset_id(&self, index: usize) -> Result<Vec<u8>, JsValue> {
+        Ok(self.request.payment(index)?.asset_id.to_vec())
+    }
+
+    #[wasm_bindgen]
+    pub fn memo(&self, index: usize) -> Result<String, JsValue> {
+        Ok(self.request.payment(index)?.memo.clone())
+    }
+
+    /// The notes paying this request, one per payment and in order. Fails
+    /// if any payment has no amount.
+    #[wasm_bindgen(js_name = "toNotes")]
+    pub fn to_notes(&self, sender: &str) -> Result<Array, JsValue> {
+        let _entry = panic_hook::entry!();
+
+        Ok(self
+            .request
+            .to_notes(sender)?
+            .into_iter()
+            .map(|note| JsValue::from(WasmNote { note }))
+            .collect())
+    }
+
+    /// Adds an output for every payment to `transaction` and applies the
+    /// requested expiration. Nothing is added if any payment is invalid.
+    #[wasm_bindgen(js_name = "addToTransaction")]
+    pub fn add_to_transaction(
+        &self,
+        transaction: &mut WasmTransaction,
+        sender: &str,
+    ) -> Result<(), JsValue> {
+        let _entry = panic_hook::entry!();
+
+        for note in self.request.to_notes(sender)? {
+            transaction.output(&WasmNote { note })?;
+        }
+        if let Some(expiration) = self.request.expiration {
+            transaction.set_expiration_sequence(expiration)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ironfish_rust::note::MEMO_SIZE;
+    use ironfish_rust::{assets::asset::Asset, SaplingKey};
+
+    use super::*;
+
+    #[test]
+    fn test_amounts() {
+        for (text, ore) in [
+            ("0", 0),
+            ("1", ORE_PER_COIN),
+            ("1.5", 150_000_000),
+            (".00000001", 1),
+            ("42.12345678", 4_212_345_678),
+        ] {
+            assert_eq!(parse_amount(text), Some(ore), "{}", text);
+        }
+        for text in [
+            "",
+            ".",
+            "-1",
+            "1e3",
+            "0.000000001",
+            "1.2.3",
+            "184467440737.1",
+        ] {
+            assert_eq!(parse_amount(text), None, "{}", text);
+        }
+        for ore in [0, 1, 150_000_000, 4_212_345_678, u64::MAX] {
+            assert_eq!(parse_amount(&format_amount(ore)), Some(ore));
+        }
+        assert_eq!(format_amount(150_000_000), "1.5");
+    }
+
+    #[test]
+    fn test_percent_encoding() {
+        let text = "for rent & ünicode 100%";
+        assert_eq!(percent_decode(&percent_encode(text)).unwrap(), text);
+        assert_eq!(percent_decode("a+b%20c").unwrap(), "a+b c");
+        assert!(percent_decode("%2").is_err());
+        assert!(percent_decode("%ff").is_err());
+    }
+
+    #[test]
+    fn test_ignores_unknown_parameters() {
+        let error = PaymentRequest::parse("ironfish:?label.1=x&message=hi", None)
+            .err()
+            .unwrap();
+        assert_eq!(error.message, "no payments");
+        assert_eq!(error.index, None);
+    }
+
+    #[test]
+    fn test_parse_and_encode() {
+        let first = SaplingKey::generate_key().public_address().public_address();
+        let second = SaplingKey::generate_key().public_address().public_address();
+        let asset = Asset::new(SaplingKey::generate_key().public_address(), "coin", "")
+            .unwrap()
+            .id()
+            .as_bytes()
+            .to_owned();
+        let uri = format!(
+            "ironfish:{}?amount=1.5&memo=rent%20%26%20fees&address.1={}&amount.1=2&asset.1={}&expiration=1200&label.2=x",
+            encode_address_bytes(&first, Network::Testnet),
+            hex::encode(second),
+            hex::encode(asset),
+        );
+        let request = PaymentRequest::parse(&uri, Some(&[asset])).unwrap();
+        assert_eq!(request.expiration, Some(1200));
+        assert_eq!(request.payments.len(), 2);
+        assert_eq!(request.payments[0].network, Some(Network::Testnet));
+        assert_eq!(request.payments[0].address, first);
+        assert_eq!(request.payments[0].amount, Some(150_000_000));
+        assert_eq!(request.payments[0].asset_id, *NATIVE_ASSET.as_bytes());
+        assert_eq!(request.payments[0].memo, "rent & fees");
+        assert_eq!(request.payments[1].address, second);
+        assert_eq!(request.payments[1].asset_id, asset);
+
+        let encoded = request.to_uri(None);
+        assert_eq!(PaymentRequest::parse(&encoded, None).unwrap(), request);
+    }
+
+    #[test]
+    fn test_rejects_invalid_payments() {
+        let key = SaplingKey::generate_key();
+        let address = hex::encode(key.public_address().public_address());
+
+        let memo = format!("ironfish:{}?memo={}", address, "x".repeat(MEMO_SIZE + 1));
+        let error = PaymentRequest::parse(&memo, None).err().unwrap();
+        assert_eq!(error.code, ErrorCode::InvalidMemo);
+        assert_eq!(error.argument.as_deref(), Some("memo"));
+        assert_eq!(error.index, Some(0));
+
+        let asset = Asset::new(key.public_address(), "coin", "").unwrap();
+        let uri = format!(
+            "ironfish:{}?address.1={}&asset.1={}",
+            address,
+            address,
+            hex::encode(asset.id().as_bytes())
+        );
+        let error = PaymentRequest::parse(&uri, Some(&[])).err().unwrap();
+        assert_eq!(error.code, ErrorCode::InvalidAssetId);
+        assert_eq!(error.index, Some(1));
+        assert_eq!(error.asset_id, Some(hex::encode(asset.id().as_bytes())));
+
+        for uri in [
+            format!("bitcoin:{}", address),
+            format!("ironfish:{}?amount=1&amount=2", address),
+            format!("ironfish:{}?address.2={}", address, address),
+            format!("ironfish:{}?address.01={}", address, address),
+            format!("ironfish:{}?req-fee=1", address),
+            "ironfish:?amount=1".to_string(),
+        ] {
+            let error = PaymentRequest::parse(&uri, None).err().unwrap();
+            assert_eq!(error.code, ErrorCode::MalformedPaymentRequest, "{}", uri);
+        }
+    }
+}
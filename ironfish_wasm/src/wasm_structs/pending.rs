@@ -0,0 +1,276 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+//! Read-only views of what a `WasmTransaction` holds before it is posted,
+//! for showing a transaction to its spender before they confirm it.
+
+use ironfish_rust::assets::asset::ID_LENGTH;
+use ironfish_rust::Note;
+use js_sys::{Array, Uint8Array};
+use wasm_bindgen::prelude::*;
+
+use super::contents::{AssetBalance, MintRecord, SpendRecord};
+use super::WasmNote;
+
+#[wasm_bindgen]
+pub struct WasmPendingSpend {
+    pub(crate) spend: SpendRecord,
+}
+
+#[wasm_bindgen]
+impl WasmPendingSpend {
+    #[wasm_bindgen(getter)]
+    pub fn note(&self) -> WasmNote {
+        WasmNote {
+            note: self.spend.note.clone(),
+        }
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn value(&self) -> u64 {
+        self.spend.note.value()
+    }
+
+    #[wasm_bindgen(getter, js_name = "assetId")]
+    pub fn asset_id(&self) -> Vec<u8> {
+        self.spend.note.asset_id().as_bytes().to_vec()
+    }
+
+    /// Position of the spent note in the note commitment tree.
+    #[wasm_bindgen(getter)]
+    pub fn position(&self) -> u64 {
+        self.spend.position
+    }
+}
+
+#[wasm_bindgen]
+pub struct WasmPendingOutput {
+    pub(crate) note: Note,
+}
+
+#[wasm_bindgen]
+impl WasmPendingOutput {
+    #[wasm_bindgen(getter)]
+    pub fn note(&self) -> WasmNote {
+        WasmNote {
+            note: self.note.clone(),
+        }
+    }
+
+    /// Hex public address the output pays.
+    #[wasm_bindgen(getter)]
+    pub fn recipient(&self) -> String {
+        self.note.owner().hex_public_address()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn value(&self) -> u64 {
+        self.note.value()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn memo(&self) -> String {
+        self.note.memo().to_string()
+    }
+
+    #[wasm_bindgen(getter, js_name = "assetId")]
+    pub fn asset_id(&self) -> Vec<u8> {
+        self.note.asset_id().as_bytes().to_vec()
+    }
+}
+
+#[wasm_bindgen]
+pub struct WasmPendingMint {
+    pub(crate) mint: MintRecord,
+}
+
+#[wasm_bindgen]
+impl WasmPendingMint {
+    #[wasm_bindgen(getter, js_name = "assetId")]
+    pub fn asset_id(&self) -> Vec<u8> {
+        self.mint.asset.id().as_bytes().to_vec()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn name(&self) -> Vec<u8> {
+        self.mint.asset.name().to_vec()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn value(&self) -> u64 {
+        self.mint.value
+    }
+
+    /// Hex public address the asset's ownership moves to, if it moves.
+    #[wasm_bindgen(getter, js_name = "transferOwnershipTo")]
+    pub fn transfer_ownership_to(&self) -> Option<String> {
+        self.mint
+            .transfer_ownership_to
+            .as_ref()
+            .map(|owner| owner.hex_public_address())
+    }
+}
+
+#[wasm_bindgen]
+pub struct WasmPendingBurn {
+    pub(crate) asset_id: [u8; ID_LENGTH],
+    pub(crate) value: u64,
+}
+
+#[wasm_bindgen]
+impl WasmPendingBurn {
+    #[wasm_bindgen(getter, js_name = "assetId")]
+    pub fn asset_id(&self) -> Vec<u8> {
+        self.asset_id.to_vec()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn value(&self) -> u64 {
+        self.value
+    }
+}
+
+/// Value of one asset going into and out of a transaction, and what
+/// posting it with a given fee leaves as change.
+#[wasm_bindgen]
+pub struct WasmAssetTotal {
+    pub(crate) balance: AssetBalance,
+}
+
+#[wasm_bindgen]
+impl WasmAssetTotal {
+    #[wasm_bindgen(getter, js_name = "assetId")]
+    pub fn asset_id(&self) -> Vec<u8> {
+        self.balance.asset_id.to_vec()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn spent(&self) -> u64 {
+        self.balance.totals.spent
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn minted(&self) -> u64 {
+        self.balance.totals.minted
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn output(&self) -> u64 {
+        self.balance.totals.output
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn burned(&self) -> u64 {
+        self.balance.totals.burned
+    }
+
+    /// Value spent and minted.
+    #[wasm_bindgen(getter, js_name = "totalIn")]
+    pub fn total_in(&self) -> u64 {
+        self.balance.totals.total_in()
+    }
+
+    /// Value output and burned.
+    #[wasm_bindgen(getter, js_name = "totalOut")]
+    pub fn total_out(&self) -> u64 {
+        self.balance.totals.total_out()
+    }
+
+    /// The part of the fee paid in this asset.
+    #[wasm_bindgen(getter)]
+    pub fn fee(&self) -> u64 {
+        self.balance.fee
+    }
+
+    /// Value sent back to the spender, 0 when in doesn't cover out and fee.
+    #[wasm_bindgen(getter)]
+    pub fn change(&self) -> u64 {
+        u64::try_from(self.balance.change().max(0)).unwrap_or(u64::MAX)
+    }
+
+    /// Value missing for in to cover out and fee, 0 when nothing is.
+    #[wasm_bindgen(getter)]
+    pub fn shortfall(&self) -> u64 {
+        self.balance.shortfall()
+    }
+}
+
+/// Whether a transaction can be posted with a given fee and, asset by
+/// asset, what is missing when it can't.
+#[wasm_bindgen]
+pub struct WasmBalanceReport {
+    pub(crate) balances: Vec<AssetBalance>,
+}
+
+#[wasm_bindgen]
+impl WasmBalanceReport {
+    /// True when every asset covers its outputs, burns and fee.
+    #[wasm_bindgen(getter, js_name = "isBalanced")]
+    pub fn is_balanced(&self) -> bool {
+        self.balances.iter().all(|balance| balance.shortfall() == 0)
+    }
+
+    /// Every asset the transaction moves, and the native asset when it
+    /// pays a fee, as `WasmAssetTotal`s.
+    #[wasm_bindgen(getter)]
+    pub fn assets(&self) -> Array {
+        self.balances
+            .iter()
+            .map(|&balance| JsValue::from(WasmAssetTotal { balance }))
+            .collect()
+    }
+
+    /// Ids of the assets whose change would be negative.
+    #[wasm_bindgen(getter, js_name = "negativeChangeAssetIds")]
+    pub fn negative_change_asset_ids(&self) -> Array {
+        self.balances
+            .iter()
+            .filter(|balance| balance.shortfall() > 0)
+            .map(|balance| JsValue::from(Uint8Array::from(&balance.asset_id[..])))
+            .collect()
+    }
+
+    /// What `asset_id` is short, 0 when it isn't or isn't moved at all.
+    #[wasm_bindgen]
+    pub fn shortfall(&self, asset_id: &[u8]) -> u64 {
+        self.balances
+            .iter()
+            .find(|balance| balance.asset_id[..] == *asset_id)
+            .map_or(0, AssetBalance::shortfall)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wasm_structs::contents::AssetTotals;
+
+    #[test]
+    fn test_balance_report() {
+        let balance = |asset_id, spent, output, fee| AssetBalance {
+            asset_id,
+            totals: AssetTotals {
+                spent,
+                output,
+                ..AssetTotals::default()
+            },
+            fee,
+        };
+        let report = WasmBalanceReport {
+            balances: vec![
+                balance([1; ID_LENGTH], 100, 30, 20),
+                balance([3; ID_LENGTH], 5, 8, 0),
+            ],
+        };
+        assert!(!report.is_balanced());
+        assert_eq!(report.shortfall(&[1; ID_LENGTH]), 0);
+        assert_eq!(report.shortfall(&[3; ID_LENGTH]), 3);
+        assert_eq!(report.shortfall(&[4; ID_LENGTH]), 0);
+
+        let total = WasmAssetTotal {
+            balance: report.balances[0],
+        };
+        assert_eq!((total.change(), total.shortfall()), (50, 0));
+    }
+}
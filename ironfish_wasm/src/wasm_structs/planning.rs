@@ -0,0 +1,575 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+//! Payments too large for one transaction.
+//!
+//! A payment that needs more notes than a transaction may spend, or whose
+//! spends would make it larger than a transaction may be, is preceded
+//! by consolidations: transactions with spends but no outputs, whose change
+//! merges the smallest of the needed notes into one. The payment then spends
+//! those merged notes, which only exist once the consolidations are mined,
+//! along with any notes it can use directly.
+
+use std::collections::BTreeMap;
+
+use ironfish_rust::assets::asset::ID_LENGTH;
+use ironfish_rust::assets::asset_identifier::NATIVE_ASSET;
+use ironfish_rust::Note;
+use js_sys::Array;
+use rand_core::OsRng;
+use wasm_bindgen::prelude::*;
+
+use super::coin_selection::{add_target, select_coins, Candidate, Coin, Selection, MAX_INPUTS};
+use super::fees::{spend_size, TransactionShape};
+use super::network::MAX_TRANSACTION_SIZE;
+use super::transaction::index_out_of_bounds;
+use super::witness::JsWitness;
+use super::{
+    panic_hook, CoinSelectionStrategy, ErrorCode, WasmError, WasmNote, WasmSpendingKey,
+    WasmTransaction,
+};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct PlannedTransaction {
+    /// Indices of the existing coins spent.
+    pub(crate) inputs: Vec<usize>,
+    pub(crate) fee: u64,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct PaymentPlan {
+    pub(crate) consolidations: Vec<PlannedTransaction>,
+    pub(crate) payment: PlannedTransaction,
+    /// Notes created by the consolidations that the payment also spends.
+    pub(crate) pending_inputs: usize,
+}
+
+/// The shape of a transaction paying `targets` with `outputs` outputs, and
+/// the fewest coins paying both it and its fee. Change is assumed for every
+/// asset spent.
+fn settle_fee(
+    coins: &[Coin],
+    targets: &BTreeMap<[u8; ID_LENGTH], u64>,
+    outputs: usize,
+    version: u8,
+    fee_rate_per_kb: u64,
+) -> Result<(TransactionShape, Selection), WasmError> {
+    let mut spends = 1;
+    loop {
+        let mut with_fee = targets.clone();
+        with_fee.entry(*NATIVE_ASSET.as_bytes()).or_default();
+        let shape = TransactionShape {
+            version,
+            spends,
+            outputs: outputs + with_fee.len(),
+            ..TransactionShape::default()
+        };
+        let fee = shape.fee(fee_rate_per_kb);
+        add_target(&mut with_fee, *NATIVE_ASSET.as_bytes(), fee)?;
+        let selection = select_coins(
+            coins,
+            &with_fee,
+            usize::MAX,
+            CoinSelectionStrategy::LargestFirst,
+            &mut OsRng,
+        )?;
+        if selection.inputs.len() <= spends {
+            return Ok((shape, selection));
+        }
+        spends = selection.inputs.len();
+    }
+}
+
+/// The fee to merge `spends` notes of one asset, plus a native note paying
+/// the fee when the asset isn't native.
+pub(crate) fn consolidation_fee(
+    spends: usize,
+    native: bool,
+    version: u8,
+    fee_rate_per_kb: u64,
+) -> u64 {
+    let fee_input = usize::from(!native);
+    TransactionShape {
+        version,
+        spends: spends + fee_input,
+        outputs: 1 + fee_input,
+        ..TransactionShape::default()
+    }
+    .fee(fee_rate_per_kb)
+}
+
+/// Most spends a transaction of `version` with `outputs` outputs can have
+/// without growing past `max_size` bytes.
+fn spends_within(max_size: usize, outputs: usize, version: u8) -> usize {
+    let shape = TransactionShape {
+        version,
+        outputs,
+        ..TransactionShape::default()
+    };
+    max_size.saturating_sub(shape.size()) / spend_size()
+}
+
+/// Plans consolidations until the payment needs at most `max_inputs` notes
+/// and fits in `max_size` bytes, as does every consolidation.
+pub(crate) fn plan_payment(
+    coins: &[Coin],
+    targets: &BTreeMap<[u8; ID_LENGTH], u64>,
+    outputs: usize,
+    max_inputs: usize,
+    max_size: usize,
+    version: u8,
+    fee_rate_per_kb: u64,
+) -> Result<PaymentPlan, WasmError> {
+    let native = *NATIVE_ASSET.as_bytes();
+    // Coins the payment can draw on, with the index of existing ones. Merged
+    // coins are newest, so they sort last among equals.
+    let mut pool: Vec<(Coin, Option<usize>)> = coins
+        .iter()
+        .enumerate()
+        .map(|(index, coin)| (*coin, Some(index)))
+        .collect();
+    let mut consolidations = Vec::new();
+
+    loop {
+        let available: Vec<Coin> = pool.iter().map(|(coin, _)| *coin).collect();
+        let (shape, selection) =
+            settle_fee(&available, targets, outputs, version, fee_rate_per_kb)?;
+        let payment_limit = max_inputs.min(spends_within(max_size, shape.outputs, version));
+        if payment_limit == 0 {
+            return Err(WasmError::new(
+                ErrorCode::OutputLimitExceeded,
+                format!(
+                    "{} outputs leave no room for a spend in {} bytes",
+                    shape.outputs, max_size
+                ),
+            ));
+        }
+        if selection.inputs.len() <= payment_limit {
+            return Ok(PaymentPlan {
+                consolidations,
+                payment: PlannedTransaction {
+                    inputs: selection.inputs.iter().filter_map(|&i| pool[i].1).collect(),
+                    fee: shape.fee(fee_rate_per_kb),
+                },
+                pending_inputs: selection
+                    .inputs
+                    .iter()
+                    .filter(|&&i| pool[i].1.is_none())
+                    .count(),
+            });
+        }
+
+        // Merge the smallest existing notes of the asset needing the most
+        let mut by_asset: BTreeMap<[u8; ID_LENGTH], Vec<usize>> = BTreeMap::new();
+        for &i in selection.inputs.iter().filter(|&&i| pool[i].1.is_some()) {
+            by_asset.entry(pool[i].0.asset_id).or_default().push(i);
+        }
+        let (asset_id, mut merge) = by_asset
+            .into_iter()
+            .max_by_key(|(_, merge)| merge.len())
+            .unwrap_or_default();
+        let is_native = asset_id == native;
+        let fee_input = usize::from(!is_native);
+        let consolidation_limit = max_inputs.min(spends_within(max_size, 1 + fee_input, version));
+        merge.sort_by_key(|&i| pool[i].0.value);
+        merge.truncate(consolidation_limit.saturating_sub(fee_input));
+        if merge.len() < 2 {
+            return Err(WasmError::new(
+                ErrorCode::InputLimitExceeded,
+                format!(
+                    "needs {} notes, and merging them would not spend fewer than {}",
+                    selection.inputs.len(),
+                    payment_limit
+                ),
+            )
+            .with_asset_id(&asset_id));
+        }
+
+        let fee = consolidation_fee(merge.len(), is_native, version, fee_rate_per_kb);
+        let merged = merge
+            .iter()
+            .fold(0u64, |total, &i| total.saturating_add(pool[i].0.value));
+        let merged_coin = |value, asset_id| Coin {
+            value,
+            asset_id,
+            sequence: u32::MAX,
+        };
+        let mut created = Vec::new();
+        if is_native {
+            if merged <= fee {
+                return Err(WasmError::new(
+                    ErrorCode::InsufficientBalance,
+                    format!(
+                        "notes worth {} can't pay the {} fee to merge them",
+                        merged, fee
+                    ),
+                )
+                .with_asset_id(&native));
+            }
+            created.push(merged_coin(merged - fee, native));
+        } else {
+            let payer = (0..pool.len())
+                .filter(|&i| {
+                    let (coin, existing) = pool[i];
+                    existing.is_some() && coin.asset_id == native && coin.value >= fee
+                })
+                .min_by_key(|&i| pool[i].0.value)
+                .ok_or_else(|| {
+                    WasmError::new(
+                        ErrorCode::InsufficientBalance,
+                        format!("no note can pay the {} fee to merge notes", fee),
+                    )
+                    .with_asset_id(&native)
+                })?;
+            created.push(merged_coin(pool[payer].0.value - fee, native));
+            created.push(merged_coin(merged, asset_id));
+            merge.push(payer);
+        }
+
+        consolidations.push(PlannedTransaction {
+            inputs: merge.iter().filter_map(|&i| pool[i].1).collect(),
+            fee,
+        });
+        merge.sort_unstable_by(|a, b| b.cmp(a));
+        for i in merge {
+            pool.remove(i);
+        }
+        pool.extend(
+            created
+                .into_iter()
+                .filter(|coin| coin.value > 0)
+                .map(|coin| (coin, None)),
+        );
+    }
+}
+
+/// Builds a transaction spending the given candidates.
+pub(crate) fn spending_transaction(
+    spender: &WasmSpendingKey,
+    version: u8,
+    candidates: &[Candidate],
+    inputs: &[usize],
+) -> Result<WasmTransaction, JsValue> {
+    let mut transaction = WasmTransaction::with_key(spender.sapling_key()?, version)?;
+    for &input in inputs {
+        let candidate = &candidates[input];
+        let note = WasmNote {
+            note: candidate.note.clone(),
+        };
+        transaction.spend(&note, candidate.witness())?;
+    }
+    Ok(transaction)
+}
+
+/// Moves a plan's transactions out to JS, which can happen only once.
+pub(crate) fn take_transactions(
+    transactions: &mut Option<Vec<WasmTransaction>>,
+) -> Result<Array, JsValue> {
+    let transactions = transactions.take().ok_or_else(|| {
+        WasmError::new(
+            ErrorCode::UseAfterWipe,
+            "the plan's transactions have already been taken",
+        )
+    })?;
+    Ok(transactions.into_iter().map(JsValue::from).collect())
+}
+
+/// Splits a payment needing more notes than one transaction may spend into
+/// consolidations followed by the payment itself.
+#[wasm_bindgen]
+pub struct PaymentPlanner {
+    fee_rate_per_kb: u64,
+    max_inputs: usize,
+    max_size: usize,
+    candidates: Vec<Candidate>,
+    outputs: Vec<Note>,
+}
+
+#[wasm_bindgen]
+impl PaymentPlanner {
+    #[wasm_bindgen(constructor)]
+    pub fn new(fee_rate_per_kb: u64) -> PaymentPlanner {
+        PaymentPlanner {
+            fee_rate_per_kb,
+            max_inputs: MAX_INPUTS,
+            max_size: MAX_TRANSACTION_SIZE,
+            candidates: Vec::new(),
+            outputs: Vec::new(),
+        }
+    }
+
+    /// Most notes any one transaction may spend, 10 unless changed.
+    #[wasm_bindgen(getter, js_name = "maxInputs")]
+    pub fn max_inputs(&self) -> usize {
+        self.max_inputs
+    }
+
+    #[wasm_bindgen(setter, js_name = "maxInputs")]
+    pub fn set_max_inputs(&mut self, max_inputs: usize) {
+        self.max_inputs = max_inputs;
+    }
+
+    /// Most bytes any one transaction may take serialized, the network's
+    /// `maxTransactionSize` unless changed.
+    #[wasm_bindgen(getter, js_name = "maxSize")]
+    pub fn max_size(&self) -> usize {
+        self.max_size
+    }
+
+    #[wasm_bindgen(setter, js_name = "maxSize")]
+    pub fn set_max_size(&mut self, max_size: usize) {
+        self.max_size = max_size;
+    }
+
+    /// Offers an unspent note, as for `CoinSelector.addCandidate`.
+    #[wasm_bindgen(js_name = "addCandidate")]
+    pub fn add_candidate(
+        &mut self,
+        note: &WasmNote,
+        witness: JsWitness,
+        position: u64,
+        sequence: u32,
+    ) {
+        self.candidates.push(Candidate {
+            note: note.note.clone(),
+            witness: witness.into(),
+            position,
+            sequence,
+        });
+    }
+
+    /// Adds a note the payment creates.
+    #[wasm_bindgen(js_name = "addOutput")]
+    pub fn add_output(&mut self, note: &WasmNote) {
+        self.outputs.push(note.note.clone());
+    }
+
+    /// Plans the payment for `spender`, building transactions of `version`.
+    #[wasm_bindgen]
+    pub fn plan(&self, spender: &WasmSpendingKey, version: u8) -> Result<WasmPaymentPlan, JsValue> {
+        let _entry = panic_hook::entry!();
+
+        let mut targets = BTreeMap::new();
+        for note in &self.outputs {
+            add_target(&mut targets, *note.asset_id().as_bytes(), note.value())?;
+        }
+        let coins: Vec<Coin> = self.candidates.iter().map(Candidate::coin).collect();
+        let plan = plan_payment(
+            &coins,
+            &targets,
+            self.outputs.len(),
+            self.max_inputs,
+            self.max_size,
+            version,
+            self.fee_rate_per_kb,
+        )?;
+
+        let mut transactions = Vec::with_capacity(plan.consolidations.len() + 1);
+        let mut fees = Vec::with_capacity(plan.consolidations.len() + 1);
+        for consolidation in &plan.consolidations {
+            transactions.push(spending_transaction(
+                spender,
+                version,
+                &self.candidates,
+                &consolidation.inputs,
+            )?);
+            fees.push(consolidation.fee);
+        }
+        let mut payment =
+            spending_transaction(spender, version, &self.candidates, &plan.payment.inputs)?;
+        for note in &self.outputs {
+            payment.output(&WasmNote { note: note.clone() })?;
+        }
+        transactions.push(payment);
+        fees.push(plan.payment.fee);
+
+        Ok(WasmPaymentPlan {
+            transactions: Some(transactions),
+            fees,
+            pending_spends: plan.pending_inputs,
+        })
+    }
+}
+
+/// Transactions to post in order, each with its fee and change going back
+/// to the spender. The last one is the payment; when `pendingSpends` isn't
+/// zero, it must also spend that many notes created by the consolidations
+/// before it can be built.
+#[wasm_bindgen]
+pub struct WasmPaymentPlan {
+    // None once taken
+    transactions: Option<Vec<WasmTransaction>>,
+    fees: Vec<u64>,
+    pending_spends: usize,
+}
+
+#[wasm_bindgen]
+impl WasmPaymentPlan {
+    #[wasm_bindgen(getter)]
+    pub fn length(&self) -> usize {
+        self.fees.len()
+    }
+
+    /// The transactions, consolidations first and the payment last. They
+    /// are moved out of the plan, so a second call throws with code
+    /// `UseAfterWipe`.
+    #[wasm_bindgen(js_name = "takeTransactions")]
+    pub fn take_transactions(&mut self) -> Result<Array, JsValue> {
+        take_transactions(&mut self.transactions)
+    }
+
+    /// The fee to post transaction `index` with.
+    #[wasm_bindgen]
+    pub fn fee(&self, index: usize) -> Result<u64, JsValue> {
+        self.fees
+            .get(index)
+            .copied()
+            .ok_or_else(|| index_out_of_bounds(index, self.fees.len()).into())
+    }
+
+    #[wasm_bindgen(getter, js_name = "totalFee")]
+    pub fn total_fee(&self) -> u64 {
+        let _entry = panic_hook::entry!();
+        self.fees.iter().sum()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn consolidations(&self) -> usize {
+        let _entry = panic_hook::entry!();
+        self.fees.len() - 1
+    }
+
+    #[wasm_bindgen(getter, js_name = "pendingSpends")]
+    pub fn pending_spends(&self) -> usize {
+        self.pending_spends
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn native(values: &[u64]) -> Vec<Coin> {
+        values
+            .iter()
+            .enumerate()
+            .map(|(sequence, &value)| Coin {
+                value,
+                asset_id: *NATIVE_ASSET.as_bytes(),
+                sequence: sequence as u32,
+            })
+            .collect()
+    }
+
+    fn targets(value: u64) -> BTreeMap<[u8; ID_LENGTH], u64> {
+        [(*NATIVE_ASSET.as_bytes(), value)].into_iter().collect()
+    }
+
+    #[test]
+    fn test_single_transaction() {
+        let coins = native(&[1_000, 5_000, 200]);
+        let plan = plan_payment(
+            &coins,
+            &targets(4_000),
+            1,
+            MAX_INPUTS,
+            MAX_TRANSACTION_SIZE,
+            2,
+            1,
+        )
+        .unwrap();
+        assert!(plan.consolidations.is_empty());
+        assert_eq!(plan.payment.inputs, vec![1]);
+        assert_eq!(plan.pending_inputs, 0);
+        let shape = TransactionShape {
+            version: 2,
+            spends: 1,
+            outputs: 2,
+            ..TransactionShape::default()
+        };
+        assert_eq!(plan.payment.fee, shape.fee(1));
+    }
+
+    #[test]
+    fn test_consolidates_smallest_notes_first() {
+        let coins = native(&[100; 25]);
+        let plan = plan_payment(
+            &coins,
+            &targets(1_500),
+            1,
+            MAX_INPUTS,
+            MAX_TRANSACTION_SIZE,
+            2,
+            1,
+        )
+        .unwrap();
+        assert!(!plan.consolidations.is_empty());
+        for consolidation in &plan.consolidations {
+            assert!(consolidation.inputs.len() <= MAX_INPUTS);
+            assert_eq!(
+                consolidation.fee,
+                consolidation_fee(consolidation.inputs.len(), true, 2, 1)
+            );
+        }
+        assert!(plan.payment.inputs.len() + plan.pending_inputs <= MAX_INPUTS);
+        assert!(plan.pending_inputs > 0);
+
+        let mut spent: Vec<usize> = plan
+            .consolidations
+            .iter()
+            .flat_map(|consolidation| consolidation.inputs.iter().copied())
+            .chain(plan.payment.inputs.iter().copied())
+            .collect();
+        let count = spent.len();
+        spent.sort_unstable();
+        spent.dedup();
+        assert_eq!(spent.len(), count, "a note is spent twice");
+    }
+
+    #[test]
+    fn test_splits_transactions_too_large() {
+        let coins = native(&[100; 6]);
+        let max_size = TransactionShape {
+            version: 2,
+            spends: 3,
+            outputs: 2,
+            ..TransactionShape::default()
+        }
+        .size();
+        let plan = plan_payment(&coins, &targets(500), 1, MAX_INPUTS, max_size, 2, 1).unwrap();
+        assert!(!plan.consolidations.is_empty());
+        for consolidation in &plan.consolidations {
+            let shape = TransactionShape {
+                version: 2,
+                spends: consolidation.inputs.len(),
+                outputs: 1,
+                ..TransactionShape::default()
+            };
+            assert!(shape.size() <= max_size);
+        }
+        assert!(plan.payment.inputs.len() + plan.pending_inputs <= 3);
+
+        let error = plan_payment(&coins, &targets(50), 1, MAX_INPUTS, spend_size(), 2, 1)
+            .err()
+            .unwrap();
+        assert_eq!(error.code, ErrorCode::OutputLimitExceeded);
+    }
+
+    #[test]
+    fn test_unpayable() {
+        let coins = native(&[100; 5]);
+        let error = plan_payment(
+            &coins,
+            &targets(1_000),
+            1,
+            MAX_INPUTS,
+            MAX_TRANSACTION_SIZE,
+            2,
+            1,
+        )
+        .err()
+        .unwrap();
+        assert_eq!(error.code, ErrorCode::InsufficientBalance);
+    }
+}
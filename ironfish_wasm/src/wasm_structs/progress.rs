@@ -0,0 +1,100 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+use serde::Serialize;
+use wasm_bindgen::prelude::*;
+
+#[wasm_bindgen(typescript_custom_section)]
+const IPROGRESS: &'static str = r#"
+type ProgressPhase = 'buildSpends' | 'buildOutputs' | 'prove' | 'sign' | 'scan';
+
+interface ProgressEvent {
+    phase: ProgressPhase;
+    index: number;
+    total: number;
+}
+
+type ProgressCallback = (event: ProgressEvent) => void;
+"#;
+
+#[wasm_bindgen]
+/// Callback handed to the long running APIs. The ProgressEvent TypeScript
+/// interface above must be manually updated if changing ProgressEvent.
+extern "C" {
+    #[wasm_bindgen(extends = js_sys::Function, typescript_type = "ProgressCallback")]
+    pub type ProgressCallback;
+}
+
+#[derive(Clone, Copy, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) enum ProgressPhase {
+    /// Spend circuits, then output and mint circuits, counted in circuits.
+    /// Proofs are made one at a time and `Prove` counts each of them.
+    BuildSpends,
+    BuildOutputs,
+    Prove,
+    Sign,
+    Scan,
+}
+
+/// `index` counts the units of `phase` completed so far, out of `total`.
+#[derive(Serialize)]
+struct ProgressEvent {
+    phase: ProgressPhase,
+    index: usize,
+    total: usize,
+}
+
+/// Forwards progress events to an optional JS callback. Errors thrown by the
+/// callback are propagated so a caller can also cancel by throwing.
+pub(crate) struct ProgressReporter {
+    callback: Option<ProgressCallback>,
+}
+
+impl ProgressReporter {
+    pub(crate) fn new(callback: Option<ProgressCallback>) -> Self {
+        ProgressReporter { callback }
+    }
+
+    pub(crate) fn report(
+        &self,
+        phase: ProgressPhase,
+        index: usize,
+        total: usize,
+    ) -> Result<(), JsValue> {
+        if let Some(callback) = &self.callback {
+            let event = serde_wasm_bindgen::to_value(&ProgressEvent {
+                phase,
+                index,
+                total,
+            })?;
+            callback.call1(&JsValue::NULL, &event)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_event_shape() {
+        let event = ProgressEvent {
+            phase: ProgressPhase::Prove,
+            index: 2,
+            total: 5,
+        };
+        assert_eq!(
+            serde_json::to_string(&event).unwrap(),
+            r#"{"phase":"prove","index":2,"total":5}"#
+        );
+    }
+
+    #[test]
+    fn test_without_callback_reports_nothing() {
+        let progress = ProgressReporter::new(None);
+        assert!(progress.report(ProgressPhase::BuildSpends, 0, 1).is_ok());
+    }
+}
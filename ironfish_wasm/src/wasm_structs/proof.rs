@@ -2,11 +2,8 @@ use ironfish_rust::transaction::{Bls12, Proof};
 use wasm_bindgen::prelude::*;
 use std::io::Cursor;
 
-#[wasm_bindgen]
-extern "C" {
-    #[wasm_bindgen(js_namespace = console)]
-    fn log(s: &str);
-}
+use super::{panic_hook, ErrorCode, WasmError};
+use crate::logger::{log, LogLevel};
 
 #[wasm_bindgen]
 pub struct WasmProof {
@@ -17,9 +14,11 @@ pub struct WasmProof {
 impl WasmProof {
     #[wasm_bindgen]
     pub fn from_array(v: &[u8]) -> Result<WasmProof, JsValue> {
-        log(&format!("Received {} bytes", v.len()));
+        let _entry = panic_hook::entry!();
+        log!(LogLevel::Debug, "proof", "Received {} bytes", v.len());
         let mut cursor = Cursor::new(v);
-        let proof = Proof::read(&mut cursor).map_err(|e| JsValue::from_str(&e.to_string()))?;
+        let proof = Proof::read(&mut cursor)
+            .map_err(|e| WasmError::new(ErrorCode::MalformedProof, e).with_argument("v"))?;
         Ok(Self { proof })
     }
 }
@@ -0,0 +1,168 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+use bellperson::groth16;
+use ironfish_rust::keys::EphemeralKeyPair;
+use ironfish_rust::sapling_bls12::SAPLING;
+use ironfish_rust::transaction::{Bls12, Proof};
+use ironfish_rust::{ProposedTransaction, PublicAddress, Transaction};
+use ironfish_zkp::proofs::{MintAsset, Output, Spend};
+use rand_core::OsRng;
+use std::vec::IntoIter;
+use wasm_bindgen::prelude::*;
+
+use super::errors::*;
+use super::progress::{ProgressPhase, ProgressReporter};
+use crate::logger;
+
+pub(crate) type Circuits = (
+    Vec<Spend>,
+    Vec<Output>,
+    Vec<EphemeralKeyPair>,
+    Vec<MintAsset>,
+);
+
+/// Posts a transaction one proof at a time, so callers can report progress
+/// or yield to the event loop between the units of work that `post` would
+/// otherwise run in a single blocking call.
+pub(crate) struct ProvingJob {
+    spend_circuits: IntoIter<Spend>,
+    output_circuits: IntoIter<Output>,
+    mint_circuits: IntoIter<MintAsset>,
+    key_pairs: Vec<EphemeralKeyPair>,
+    spend_proofs: Vec<Proof<Bls12>>,
+    output_proofs: Vec<Proof<Bls12>>,
+    mint_proofs: Vec<Proof<Bls12>>,
+    total: usize,
+}
+
+impl ProvingJob {
+    /// `spends` is how many spends the transaction has, for reporting.
+    pub(crate) fn new(
+        transaction: &mut ProposedTransaction,
+        change_goes_to: Option<PublicAddress>,
+        intended_transaction_fee: u64,
+        spends: usize,
+        progress: &ProgressReporter,
+    ) -> Result<Self, JsValue> {
+        let (spend_circuits, output_circuits, key_pairs, mint_circuits) = build_circuits(
+            transaction,
+            change_goes_to,
+            intended_transaction_fee,
+            spends,
+            progress,
+        )?;
+
+        let total = spend_circuits.len() + output_circuits.len() + mint_circuits.len();
+        progress.report(ProgressPhase::Prove, 0, total)?;
+
+        Ok(ProvingJob {
+            spend_proofs: Vec::with_capacity(spend_circuits.len()),
+            output_proofs: Vec::with_capacity(output_circuits.len()),
+            mint_proofs: Vec::with_capacity(mint_circuits.len()),
+            spend_circuits: spend_circuits.into_iter(),
+            output_circuits: output_circuits.into_iter(),
+            mint_circuits: mint_circuits.into_iter(),
+            key_pairs,
+            total,
+        })
+    }
+
+    /// Creates every proof and signs, in one blocking call.
+    pub(crate) fn run(
+        transaction: &mut ProposedTransaction,
+        change_goes_to: Option<PublicAddress>,
+        intended_transaction_fee: u64,
+        spends: usize,
+        progress: &ProgressReporter,
+    ) -> Result<Transaction, JsValue> {
+        let mut job = ProvingJob::new(
+            transaction,
+            change_goes_to,
+            intended_transaction_fee,
+            spends,
+            progress,
+        )?;
+        while job.prove_next(progress)? {}
+        job.finish(transaction, progress)
+    }
+
+    fn proven(&self) -> usize {
+        self.spend_proofs.len() + self.output_proofs.len() + self.mint_proofs.len()
+    }
+
+    /// Creates the next outstanding proof. Returns false once every spend,
+    /// output and mint has been proven.
+    pub(crate) fn prove_next(&mut self, progress: &ProgressReporter) -> Result<bool, JsValue> {
+        if let Some(circuit) = self.spend_circuits.next() {
+            let proof = groth16::create_random_proof(circuit, &SAPLING.spend_params, &mut OsRng)
+                .map_err(proving_error)?;
+            self.spend_proofs.push(proof);
+        } else if let Some(circuit) = self.output_circuits.next() {
+            let proof = groth16::create_random_proof(circuit, &SAPLING.output_params, &mut OsRng)
+                .map_err(proving_error)?;
+            self.output_proofs.push(proof);
+        } else if let Some(circuit) = self.mint_circuits.next() {
+            let proof = groth16::create_random_proof(circuit, &SAPLING.mint_params, &mut OsRng)
+                .map_err(proving_error)?;
+            self.mint_proofs.push(proof);
+        } else {
+            return Ok(false);
+        }
+
+        progress.report(ProgressPhase::Prove, self.proven(), self.total)?;
+        Ok(true)
+    }
+
+    /// Signs the transaction with the proofs created so far.
+    pub(crate) fn finish(
+        self,
+        transaction: &mut ProposedTransaction,
+        progress: &ProgressReporter,
+    ) -> Result<Transaction, JsValue> {
+        let _span = logger::span("post_wasm");
+        progress.report(ProgressPhase::Sign, 0, 1)?;
+        let posted_transaction = transaction
+            .post_wasm(
+                self.spend_proofs,
+                self.output_proofs,
+                self.key_pairs,
+                self.mint_proofs,
+            )
+            .map_err(|e| WasmError::new(ErrorCode::TransactionBuildFailed, e))?;
+        progress.report(ProgressPhase::Sign, 1, 1)?;
+        Ok(posted_transaction)
+    }
+}
+
+/// Builds the circuits of every spend, output and mint in `transaction`.
+/// ironfish_rust builds them all in one call, so `BuildSpends` is reported
+/// before and after it, and `BuildOutputs`, counting change outputs and
+/// mints, once it returns.
+pub(crate) fn build_circuits(
+    transaction: &mut ProposedTransaction,
+    change_goes_to: Option<PublicAddress>,
+    intended_transaction_fee: u64,
+    spends: usize,
+    progress: &ProgressReporter,
+) -> Result<Circuits, JsValue> {
+    let _span = logger::span("build_circuits");
+    progress.report(ProgressPhase::BuildSpends, 0, spends)?;
+    let circuits = transaction
+        .build_circuits(change_goes_to, intended_transaction_fee)
+        .map_err(|e| WasmError::new(ErrorCode::TransactionBuildFailed, e))?;
+    let (spend_circuits, output_circuits, _, mint_circuits) = &circuits;
+    progress.report(
+        ProgressPhase::BuildSpends,
+        spend_circuits.len(),
+        spend_circuits.len(),
+    )?;
+    let outputs = output_circuits.len() + mint_circuits.len();
+    progress.report(ProgressPhase::BuildOutputs, outputs, outputs)?;
+    Ok(circuits)
+}
+
+fn proving_error(e: bellperson::SynthesisError) -> WasmError {
+    WasmError::new(ErrorCode::ProvingFailed, e)
+}
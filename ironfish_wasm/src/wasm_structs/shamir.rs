@@ -0,0 +1,406 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+//! k-of-n Shamir secret sharing of spending keys, with shares written as
+//! words from the BIP39 wordlists.
+//!
+//! Each byte of the key is shared separately over GF(2^8) (the AES field).
+//! A share is 41 bytes, written as 30 words of 11 bits each:
+//!
+//! ```text
+//!  1 version
+//!  2 identifier, random per split, the same in every share of it
+//!  1 threshold
+//!  1 x coordinate, 1..=n
+//! 32 y coordinates, one per key byte
+//!  4 BLAKE2b checksum of the above
+//! ```
+
+use bip39::Language;
+use ironfish_rust::keys::SPEND_KEY_SIZE;
+use rand_core::{OsRng, RngCore};
+use wasm_bindgen::prelude::*;
+use zeroize::{Zeroize, Zeroizing};
+
+use super::mnemonic::{detect_language, suggestions};
+use super::{panic_hook, ErrorCode, WasmError, WasmSpendingKey};
+use crate::LanguageCode;
+
+const SHARE_VERSION: u8 = 1;
+const SHARE_CHECKSUM_PERSONALIZATION: &[u8; 16] = b"Ironfish shares\0";
+const CHECKSUM_LENGTH: usize = 4;
+const PAYLOAD_LENGTH: usize = 5 + SPEND_KEY_SIZE;
+const SHARE_LENGTH: usize = PAYLOAD_LENGTH + CHECKSUM_LENGTH;
+const BITS_PER_WORD: usize = 11;
+pub(crate) const SHARE_WORDS: usize = (SHARE_LENGTH * 8).div_ceil(BITS_PER_WORD);
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct Share {
+    pub(crate) identifier: u16,
+    pub(crate) threshold: u8,
+    pub(crate) x: u8,
+    pub(crate) y: Zeroizing<[u8; SPEND_KEY_SIZE]>,
+}
+
+fn gf_mul(mut a: u8, mut b: u8) -> u8 {
+    // Fixed iteration count and no tables, so timing doesn't depend on the
+    // secret bytes.
+    let mut product = 0;
+    for _ in 0..8 {
+        product ^= a & 0u8.wrapping_sub(b & 1);
+        let carry = 0u8.wrapping_sub(a >> 7);
+        a = (a << 1) ^ (carry & 0x1b);
+        b >>= 1;
+    }
+    product
+}
+
+fn gf_inv(a: u8) -> u8 {
+    // a^254 = a^-1 in GF(2^8)
+    let mut result = 1;
+    let mut power = a;
+    let mut exponent = 254u8;
+    while exponent > 0 {
+        if exponent & 1 == 1 {
+            result = gf_mul(result, power);
+        }
+        power = gf_mul(power, power);
+        exponent >>= 1;
+    }
+    result
+}
+
+fn checksum(payload: &[u8]) -> [u8; CHECKSUM_LENGTH] {
+    let hash = blake2b_simd::Params::new()
+        .hash_length(CHECKSUM_LENGTH)
+        .personal(SHARE_CHECKSUM_PERSONALIZATION)
+        .hash(payload);
+    let mut checksum = [0u8; CHECKSUM_LENGTH];
+    checksum.copy_from_slice(hash.as_bytes());
+    checksum
+}
+
+/// Splits `secret` into `count` shares, any `threshold` of which recover it.
+pub(crate) fn split(
+    secret: &[u8; SPEND_KEY_SIZE],
+    threshold: u8,
+    count: u8,
+    identifier: u16,
+    rng: &mut impl RngCore,
+) -> Result<Vec<Share>, WasmError> {
+    if threshold < 2 {
+        return Err(
+            WasmError::new(ErrorCode::InvalidArgument, "threshold must be at least 2")
+                .with_argument("threshold"),
+        );
+    }
+    if count < threshold {
+        return Err(WasmError::new(
+            ErrorCode::InvalidArgument,
+            "count must be at least the threshold",
+        )
+        .with_argument("count"));
+    }
+
+    // coefficients[i] holds the degree i + 1 coefficient of every byte's
+    // polynomial; the constant term is the secret byte itself.
+    let mut coefficients = Zeroizing::new(vec![[0u8; SPEND_KEY_SIZE]; threshold as usize - 1]);
+    for coefficient in coefficients.iter_mut() {
+        rng.fill_bytes(coefficient);
+    }
+
+    Ok((1..=count)
+        .map(|x| {
+            let mut y = Zeroizing::new([0u8; SPEND_KEY_SIZE]);
+            for (byte, secret_byte) in secret.iter().enumerate() {
+                // Horner's rule, highest degree first
+                let mut value = 0;
+                for coefficient in coefficients.iter().rev() {
+                    value = gf_mul(value, x) ^ coefficient[byte];
+                }
+                y[byte] = gf_mul(value, x) ^ secret_byte;
+            }
+            Share {
+                identifier,
+                threshold,
+                x,
+                y,
+            }
+        })
+        .collect())
+}
+
+/// Recovers the secret from at least `threshold` shares of the same split.
+pub(crate) fn combine(shares: &[Share]) -> Result<Zeroizing<[u8; SPEND_KEY_SIZE]>, WasmError> {
+    let first = shares.first().ok_or_else(|| {
+        WasmError::new(ErrorCode::InvalidArgument, "no shares given").with_argument("shares")
+    })?;
+    for (index, share) in shares.iter().enumerate() {
+        if share.identifier != first.identifier || share.threshold != first.threshold {
+            return Err(WasmError::new(
+                ErrorCode::InvalidArgument,
+                "share belongs to a different split",
+            )
+            .with_argument("shares")
+            .with_index(index));
+        }
+        if shares[..index].iter().any(|other| other.x == share.x) {
+            return Err(
+                WasmError::new(ErrorCode::InvalidArgument, "share was given twice")
+                    .with_argument("shares")
+                    .with_index(index),
+            );
+        }
+    }
+    let threshold = first.threshold as usize;
+    if shares.len() < threshold {
+        return Err(WasmError::new(
+            ErrorCode::InvalidArgument,
+            format!("need {} shares, got {}", threshold, shares.len()),
+        )
+        .with_argument("shares"));
+    }
+
+    // Lagrange interpolation at x = 0; subtraction is xor in GF(2^8)
+    let shares = &shares[..threshold];
+    let mut secret = Zeroizing::new([0u8; SPEND_KEY_SIZE]);
+    for (i, share) in shares.iter().enumerate() {
+        let mut numerator = 1;
+        let mut denominator = 1;
+        for (j, other) in shares.iter().enumerate() {
+            if i != j {
+                numerator = gf_mul(numerator, other.x);
+                denominator = gf_mul(denominator, share.x ^ other.x);
+            }
+        }
+        let basis = gf_mul(numerator, gf_inv(denominator));
+        for (byte, y) in secret.iter_mut().zip(share.y.iter()) {
+            *byte ^= gf_mul(basis, *y);
+        }
+    }
+    Ok(secret)
+}
+
+impl Share {
+    fn to_bytes(&self) -> Zeroizing<[u8; SHARE_LENGTH]> {
+        let mut bytes = Zeroizing::new([0u8; SHARE_LENGTH]);
+        bytes[0] = SHARE_VERSION;
+        bytes[1..3].copy_from_slice(&self.identifier.to_le_bytes());
+        bytes[3] = self.threshold;
+        bytes[4] = self.x;
+        bytes[5..PAYLOAD_LENGTH].copy_from_slice(self.y.as_ref());
+        let checksum = checksum(&bytes[..PAYLOAD_LENGTH]);
+        bytes[PAYLOAD_LENGTH..].copy_from_slice(&checksum);
+        bytes
+    }
+
+    fn from_bytes(bytes: &[u8; SHARE_LENGTH]) -> Result<Share, WasmError> {
+        if checksum(&bytes[..PAYLOAD_LENGTH]) != bytes[PAYLOAD_LENGTH..] {
+            return Err(WasmError::new(
+                ErrorCode::MalformedShare,
+                "checksum does not match; a word may be wrong or out of order",
+            ));
+        }
+        if bytes[0] != SHARE_VERSION {
+            return Err(WasmError::new(
+                ErrorCode::MalformedShare,
+                format!("unsupported share version {}", bytes[0]),
+            ));
+        }
+        if bytes[3] < 2 || bytes[4] == 0 {
+            return Err(WasmError::new(
+                ErrorCode::MalformedShare,
+                "share has an invalid threshold or coordinate",
+            ));
+        }
+        let mut y = Zeroizing::new([0u8; SPEND_KEY_SIZE]);
+        y.copy_from_slice(&bytes[5..PAYLOAD_LENGTH]);
+        Ok(Share {
+            identifier: u16::from_le_bytes([bytes[1], bytes[2]]),
+            threshold: bytes[3],
+            x: bytes[4],
+            y,
+        })
+    }
+
+    pub(crate) fn to_words(&self, language: Language) -> String {
+        let bytes = self.to_bytes();
+        let wordlist = language.wordlist();
+        let mut words = Vec::with_capacity(SHARE_WORDS);
+        for word in 0..SHARE_WORDS {
+            let mut bits = 0u16;
+            for bit in word * BITS_PER_WORD..(word + 1) * BITS_PER_WORD {
+                let set = bytes
+                    .get(bit / 8)
+                    .map_or(0, |byte| (byte >> (7 - bit % 8)) & 1);
+                bits = (bits << 1) | u16::from(set);
+            }
+            words.push(wordlist.get_word(bits.into()));
+        }
+        words.join(" ")
+    }
+
+    pub(crate) fn from_words(
+        phrase: &str,
+        language_code: Option<LanguageCode>,
+    ) -> Result<Share, WasmError> {
+        let language_code = language_code
+            .or_else(|| detect_language(phrase))
+            .ok_or_else(|| {
+                WasmError::new(
+                    ErrorCode::MalformedShare,
+                    "no word is in any supported wordlist",
+                )
+            })?;
+        let language = Language::from(language_code);
+        let words: Vec<&str> = phrase.split_whitespace().collect();
+        if words.len() != SHARE_WORDS {
+            return Err(WasmError::new(
+                ErrorCode::MalformedShare,
+                format!("expected {} words, got {}", SHARE_WORDS, words.len()),
+            ));
+        }
+
+        let wordmap = language.wordmap();
+        let mut bytes = Zeroizing::new([0u8; SHARE_LENGTH]);
+        for (position, word) in words.iter().enumerate() {
+            let normalized = Zeroizing::new(word.to_lowercase());
+            let bits = match wordmap.get_bits(&normalized) {
+                Ok(bits) => u16::from(bits),
+                Err(_) => {
+                    return Err(WasmError::new(
+                        ErrorCode::MalformedShare,
+                        format!("word {} is not in the wordlist", position + 1),
+                    )
+                    .with_suggestions(suggestions(language, &normalized)))
+                }
+            };
+            for offset in 0..BITS_PER_WORD {
+                let bit = position * BITS_PER_WORD + offset;
+                let set = (bits >> (BITS_PER_WORD - 1 - offset)) & 1;
+                match bytes.get_mut(bit / 8) {
+                    Some(byte) => *byte |= (set as u8) << (7 - bit % 8),
+                    // padding past the last byte must be zero
+                    None if set == 1 => {
+                        return Err(WasmError::new(
+                            ErrorCode::MalformedShare,
+                            "checksum does not match; a word may be wrong or out of order",
+                        ))
+                    }
+                    None => {}
+                }
+            }
+        }
+        Share::from_bytes(&bytes)
+    }
+}
+
+/// Splits a spending key into `count` word shares, any `threshold` of which
+/// recover it with `combineSpendingKeyShares`. Every share carries the same
+/// random identifier, so shares of different splits are never combined.
+#[wasm_bindgen(js_name = "splitSpendingKey")]
+pub fn split_spending_key(
+    spending_key: &WasmSpendingKey,
+    threshold: u8,
+    count: u8,
+    language_code: LanguageCode,
+) -> Result<js_sys::Array, JsValue> {
+    let _entry = panic_hook::entry!();
+
+    let secret = Zeroizing::new(spending_key.sapling_key()?.spending_key());
+    let identifier = OsRng.next_u32() as u16;
+    let shares = split(&secret, threshold, count, identifier, &mut OsRng)?;
+    Ok(shares
+        .iter()
+        .map(|share| JsValue::from(share.to_words(language_code.into())))
+        .collect())
+}
+
+/// Recovers a spending key from word shares made by `splitSpendingKey`. The
+/// language of each share is detected when not given. Errors carry the
+/// `index` of the offending share.
+#[wasm_bindgen(js_name = "combineSpendingKeyShares")]
+pub fn combine_spending_key_shares(
+    shares: js_sys::Array,
+    language_code: Option<LanguageCode>,
+) -> Result<WasmSpendingKey, JsValue> {
+    let _entry = panic_hook::entry!();
+
+    let mut parsed = Vec::with_capacity(shares.length() as usize);
+    for (index, share) in shares.iter().enumerate() {
+        let mut phrase = share.as_string().ok_or_else(|| {
+            WasmError::new(ErrorCode::InvalidArgument, "expected a string")
+                .with_argument("shares")
+                .with_index(index)
+        })?;
+        let share = Share::from_words(&phrase, language_code);
+        phrase.zeroize();
+        parsed.push(share.map_err(|e| e.with_argument("shares").with_index(index))?);
+    }
+    let secret = combine(&parsed)?;
+    Ok(WasmSpendingKey::from_bytes(*secret)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::*;
+
+    fn secret() -> [u8; SPEND_KEY_SIZE] {
+        let mut secret = [0u8; SPEND_KEY_SIZE];
+        for (i, byte) in secret.iter_mut().enumerate() {
+            *byte = (i as u8).wrapping_mul(37) ^ 0xa5;
+        }
+        secret
+    }
+
+    #[test]
+    fn test_field_inverse() {
+        for a in 1..=255u8 {
+            assert_eq!(gf_mul(a, gf_inv(a)), 1);
+        }
+    }
+
+    #[test]
+    fn test_any_threshold_subset_recovers() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let shares = split(&secret(), 3, 5, 42, &mut rng).unwrap();
+        for subset in [[0, 1, 2], [4, 2, 0], [1, 3, 4]] {
+            let chosen: Vec<Share> = subset.iter().map(|&i| shares[i].clone()).collect();
+            assert_eq!(*combine(&chosen).unwrap(), secret());
+        }
+        assert!(combine(&shares[..2]).is_err());
+    }
+
+    #[test]
+    fn test_words_round_trip() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let shares = split(&secret(), 2, 3, 42, &mut rng).unwrap();
+        for share in &shares {
+            let words = share.to_words(Language::English);
+            assert_eq!(words.split(' ').count(), SHARE_WORDS);
+            assert_eq!(&Share::from_words(&words, None).unwrap(), share);
+        }
+    }
+
+    #[test]
+    fn test_rejects_mixed_and_corrupted_shares() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let first = split(&secret(), 2, 2, 1, &mut rng).unwrap();
+        let second = split(&secret(), 2, 2, 2, &mut rng).unwrap();
+        let mixed = [first[0].clone(), second[1].clone()];
+        assert_eq!(combine(&mixed).err().unwrap().index, Some(1));
+        let twice = [first[0].clone(), first[0].clone()];
+        assert_eq!(combine(&twice).err().unwrap().index, Some(1));
+
+        let words = first[0].to_words(Language::English);
+        let mut swapped: Vec<&str> = words.split(' ').collect();
+        swapped.swap(3, 4);
+        if swapped.join(" ") != words {
+            let error = Share::from_words(&swapped.join(" "), None).err().unwrap();
+            assert_eq!(error.code, ErrorCode::MalformedShare);
+        }
+    }
+}
@@ -0,0 +1,220 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+//! Message signatures for proving control of an address off chain.
+//!
+//! A public address is `ivk * PUBLIC_KEY_GENERATOR` and the view key ends
+//! with `nk = nsk * PROOF_GENERATION_KEY_GENERATOR`, where the proof
+//! authorizing key `nsk` is derived from the spending key and held by nobody
+//! else. A message carries two RedJubjub signatures over the same digest:
+//!
+//! ```text
+//!  1 version
+//! 64 signature by the incoming view key, checked against the address
+//! 64 signature by the proof authorizing key, checked against nk
+//! ```
+//!
+//! Anyone can check the first against the address, but it only proves the
+//! signer holds the incoming view key, which view-only wallets and auditors
+//! hold too. The second proves the signer holds the spending key, and needs
+//! the signer's view key to check. The digest is
+//! BLAKE2b-256 under "Ironfish message" of the signer's address followed by
+//! the message, so signatures can't be replayed as transaction signatures or
+//! for another address.
+
+use ironfish_rust::{PublicAddress, SaplingKey, ViewKey};
+use ironfish_zkp::constants::{PROOF_GENERATION_KEY_GENERATOR, PUBLIC_KEY_GENERATOR};
+use ironfish_zkp::redjubjub::{PrivateKey, PublicKey, Signature};
+use jubjub::{AffinePoint, ExtendedPoint, Fr, SubgroupPoint};
+use rand_core::OsRng;
+use zeroize::Zeroizing;
+
+use super::view_only_key::incoming_view_key_bytes;
+use super::{ErrorCode, WasmError};
+
+const MESSAGE_PERSONALIZATION: &[u8; 16] = b"Ironfish message";
+const SIGNATURE_VERSION: u8 = 1;
+const REDJUBJUB_SIGNATURE_LENGTH: usize = 64;
+pub(crate) const MESSAGE_SIGNATURE_LENGTH: usize = 1 + 2 * REDJUBJUB_SIGNATURE_LENGTH;
+
+fn digest(address: &[u8; 32], message: &[u8]) -> [u8; 32] {
+    let hash = blake2b_simd::Params::new()
+        .hash_length(32)
+        .personal(MESSAGE_PERSONALIZATION)
+        .to_state()
+        .update(address)
+        .update(message)
+        .finalize();
+    let mut digest = [0u8; 32];
+    digest.copy_from_slice(hash.as_bytes());
+    digest
+}
+
+fn point_bytes(point: SubgroupPoint) -> [u8; 32] {
+    AffinePoint::from(ExtendedPoint::from(point)).to_bytes()
+}
+
+fn read_point(bytes: [u8; 32]) -> Option<ExtendedPoint> {
+    Option::<AffinePoint>::from(AffinePoint::from_bytes(bytes)).map(ExtendedPoint::from)
+}
+
+/// The proof authorizing key, as `ironfish_rust` derives it. Checked against
+/// the view key so a mismatch fails loudly rather than producing signatures
+/// nobody can verify.
+fn proof_authorizing_key(key: &SaplingKey, view_key: &[u8; 64]) -> Result<Fr, WasmError> {
+    let nsk = key.sapling_proof_generation_key().nsk;
+    if view_key[32..] != point_bytes(PROOF_GENERATION_KEY_GENERATOR * nsk) {
+        return Err(WasmError::new(
+            ErrorCode::InvalidSpendingKey,
+            "proof authorizing key does not match the view key",
+        ));
+    }
+    Ok(nsk)
+}
+
+fn incoming_view_key_scalar(view_key: &[u8; 64]) -> Result<Fr, WasmError> {
+    let invalid = || WasmError::new(ErrorCode::InvalidViewKey, "view key has no valid scalar");
+    let bytes = Zeroizing::new(incoming_view_key_bytes(view_key).ok_or_else(invalid)?);
+    Option::<Fr>::from(Fr::from_bytes(&bytes)).ok_or_else(invalid)
+}
+
+fn view_key_bytes(view_key: &ViewKey) -> Result<Zeroizing<[u8; 64]>, WasmError> {
+    let mut bytes = Zeroizing::new([0u8; 64]);
+    let decoded = Zeroizing::new(hex::decode(view_key.hex_key()).unwrap_or_default());
+    if decoded.len() != bytes.len() {
+        return Err(WasmError::new(
+            ErrorCode::InvalidViewKey,
+            "expected a 64 byte view key",
+        ));
+    }
+    bytes.copy_from_slice(&decoded);
+    Ok(bytes)
+}
+
+pub(crate) fn sign(
+    key: &SaplingKey,
+    message: &[u8],
+) -> Result<[u8; MESSAGE_SIGNATURE_LENGTH], WasmError> {
+    let address = key.public_address().public_address();
+    let view_key = view_key_bytes(key.view_key())?;
+    let ivk = incoming_view_key_scalar(&view_key)?;
+    if point_bytes(PUBLIC_KEY_GENERATOR * ivk) != address {
+        return Err(WasmError::new(
+            ErrorCode::InvalidSpendingKey,
+            "incoming view key does not match the public address",
+        ));
+    }
+    let nsk = proof_authorizing_key(key, &view_key)?;
+
+    let digest = digest(&address, message);
+    let mut signature = [0u8; MESSAGE_SIGNATURE_LENGTH];
+    signature[0] = SIGNATURE_VERSION;
+    PrivateKey(ivk)
+        .sign(&digest, &mut OsRng, PUBLIC_KEY_GENERATOR)
+        .write(&mut signature[1..1 + REDJUBJUB_SIGNATURE_LENGTH])
+        .map_err(|e| WasmError::new(ErrorCode::Io, e))?;
+    PrivateKey(nsk)
+        .sign(&digest, &mut OsRng, PROOF_GENERATION_KEY_GENERATOR)
+        .write(&mut signature[1 + REDJUBJUB_SIGNATURE_LENGTH..])
+        .map_err(|e| WasmError::new(ErrorCode::Io, e))?;
+    Ok(signature)
+}
+
+fn read_signatures(signature: &[u8]) -> Result<(Signature, Signature), WasmError> {
+    let malformed = |message: &str| {
+        WasmError::new(ErrorCode::MalformedSignature, message).with_argument("signature")
+    };
+    if signature.len() != MESSAGE_SIGNATURE_LENGTH {
+        return Err(malformed("expected a 129 byte message signature"));
+    }
+    if signature[0] != SIGNATURE_VERSION {
+        return Err(malformed("unsupported message signature version"));
+    }
+    let (address_signature, spend_signature) = signature[1..].split_at(REDJUBJUB_SIGNATURE_LENGTH);
+    Ok((
+        Signature::read(address_signature).map_err(|e| malformed(&e.to_string()))?,
+        Signature::read(spend_signature).map_err(|e| malformed(&e.to_string()))?,
+    ))
+}
+
+/// Checks the incoming view key signature against a public address. This
+/// only proves the signer holds the address's incoming view key.
+pub(crate) fn verify_with_address(
+    address: &PublicAddress,
+    message: &[u8],
+    signature: &[u8],
+) -> Result<bool, WasmError> {
+    let (address_signature, _) = read_signatures(signature)?;
+    let address = address.public_address();
+    let Some(point) = read_point(address) else {
+        return Ok(false);
+    };
+    Ok(PublicKey(point).verify(
+        &digest(&address, message),
+        &address_signature,
+        PUBLIC_KEY_GENERATOR,
+    ))
+}
+
+/// Checks both signatures, deriving the address from the view key. This
+/// proves the signer holds the spending key.
+pub(crate) fn verify_with_view_key(
+    view_key: &ViewKey,
+    message: &[u8],
+    signature: &[u8],
+) -> Result<bool, WasmError> {
+    let (address_signature, spend_signature) = read_signatures(signature)?;
+    let view_key = view_key_bytes(view_key)?;
+    let ivk = incoming_view_key_scalar(&view_key)?;
+    let address_point = PUBLIC_KEY_GENERATOR * ivk;
+    let address = point_bytes(address_point);
+    let mut nk = [0u8; 32];
+    nk.copy_from_slice(&view_key[32..]);
+    let Some(nk) = read_point(nk) else {
+        return Ok(false);
+    };
+
+    let digest = digest(&address, message);
+    Ok(PublicKey(ExtendedPoint::from(address_point)).verify(
+        &digest,
+        &address_signature,
+        PUBLIC_KEY_GENERATOR,
+    ) && PublicKey(nk).verify(&digest, &spend_signature, PROOF_GENERATION_KEY_GENERATOR))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sign_and_verify() {
+        let key = SaplingKey::generate_key();
+        let other = SaplingKey::generate_key();
+        let signature = sign(&key, b"sign in to example.com").unwrap();
+
+        let address = key.public_address();
+        assert!(verify_with_address(&address, b"sign in to example.com", &signature).unwrap());
+        assert!(!verify_with_address(&address, b"sign in to evil.com", &signature).unwrap());
+        assert!(!verify_with_address(
+            &other.public_address(),
+            b"sign in to example.com",
+            &signature
+        )
+        .unwrap());
+        assert!(
+            verify_with_view_key(key.view_key(), b"sign in to example.com", &signature).unwrap()
+        );
+        assert!(
+            !verify_with_view_key(other.view_key(), b"sign in to example.com", &signature).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_rejects_malformed_signature() {
+        for signature in [vec![], vec![SIGNATURE_VERSION; 64], vec![2; 129]] {
+            let error = read_signatures(&signature).err().unwrap();
+            assert_eq!(error.code, ErrorCode::MalformedSignature);
+        }
+    }
+}
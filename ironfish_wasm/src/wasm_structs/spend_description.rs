@@ -5,7 +5,7 @@
 use ironfish_rust::{MerkleNoteHash, SpendDescription};
 use wasm_bindgen::prelude::*;
 
-use super::WasmIronfishError;
+use super::{panic_hook, WasmIronfishError};
 
 #[wasm_bindgen]
 pub struct WasmSpendDescription {
@@ -21,6 +21,7 @@ impl WasmSpendDescription {
 
     #[wasm_bindgen(getter, js_name = "rootHash")]
     pub fn root_hash(&self) -> Result<Vec<u8>, JsValue> {
+        let _entry = panic_hook::entry!();
         let mut cursor: std::io::Cursor<Vec<u8>> = std::io::Cursor::new(vec![]);
         MerkleNoteHash::new(self.description.root_hash())
             .write(&mut cursor)
@@ -0,0 +1,160 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+use ironfish_rust::keys::SPEND_KEY_SIZE;
+use ironfish_rust::SaplingKey;
+use wasm_bindgen::prelude::*;
+use zeroize::{Zeroize, Zeroizing};
+
+use super::{mnemonic, panic_hook, ErrorCode, WasmError, WasmIronfishError};
+use crate::LanguageCode;
+
+/// Opaque handle to a spending key. The key bytes stay in wasm memory, are
+/// only copied out by the explicit `toHex`/`toWords` exports, and are
+/// overwritten with zeros by `wipe()` or when the handle is freed.
+///
+/// Deriving view keys or addresses, or starting a transaction, goes through
+/// ironfish_rust's `SaplingKey`, which doesn't zeroize: those copies are
+/// freed when done with, but not overwritten.
+#[wasm_bindgen]
+pub struct WasmSpendingKey {
+    bytes: Option<Zeroizing<[u8; SPEND_KEY_SIZE]>>,
+}
+
+#[wasm_bindgen]
+impl WasmSpendingKey {
+    #[wasm_bindgen]
+    pub fn generate() -> WasmSpendingKey {
+        let _entry = panic_hook::entry!();
+
+        WasmSpendingKey::from_sapling_key(&SaplingKey::generate_key())
+    }
+
+    /// The hex string is zeroized once it has been parsed.
+    #[wasm_bindgen(js_name = "fromHex")]
+    pub fn from_hex(mut hex: String) -> Result<WasmSpendingKey, JsValue> {
+        let _entry = panic_hook::entry!();
+
+        let key = SaplingKey::from_hex(&hex);
+        hex.zeroize();
+        let key =
+            key.map_err(|e| WasmError::new(ErrorCode::InvalidSpendingKey, e).with_argument("hex"))?;
+        Ok(WasmSpendingKey::from_sapling_key(&key))
+    }
+
+    /// The phrase passed in is zeroized once it has been parsed.
+    #[wasm_bindgen(js_name = "fromWords")]
+    pub fn from_words(
+        words: String,
+        language_code: LanguageCode,
+    ) -> Result<WasmSpendingKey, JsValue> {
+        let _entry = panic_hook::entry!();
+
+        let words = Zeroizing::new(words);
+        Ok(WasmSpendingKey::from_phrase(&words, language_code)?)
+    }
+
+    #[wasm_bindgen(js_name = "toHex")]
+    pub fn to_hex(&self) -> Result<String, JsValue> {
+        Ok(hex::encode(self.bytes()?.as_ref()))
+    }
+
+    #[wasm_bindgen(js_name = "toWords")]
+    pub fn to_words(&self, language_code: LanguageCode) -> Result<String, JsValue> {
+        let _entry = panic_hook::entry!();
+
+        let mnemonic = self
+            .sapling_key()?
+            .to_words(language_code.into())
+            .map_err(WasmIronfishError)?;
+        Ok(mnemonic.into_phrase())
+    }
+
+    #[wasm_bindgen(getter, js_name = "viewKey")]
+    pub fn view_key(&self) -> Result<String, JsValue> {
+        let _entry = panic_hook::entry!();
+        Ok(self.sapling_key()?.view_key().hex_key())
+    }
+
+    #[wasm_bindgen(getter, js_name = "incomingViewKey")]
+    pub fn incoming_view_key(&self) -> Result<String, JsValue> {
+        let _entry = panic_hook::entry!();
+        Ok(self.sapling_key()?.incoming_view_key().hex_key())
+    }
+
+    #[wasm_bindgen(getter, js_name = "outgoingViewKey")]
+    pub fn outgoing_view_key(&self) -> Result<String, JsValue> {
+        let _entry = panic_hook::entry!();
+        Ok(self.sapling_key()?.outgoing_view_key().hex_key())
+    }
+
+    #[wasm_bindgen(getter, js_name = "publicAddress")]
+    pub fn public_address(&self) -> Result<String, JsValue> {
+        let _entry = panic_hook::entry!();
+        Ok(self.sapling_key()?.public_address().hex_public_address())
+    }
+
+    /// Zeroizes the key now rather than when the handle is freed. Any later
+    /// use of the handle throws.
+    #[wasm_bindgen]
+    pub fn wipe(&mut self) {
+        self.bytes.take();
+    }
+}
+
+impl WasmSpendingKey {
+    pub(crate) fn from_sapling_key(key: &SaplingKey) -> WasmSpendingKey {
+        WasmSpendingKey {
+            bytes: Some(Zeroizing::new(key.spending_key())),
+        }
+    }
+
+    /// `fromWords` for a phrase owned by the caller, who zeroizes it.
+    pub(crate) fn from_phrase(
+        phrase: &str,
+        language_code: LanguageCode,
+    ) -> Result<WasmSpendingKey, WasmError> {
+        let bytes = mnemonic::spending_key_bytes(phrase, language_code)?;
+        WasmSpendingKey::from_bytes(*bytes)
+    }
+
+    pub(crate) fn from_bytes(bytes: [u8; SPEND_KEY_SIZE]) -> Result<WasmSpendingKey, WasmError> {
+        let key =
+            SaplingKey::new(bytes).map_err(|e| WasmError::new(ErrorCode::InvalidSpendingKey, e))?;
+        Ok(WasmSpendingKey::from_sapling_key(&key))
+    }
+
+    fn bytes(&self) -> Result<&Zeroizing<[u8; SPEND_KEY_SIZE]>, WasmError> {
+        self.bytes
+            .as_ref()
+            .ok_or_else(|| WasmError::new(ErrorCode::UseAfterWipe, "spending key has been wiped"))
+    }
+
+    /// Not zeroized when dropped, so keep it no longer than needed.
+    pub(crate) fn sapling_key(&self) -> Result<SaplingKey, WasmError> {
+        SaplingKey::new(**self.bytes()?)
+            .map_err(|e| WasmError::new(ErrorCode::InvalidSpendingKey, e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wipe_zeroizes_and_refuses_later_use() {
+        let mut key = WasmSpendingKey {
+            bytes: Some(Zeroizing::new([7; SPEND_KEY_SIZE])),
+        };
+        assert_eq!(**key.bytes().ok().unwrap(), [7; SPEND_KEY_SIZE]);
+
+        key.wipe();
+        assert!(key.bytes.is_none());
+        assert_eq!(key.bytes().err().unwrap().code, ErrorCode::UseAfterWipe);
+        assert_eq!(
+            key.sapling_key().err().unwrap().code,
+            ErrorCode::UseAfterWipe
+        );
+    }
+}
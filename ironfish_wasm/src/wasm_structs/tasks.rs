@@ -0,0 +1,55 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+use js_sys::Promise;
+use std::future::Future;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen_futures::{future_to_promise, JsFuture};
+use web_sys::AbortSignal;
+
+use super::{panic_hook, ErrorCode, WasmError};
+
+#[wasm_bindgen]
+extern "C" {
+    #[wasm_bindgen(js_name = "setTimeout")]
+    fn set_timeout(handler: &js_sys::Function, timeout: i32) -> JsValue;
+}
+
+/// Resolves on the next macrotask, giving the host a chance to render and
+/// handle input between units of heavy work. A microtask would not be enough
+/// here since the browser only paints once the microtask queue is drained.
+pub(crate) async fn yield_now() -> Result<(), JsValue> {
+    let promise = Promise::new(&mut |resolve, _reject| {
+        set_timeout(&resolve, 0);
+    });
+    JsFuture::from(promise).await?;
+    Ok(())
+}
+
+/// Fails with an `AbortError` if the caller has aborted the given signal.
+pub(crate) fn check_aborted(signal: &Option<AbortSignal>) -> Result<(), JsValue> {
+    match signal {
+        Some(signal) if signal.aborted() => {
+            Err(WasmError::new(ErrorCode::Aborted, "The operation was aborted").into())
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Checks the signal, then yields to the event loop and checks it again, so
+/// an abort requested while we were suspended is honoured right away.
+pub(crate) async fn checkpoint(signal: &Option<AbortSignal>) -> Result<(), JsValue> {
+    check_aborted(signal)?;
+    yield_now().await?;
+    check_aborted(signal)
+}
+
+/// `future_to_promise`, with `function` named in reports of panics raised
+/// while the future is being polled.
+pub(crate) fn to_promise<F>(function: &'static str, future: F) -> Promise
+where
+    F: Future<Output = Result<JsValue, JsValue>> + 'static,
+{
+    future_to_promise(panic_hook::instrument(function, future))
+}
@@ -2,11 +2,16 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
 
+use super::address::parse_public_address;
+use super::assets::read_asset_id;
+use super::contents::{MintRecord, SpendRecord, TransactionContents};
+use super::ephemeral_key_pair::EPHEMERAL_KEY_PAIR_LENGTH;
 use super::get_encrypted_note_length;
+use super::in_flight::Slot;
+use super::network::{tag_transaction, untag_transaction, NetworkConfig};
 use super::WasmAsset;
 use super::WasmEphemeralKeyPair;
 use super::WasmProof;
-use ironfish_rust::assets::asset_identifier::AssetIdentifier;
 use ironfish_rust::keys::EphemeralKeyPair;
 use ironfish_rust::transaction::outputs::PROOF_SIZE;
 use ironfish_rust::transaction::TransactionVersion;
@@ -15,15 +20,30 @@ use ironfish_rust::transaction::TRANSACTION_FEE_SIZE;
 use ironfish_rust::transaction::TRANSACTION_PUBLIC_KEY_SIZE;
 use ironfish_rust::transaction::TRANSACTION_SIGNATURE_SIZE;
 use wasm_bindgen::prelude::*;
-use js_sys::Uint8Array;
+use zeroize::Zeroize;
+use js_sys::{Array, Promise, Uint8Array};
+use std::cell::{Ref, RefCell};
+use std::rc::Rc;
+use web_sys::AbortSignal;
 
 use ironfish_rust::{ProposedTransaction, PublicAddress, SaplingKey, Transaction};
 
 use super::errors::*;
 use super::note::WasmNote;
 use super::panic_hook;
+use super::payment_batch::{parse_payment_list, WasmPaymentBatch};
+use super::pending::{
+    WasmAssetTotal, WasmBalanceReport, WasmPendingBurn, WasmPendingMint, WasmPendingOutput,
+    WasmPendingSpend,
+};
+use super::progress::{ProgressCallback, ProgressReporter};
+use super::proving::{build_circuits, ProvingJob};
+use super::tasks::{checkpoint, to_promise};
+use super::validation::{validate, ValidationContext};
 use super::witness::{JsWitness, JsWitness1};
 use super::WasmSpendDescription;
+use super::WasmSpendingKey;
+use crate::logger;
 
 #[wasm_bindgen]
 pub fn get_proof_length() -> u32 {
@@ -97,21 +117,64 @@ impl TransactionCircuits {
 #[wasm_bindgen]
 pub struct WasmTransactionPosted {
     transaction: Transaction,
+    // Id of the network the transaction was built for, when known
+    network_id: Option<u32>,
 }
 
 #[wasm_bindgen]
 impl WasmTransactionPosted {
     #[wasm_bindgen(constructor)]
     pub fn new(bytes: &[u8]) -> Result<WasmTransactionPosted, JsValue> {
-        panic_hook::set_once();
+        let _entry = panic_hook::entry!();
 
         let mut cursor: std::io::Cursor<&[u8]> = std::io::Cursor::new(bytes);
-        let transaction = Transaction::read(&mut cursor).map_err(WasmIronfishError)?;
-        Ok(WasmTransactionPosted { transaction })
+        let transaction = Transaction::read(&mut cursor).map_err(|e| {
+            WasmError::new(ErrorCode::MalformedTransaction, e).with_argument("bytes")
+        })?;
+        Ok(WasmTransactionPosted {
+            transaction,
+            network_id: None,
+        })
+    }
+
+    /// Reads a transaction serialized with `serializeForNetwork`, rejecting
+    /// one built for another network.
+    #[wasm_bindgen(js_name = "deserializeForNetwork")]
+    pub fn deserialize_for_network(
+        bytes: &[u8],
+        network: &NetworkConfig,
+    ) -> Result<WasmTransactionPosted, JsValue> {
+        let _entry = panic_hook::entry!();
+
+        let bytes = untag_transaction(network.network_id(), bytes)?;
+        let mut posted = WasmTransactionPosted::new(bytes)?;
+        posted.network_id = Some(network.network_id());
+        Ok(posted)
+    }
+
+    /// Serializes the transaction prefixed with the id of its network, so
+    /// tooling for another network refuses it. Fails for a transaction
+    /// built for a network other than `network`.
+    #[wasm_bindgen(js_name = "serializeForNetwork")]
+    pub fn serialize_for_network(&self, network: &NetworkConfig) -> Result<Vec<u8>, JsValue> {
+        let _entry = panic_hook::entry!();
+
+        if let Some(network_id) = self.network_id {
+            untag_transaction(network.network_id(), &tag_transaction(network_id, &[]))?;
+        }
+        let bytes = self.serialize()?;
+        Ok(tag_transaction(network.network_id(), &bytes))
+    }
+
+    /// Id of the network the transaction was built or read for.
+    #[wasm_bindgen(getter, js_name = "networkId")]
+    pub fn network_id(&self) -> Option<u32> {
+        self.network_id
     }
 
     #[wasm_bindgen]
     pub fn serialize(&self) -> Result<Vec<u8>, JsValue> {
+        let _entry = panic_hook::entry!();
         let mut cursor: std::io::Cursor<Vec<u8>> = std::io::Cursor::new(vec![]);
         self.transaction
             .write(&mut cursor)
@@ -126,7 +189,11 @@ impl WasmTransactionPosted {
 
     #[wasm_bindgen(js_name = "getNote")]
     pub fn get_note(&self, index: usize) -> Result<Vec<u8>, JsValue> {
-        let description = &self.transaction.outputs()[index];
+        let _entry = panic_hook::entry!();
+        let outputs = self.transaction.outputs();
+        let description = outputs
+            .get(index)
+            .ok_or_else(|| index_out_of_bounds(index, outputs.len()))?;
         // Note bytes are 275
         let mut cursor: Vec<u8> = Vec::with_capacity(get_encrypted_note_length() as usize);
         description
@@ -142,11 +209,15 @@ impl WasmTransactionPosted {
     }
 
     #[wasm_bindgen(js_name = "getSpend")]
-    pub fn get_spend(&self, index: usize) -> WasmSpendDescription {
-        let description = &self.transaction.spends()[index];
-        WasmSpendDescription {
+    pub fn get_spend(&self, index: usize) -> Result<WasmSpendDescription, JsValue> {
+        let _entry = panic_hook::entry!();
+        let spends = self.transaction.spends();
+        let description = spends
+            .get(index)
+            .ok_or_else(|| index_out_of_bounds(index, spends.len()))?;
+        Ok(WasmSpendDescription {
             description: description.clone(),
-        }
+        })
     }
 
     #[wasm_bindgen(getter, js_name = "fee")]
@@ -156,6 +227,7 @@ impl WasmTransactionPosted {
 
     #[wasm_bindgen(getter, js_name = "transactionSignature")]
     pub fn transaction_signature(&self) -> Result<Vec<u8>, JsValue> {
+        let _entry = panic_hook::entry!();
         let mut serialized_signature = vec![];
         self.transaction
             .binding_signature()
@@ -166,6 +238,7 @@ impl WasmTransactionPosted {
 
     #[wasm_bindgen(getter, js_name = "hash")]
     pub fn hash(&self) -> Result<Vec<u8>, JsValue> {
+        let _entry = panic_hook::entry!();
         let hash = self
             .transaction
             .transaction_signature_hash()
@@ -179,8 +252,11 @@ impl WasmTransactionPosted {
     }
 
     pub fn to_hex(&self) -> Result<String, JsValue> {
+        let _entry = panic_hook::entry!();
         let mut vec: Vec<u8> = vec![];
-        self.transaction.write(&mut vec).map_err(|e| e.to_string())?;
+        self.transaction
+            .write(&mut vec)
+            .map_err(WasmIronfishError)?;
         let signed_transaction = hex::encode(vec);
         Ok(signed_transaction)
     }
@@ -188,39 +264,111 @@ impl WasmTransactionPosted {
 
 #[wasm_bindgen]
 pub struct WasmTransaction {
-    transaction: ProposedTransaction,
+    // The async variants below take the transaction out of its slot until
+    // they settle, and keep working on it after returning to JS
+    transaction: Slot<ProposedTransaction>,
+    // What has been added so far, since ProposedTransaction doesn't say
+    contents: Rc<RefCell<TransactionContents>>,
+    network_id: Option<u32>,
 }
 
 #[wasm_bindgen]
 impl WasmTransaction {
     #[wasm_bindgen(constructor)]
-    pub fn new(spender_hex_key: String, version: u8) -> Result<WasmTransaction, JsValue> {
-        panic_hook::set_once();
+    pub fn new(mut spender_hex_key: String, version: u8) -> Result<WasmTransaction, JsValue> {
+        let _entry = panic_hook::entry!();
+
+        let spender_key = SaplingKey::from_hex(&spender_hex_key);
+        spender_hex_key.zeroize();
+        let spender_key = spender_key.map_err(|e| {
+            WasmError::new(ErrorCode::InvalidSpendingKey, e).with_argument("spender_hex_key")
+        })?;
+        Self::with_key(spender_key, version)
+    }
+
+    /// Same as the constructor, but takes the spending key as a handle so the
+    /// key never has to exist as a hex string on the JS side.
+    #[wasm_bindgen(js_name = "fromSpendingKey")]
+    pub fn from_spending_key(
+        spender_key: &WasmSpendingKey,
+        version: u8,
+    ) -> Result<WasmTransaction, JsValue> {
+        let _entry = panic_hook::entry!();
+
+        Self::with_key(spender_key.sapling_key()?, version)
+    }
+
+    /// Starts a transaction for `network`, with the version it requires for
+    /// a transaction mined after `head_sequence`, or its latest version when
+    /// the head isn't known. Posted transactions remember the network.
+    #[wasm_bindgen(js_name = "forNetwork")]
+    pub fn for_network(
+        spender_key: &WasmSpendingKey,
+        network: &NetworkConfig,
+        head_sequence: Option<u32>,
+    ) -> Result<WasmTransaction, JsValue> {
+        let _entry = panic_hook::entry!();
+
+        let version = network.version_for_head(head_sequence);
+        let mut transaction = Self::with_key(spender_key.sapling_key()?, version)?;
+        transaction.network_id = Some(network.network_id());
+        Ok(transaction)
+    }
 
-        let spender_key = SaplingKey::from_hex(&spender_hex_key).map_err(WasmIronfishError)?;
-        let tx_version = version.try_into().map_err(WasmIronfishError)?;
+    pub(crate) fn with_key(
+        spender_key: SaplingKey,
+        version: u8,
+    ) -> Result<WasmTransaction, JsValue> {
+        let tx_version = version.try_into().map_err(|e| {
+            WasmError::new(ErrorCode::InvalidTransactionVersion, e).with_argument("version")
+        })?;
         let transaction = ProposedTransaction::new(spender_key, tx_version);
 
-        Ok(WasmTransaction { transaction })
+        Ok(WasmTransaction {
+            transaction: Slot::new(transaction),
+            contents: Rc::new(RefCell::new(TransactionContents::new(version))),
+            network_id: None,
+        })
+    }
+
+    /// Drops the proposed transaction without waiting for the JS object to
+    /// be garbage collected. Its copy of the spending key is freed, but not
+    /// overwritten: ironfish_rust doesn't zeroize it. Any later call,
+    /// including a pending async one, throws with code `UseAfterWipe`.
+    #[wasm_bindgen]
+    pub fn wipe(&mut self) {
+        self.transaction.wipe();
+        self.contents.take();
     }
 
     /// Create a proof of a new note owned by the recipient in this transaction.
     #[wasm_bindgen]
     pub fn output(&mut self, note: &WasmNote) -> Result<String, JsValue> {
+        let _entry = panic_hook::entry!();
         self.transaction
+            .get_mut()?
             .add_output(note.note.clone())
-            .map_err(WasmIronfishError)?;
+            .map_err(|e| WasmError::new(ErrorCode::TransactionBuildFailed, e))?;
+        self.contents.borrow_mut().outputs.push(note.note.clone());
         Ok("".to_string())
     }
 
     /// Spend the note owned by spender_hex_key at the given witness location.
     #[wasm_bindgen]
     pub fn spend(&mut self, note: &WasmNote, witness: JsWitness) -> Result<String, JsValue> {
-        let witness = JsWitness1 { obj: witness };
+        let _entry = panic_hook::entry!();
+        let witness = JsWitness1::new(witness)?;
         // println!("spend: {:?}", witness);
         self.transaction
+            .get_mut()?
             .add_spend(note.note.clone(), &witness)
-            .map_err(WasmIronfishError)?;
+            .map_err(|e| WasmError::new(ErrorCode::TransactionBuildFailed, e))?;
+        self.contents.borrow_mut().spends.push(SpendRecord {
+            note: note.note.clone(),
+            position: witness.position(),
+            root_hash: witness.obj.serialize_root_hash(),
+            tree_size: witness.obj.tree_size(),
+        });
         Ok("".to_string())
     }
 
@@ -232,18 +380,29 @@ impl WasmTransaction {
         value: u64,
         transfer_ownership_to: Option<String>,
     ) -> Result<String, JsValue> {
-        match transfer_ownership_to {
-            None => self
-                .transaction
-                .add_mint(asset.asset, value)
-                .map_err(WasmIronfishError)?,
+        let _entry = panic_hook::entry!();
+        let transfer_ownership_to = match transfer_ownership_to {
+            None => {
+                self.transaction
+                    .get_mut()?
+                    .add_mint(asset.asset, value)
+                    .map_err(|e| WasmError::new(ErrorCode::TransactionBuildFailed, e))?;
+                None
+            }
             Some(new_owner) => {
-                let new_owner = PublicAddress::from_hex(&new_owner).map_err(WasmIronfishError)?;
+                let new_owner = parse_public_address(&new_owner, "transfer_ownership_to")?;
                 self.transaction
-                    .add_mint_with_new_owner(asset.asset, value, new_owner)
-                    .map_err(WasmIronfishError)?;
+                    .get_mut()?
+                    .add_mint_with_new_owner(asset.asset, value, new_owner.clone())
+                    .map_err(|e| WasmError::new(ErrorCode::TransactionBuildFailed, e))?;
+                Some(new_owner)
             }
-        }
+        };
+        self.contents.borrow_mut().mints.push(MintRecord {
+            asset: asset.asset,
+            value,
+            transfer_ownership_to,
+        });
 
         Ok("".to_string())
     }
@@ -251,11 +410,16 @@ impl WasmTransaction {
     /// Burn some supply of a given asset and value as part of this transaction.
     #[wasm_bindgen]
     pub fn burn(&mut self, asset_id_bytes: &[u8], value: u64) -> Result<String, JsValue> {
-        let asset_id =
-            AssetIdentifier::new(asset_id_bytes.try_into().unwrap()).map_err(WasmIronfishError)?;
+        let _entry = panic_hook::entry!();
+        let asset_id = read_asset_id(asset_id_bytes, "asset_id_bytes")?;
         self.transaction
+            .get_mut()?
             .add_burn(asset_id, value)
-            .map_err(WasmIronfishError)?;
+            .map_err(|e| WasmError::new(ErrorCode::TransactionBuildFailed, e))?;
+        self.contents
+            .borrow_mut()
+            .burns
+            .push((*asset_id.as_bytes(), value));
 
         Ok("".to_string())
     }
@@ -267,11 +431,16 @@ impl WasmTransaction {
     /// as the miners fee.
     #[wasm_bindgen]
     pub fn post_miners_fee(&mut self) -> Result<WasmTransactionPosted, JsValue> {
+        let _entry = panic_hook::entry!();
         let transaction = self
             .transaction
+            .get_mut()?
             .post_miners_fee()
-            .map_err(WasmIronfishError)?;
-        Ok(WasmTransactionPosted { transaction })
+            .map_err(|e| WasmError::new(ErrorCode::TransactionBuildFailed, e))?;
+        Ok(WasmTransactionPosted {
+            transaction,
+            network_id: self.network_id,
+        })
     }
 
     /// Post the transaction. This performs a bit of validation, and signs
@@ -284,25 +453,43 @@ impl WasmTransaction {
     ///
     /// sum(spends) - sum(outputs) - intended_transaction_fee - change = 0
     /// aka: self.transaction_fee - intended_transaction_fee - change = 0
+    ///
+    /// When some asset can't cover its outputs, burns and fee this fails
+    /// with a `BalanceError` for that asset; see `balanceReport`.
+    ///
+    /// Building the circuits adds the change outputs for this fee, so if
+    /// posting fails after that the transaction is wiped, and later calls
+    /// throw with code `UseAfterWipe`.
     #[wasm_bindgen]
     pub fn post(
         &mut self,
         change_goes_to: Option<String>,
         intended_transaction_fee: u64,
     ) -> Result<WasmTransactionPosted, JsValue> {
-        let change_key = match change_goes_to {
-            Some(s) => Some(PublicAddress::from_hex(&s).map_err(WasmIronfishError)?),
-            None => None,
-        };
-
-        let posted_transaction = self
-            .transaction
-            .post(change_key, intended_transaction_fee)
-            .map_err(WasmIronfishError)?;
+        let _entry = panic_hook::entry!();
+        self.post_reporting(
+            change_goes_to,
+            intended_transaction_fee,
+            ProgressReporter::new(None),
+        )
+    }
 
-        Ok(WasmTransactionPosted {
-            transaction: posted_transaction,
-        })
+    /// `post`, telling `progress` about building the circuits, each proof
+    /// and signing. A callback that throws stops posting, which wipes the
+    /// transaction as any other failure would.
+    #[wasm_bindgen(js_name = "postWithProgress")]
+    pub fn post_with_progress(
+        &mut self,
+        change_goes_to: Option<String>,
+        intended_transaction_fee: u64,
+        progress: ProgressCallback,
+    ) -> Result<WasmTransactionPosted, JsValue> {
+        let _entry = panic_hook::entry!();
+        self.post_reporting(
+            change_goes_to,
+            intended_transaction_fee,
+            ProgressReporter::new(Some(progress)),
+        )
     }
 
     #[wasm_bindgen]
@@ -313,30 +500,15 @@ impl WasmTransaction {
         output_diffie_hellman_keys: Vec<WasmEphemeralKeyPair>,
         mint_asset_proofs: Vec<WasmProof>,
     ) -> Result<WasmTransactionPosted, JsValue> {
-        let spend_proofs = spend_proofs
-            .iter()
-            .map(|proof| proof.proof.clone())
-            .collect();
-        let output_proofs = output_proofs
-            .iter()
-            .map(|proof| proof.proof.clone())
-            .collect();
-        let key_pairs = output_diffie_hellman_keys
-            .iter()
-            .map(|key_pair| EphemeralKeyPair::from_bytes_le(key_pair.key_pair_bytes.clone()))
-            .collect();
-        let mint_asset_proofs = mint_asset_proofs
-            .iter()
-            .map(|proof| proof.proof.clone())
-            .collect();
-        let posted_transaction = self
-            .transaction
-            .post_wasm(spend_proofs, output_proofs, key_pairs, mint_asset_proofs)
-            .map_err(WasmIronfishError)?;
-
-        Ok(WasmTransactionPosted {
-            transaction: posted_transaction,
-        })
+        let _entry = panic_hook::entry!();
+        post_with_proofs(
+            &mut *self.transaction.get_mut()?,
+            spend_proofs,
+            output_proofs,
+            output_diffie_hellman_keys,
+            mint_asset_proofs,
+            self.network_id,
+        )
     }
 
     #[wasm_bindgen]
@@ -345,53 +517,484 @@ impl WasmTransaction {
         change_goes_to: Option<String>,
         intended_transaction_fee: u64,
     ) -> Result<TransactionCircuits, JsValue> {
-        let change_key = match change_goes_to {
-            Some(s) => Some(PublicAddress::from_hex(&s).map_err(WasmIronfishError)?),
-            None => None,
-        };
+        let _entry = panic_hook::entry!();
+        self.build_circuits_reporting(
+            change_goes_to,
+            intended_transaction_fee,
+            ProgressReporter::new(None),
+        )
+    }
+
+    /// `build_circuits`, telling `progress` about the spend and output
+    /// circuits. A callback that throws once building has started wipes the
+    /// transaction, since its change outputs were already added.
+    #[wasm_bindgen(js_name = "buildCircuitsWithProgress")]
+    pub fn build_circuits_with_progress(
+        &mut self,
+        change_goes_to: Option<String>,
+        intended_transaction_fee: u64,
+        progress: ProgressCallback,
+    ) -> Result<TransactionCircuits, JsValue> {
+        let _entry = panic_hook::entry!();
+        self.build_circuits_reporting(
+            change_goes_to,
+            intended_transaction_fee,
+            ProgressReporter::new(Some(progress)),
+        )
+    }
+
+    /// Async counterpart of `postWithProgress`. The circuits are built up
+    /// front and each spend, output and mint proof is then created in turn,
+    /// yielding to the event loop in between. Resolves to a
+    /// `WasmTransactionPosted`, or rejects with an `AbortError` if `signal`
+    /// is aborted before signing. Aborting or failing once the circuits are
+    /// being built wipes the transaction, as for `post`.
+    ///
+    /// Until the promise settles the transaction can't be changed or posted
+    /// again; calls that would throw with code `OperationInFlight`. The
+    /// other async variants below hold the transaction the same way.
+    #[wasm_bindgen]
+    pub fn post_async(
+        &self,
+        change_goes_to: Option<String>,
+        intended_transaction_fee: u64,
+        signal: Option<AbortSignal>,
+        progress: Option<ProgressCallback>,
+    ) -> Promise {
+        let transaction = self.transaction.take("WasmTransaction.post_async");
+        let contents = self.contents.clone();
+        let network_id = self.network_id;
+        to_promise(panic_hook::function_name!(), async move {
+            let mut transaction = transaction?;
+            let change_key = parse_change_address(change_goes_to)?;
+            contents.borrow().check_balance(intended_transaction_fee)?;
+            let spends = contents.borrow().spends.len();
+            let progress = ProgressReporter::new(progress);
+            checkpoint(&signal).await?;
+
+            let posted_transaction = async {
+                let mut job = ProvingJob::new(
+                    transaction.get_mut()?,
+                    change_key,
+                    intended_transaction_fee,
+                    spends,
+                    &progress,
+                )?;
+                loop {
+                    checkpoint(&signal).await?;
+                    if !job.prove_next(&progress)? {
+                        break;
+                    }
+                }
+                job.finish(transaction.get_mut()?, &progress)
+            }
+            .await
+            .inspect_err(|_| transaction.wipe())?;
+            Ok(WasmTransactionPosted {
+                transaction: posted_transaction,
+                network_id,
+            }
+            .into())
+        })
+    }
+
+    /// Async counterpart of `post_wasm`. Resolves to a `WasmTransactionPosted`.
+    #[wasm_bindgen]
+    pub fn post_wasm_async(
+        &self,
+        spend_proofs: Vec<WasmProof>,
+        output_proofs: Vec<WasmProof>,
+        output_diffie_hellman_keys: Vec<WasmEphemeralKeyPair>,
+        mint_asset_proofs: Vec<WasmProof>,
+        signal: Option<AbortSignal>,
+    ) -> Promise {
+        let transaction = self.transaction.take("WasmTransaction.post_wasm_async");
+        let network_id = self.network_id;
+        to_promise(panic_hook::function_name!(), async move {
+            let mut transaction = transaction?;
+            checkpoint(&signal).await?;
+            let posted = post_with_proofs(
+                transaction.get_mut()?,
+                spend_proofs,
+                output_proofs,
+                output_diffie_hellman_keys,
+                mint_asset_proofs,
+                network_id,
+            )?;
+            Ok(posted.into())
+        })
+    }
 
-        let (spend_circuits, output_circuits, output_diffie_hellman_keys, mint_circuits) = self
+    /// Async counterpart of `build_circuits`. Resolves to a `TransactionCircuits`.
+    #[wasm_bindgen]
+    pub fn build_circuits_async(
+        &self,
+        change_goes_to: Option<String>,
+        intended_transaction_fee: u64,
+        signal: Option<AbortSignal>,
+        progress: Option<ProgressCallback>,
+    ) -> Promise {
+        let transaction = self
             .transaction
-            .build_circuits(change_key, intended_transaction_fee)
-            .map_err(WasmIronfishError)?;
-        let spend_circuits: Vec<Vec<u8>> = spend_circuits
+            .take("WasmTransaction.build_circuits_async");
+        let contents = self.contents.clone();
+        to_promise(panic_hook::function_name!(), async move {
+            let mut transaction = transaction?;
+            let change_key = parse_change_address(change_goes_to)?;
+            contents.borrow().check_balance(intended_transaction_fee)?;
+            checkpoint(&signal).await?;
+            let spends = contents.borrow().spends.len();
+            let circuits = build_transaction_circuits(
+                transaction.get_mut()?,
+                change_key,
+                intended_transaction_fee,
+                spends,
+                &ProgressReporter::new(progress),
+            )
+            .inspect_err(|_| transaction.wipe())?;
+            Ok(circuits.into())
+        })
+    }
+
+    /// Size in bytes of the transaction `post` would build with this fee,
+    /// including the change outputs it adds. The fee defaults to 0.
+    #[wasm_bindgen]
+    pub fn estimated_size(&self, intended_transaction_fee: Option<u64>) -> Result<usize, JsValue> {
+        let _entry = panic_hook::entry!();
+        Ok(self
+            .contents()?
+            .shape(intended_transaction_fee.unwrap_or(0))
+            .size())
+    }
+
+    /// The fee to post with at `fee_rate_per_kb` ore per kilobyte, rounded
+    /// up, allowing for the change output the fee leaves room for.
+    #[wasm_bindgen]
+    pub fn estimate_fee(&self, fee_rate_per_kb: u64) -> Result<u64, JsValue> {
+        let _entry = panic_hook::entry!();
+        Ok(self.contents()?.fee(fee_rate_per_kb))
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn version(&self) -> Result<u8, JsValue> {
+        Ok(self.contents()?.version)
+    }
+
+    #[wasm_bindgen(getter, js_name = "expirationSequence")]
+    pub fn expiration_sequence(&self) -> Result<u32, JsValue> {
+        Ok(self.contents()?.expiration)
+    }
+
+    /// The spends added so far, as `WasmPendingSpend`s.
+    #[wasm_bindgen(getter)]
+    pub fn spends(&self) -> Result<Array, JsValue> {
+        Ok(self
+            .contents()?
+            .spends
             .iter()
             .map(|spend| {
-                let mut spend_bytes = vec![];
-                spend.write(&mut spend_bytes).unwrap();
-                spend_bytes
+                JsValue::from(WasmPendingSpend {
+                    spend: spend.clone(),
+                })
             })
-            .collect();
-        let output_circuits: Vec<Vec<u8>> = output_circuits
+            .collect())
+    }
+
+    /// The outputs added so far, as `WasmPendingOutput`s. Change outputs
+    /// are only added by `post`, so aren't among them.
+    #[wasm_bindgen(getter)]
+    pub fn outputs(&self) -> Result<Array, JsValue> {
+        Ok(self
+            .contents()?
+            .outputs
             .iter()
-            .map(|output| {
-                let mut output_bytes = vec![];
-                output.write(&mut output_bytes).unwrap();
-                output_bytes
-            })
-            .collect();
-        let output_diffie_hellman_keys: Vec<Vec<u8>> = output_diffie_hellman_keys
+            .map(|note| JsValue::from(WasmPendingOutput { note: note.clone() }))
+            .collect())
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn mints(&self) -> Result<Array, JsValue> {
+        Ok(self
+            .contents()?
+            .mints
             .iter()
-            .map(|keys| keys.to_bytes_le())
-            .collect();
-        let mint_asset_circuits: Vec<Vec<u8>> = mint_circuits
+            .map(|mint| JsValue::from(WasmPendingMint { mint: mint.clone() }))
+            .collect())
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn burns(&self) -> Result<Array, JsValue> {
+        Ok(self
+            .contents()?
+            .burns
             .iter()
-            .map(|mint_asset| {
-                let mut mint_asset_bytes = vec![];
-                mint_asset.write(&mut mint_asset_bytes).unwrap();
-                mint_asset_bytes
-            })
-            .collect();
-        Ok(TransactionCircuits {
-            spend_circuits,
-            output_circuits,
-            output_diffie_hellman_keys,
-            mint_asset_circuits,
+            .map(|&(asset_id, value)| JsValue::from(WasmPendingBurn { asset_id, value }))
+            .collect())
+    }
+
+    /// Value in and out of each asset the transaction moves, as
+    /// `WasmAssetTotal`s, with the change posting it with
+    /// `intended_transaction_fee` would leave. The fee defaults to 0.
+    #[wasm_bindgen(js_name = "assetTotals")]
+    pub fn asset_totals(&self, intended_transaction_fee: Option<u64>) -> Result<Array, JsValue> {
+        Ok(self
+            .contents()?
+            .asset_balances(intended_transaction_fee.unwrap_or(0))
+            .into_iter()
+            .map(|balance| JsValue::from(WasmAssetTotal { balance }))
+            .collect())
+    }
+
+    /// Checks, without posting, whether every asset covers its outputs,
+    /// burns and `intended_transaction_fee`, and reports what each is short.
+    #[wasm_bindgen(js_name = "balanceReport")]
+    pub fn balance_report(
+        &self,
+        intended_transaction_fee: u64,
+    ) -> Result<WasmBalanceReport, JsValue> {
+        Ok(WasmBalanceReport {
+            balances: self.contents()?.asset_balances(intended_transaction_fee),
         })
     }
 
+    /// Checks everything about the transaction that doesn't need proofs:
+    /// balances after `intended_transaction_fee`, duplicate spends, that
+    /// witnesses agree on the tree, expiration against `head_sequence`,
+    /// mint and burn rules for the version, memos and the input and output
+    /// limits. Returns every violation as an `IronfishError`, so an empty
+    /// array means proving should succeed.
+    #[wasm_bindgen]
+    pub fn validate(
+        &self,
+        intended_transaction_fee: u64,
+        head_sequence: Option<u32>,
+        max_inputs: Option<usize>,
+        max_outputs: Option<usize>,
+    ) -> Result<Array, JsValue> {
+        let _entry = panic_hook::entry!();
+        let context = ValidationContext {
+            intended_transaction_fee,
+            head_sequence,
+            max_inputs,
+            max_outputs,
+        };
+        let contents = self.contents()?;
+        Ok(validate(&contents, &context)
+            .into_iter()
+            .map(JsValue::from)
+            .collect())
+    }
+
+    /// Adds an output for every payment in a JSON list; see
+    /// `WasmPaymentBatch` for the format. When any payment is invalid
+    /// nothing is added and the returned batch has an error for each. With
+    /// `max_outputs`, payments past the limit, change included, aren't added
+    /// but returned, to go in further transactions with
+    /// `WasmPaymentBatch.addToTransaction`.
+    #[wasm_bindgen]
+    pub fn add_outputs_from_json(
+        &mut self,
+        json: &str,
+        sender: &str,
+        max_outputs: Option<usize>,
+    ) -> Result<WasmPaymentBatch, JsValue> {
+        let _entry = panic_hook::entry!();
+        let batch = parse_payment_list(json)?;
+        if !batch.is_valid() {
+            return Ok(batch);
+        }
+        batch.add_to_transaction(self, sender, max_outputs)
+    }
+
     #[wasm_bindgen(js_name = "setExpirationSequence")]
-    pub fn set_expiration_sequence(&mut self, expiration_sequence: u32) {
-        self.transaction.set_expiration(expiration_sequence);
+    pub fn set_expiration_sequence(&mut self, expiration_sequence: u32) -> Result<(), JsValue> {
+        let _entry = panic_hook::entry!();
+        self.transaction
+            .get_mut()?
+            .set_expiration(expiration_sequence);
+        self.contents.borrow_mut().expiration = expiration_sequence;
+        Ok(())
     }
 }
+
+impl WasmTransaction {
+    fn post_reporting(
+        &mut self,
+        change_goes_to: Option<String>,
+        intended_transaction_fee: u64,
+        progress: ProgressReporter,
+    ) -> Result<WasmTransactionPosted, JsValue> {
+        let change_key = parse_change_address(change_goes_to)?;
+        self.contents()?.check_balance(intended_transaction_fee)?;
+        let spends = self.contents()?.spends.len();
+        let posted = ProvingJob::run(
+            &mut *self.transaction.get_mut()?,
+            change_key,
+            intended_transaction_fee,
+            spends,
+            &progress,
+        );
+        let posted_transaction = posted.inspect_err(|_| self.transaction.wipe())?;
+        Ok(WasmTransactionPosted {
+            transaction: posted_transaction,
+            network_id: self.network_id,
+        })
+    }
+
+    fn build_circuits_reporting(
+        &mut self,
+        change_goes_to: Option<String>,
+        intended_transaction_fee: u64,
+        progress: ProgressReporter,
+    ) -> Result<TransactionCircuits, JsValue> {
+        let change_key = parse_change_address(change_goes_to)?;
+        self.contents()?.check_balance(intended_transaction_fee)?;
+        let spends = self.contents()?.spends.len();
+        let circuits = build_transaction_circuits(
+            &mut *self.transaction.get_mut()?,
+            change_key,
+            intended_transaction_fee,
+            spends,
+            &progress,
+        );
+        circuits.inspect_err(|_| self.transaction.wipe())
+    }
+
+    /// Outputs so far, with change for every asset in surplus before any
+    /// fee is paid.
+    pub(crate) fn outputs_with_change(&self) -> Result<usize, WasmError> {
+        Ok(self.contents()?.shape(0).outputs)
+    }
+
+    // Readable while an async call has the transaction, as it can't change
+    fn contents(&self) -> Result<Ref<'_, TransactionContents>, WasmError> {
+        if self.transaction.is_wiped() {
+            return Err(WasmError::new(
+                ErrorCode::UseAfterWipe,
+                "transaction has been wiped",
+            ));
+        }
+        Ok(self.contents.borrow())
+    }
+}
+
+pub(crate) fn index_out_of_bounds(index: usize, len: usize) -> WasmError {
+    WasmError::new(
+        ErrorCode::IndexOutOfBounds,
+        format!("index {} out of bounds for length {}", index, len),
+    )
+    .with_argument("index")
+    .with_index(index)
+}
+
+fn parse_change_address(change_goes_to: Option<String>) -> Result<Option<PublicAddress>, JsValue> {
+    match change_goes_to {
+        Some(s) => Ok(Some(parse_public_address(&s, "change_goes_to")?)),
+        None => Ok(None),
+    }
+}
+
+fn post_with_proofs(
+    transaction: &mut ProposedTransaction,
+    spend_proofs: Vec<WasmProof>,
+    output_proofs: Vec<WasmProof>,
+    output_diffie_hellman_keys: Vec<WasmEphemeralKeyPair>,
+    mint_asset_proofs: Vec<WasmProof>,
+    network_id: Option<u32>,
+) -> Result<WasmTransactionPosted, JsValue> {
+    let _span = logger::span("post_wasm");
+    let spend_proofs = spend_proofs
+        .iter()
+        .map(|proof| proof.proof.clone())
+        .collect();
+    let output_proofs = output_proofs
+        .iter()
+        .map(|proof| proof.proof.clone())
+        .collect();
+    let key_pairs = output_diffie_hellman_keys
+        .iter()
+        .enumerate()
+        .map(|(index, key_pair)| {
+            if key_pair.key_pair_bytes.len() != EPHEMERAL_KEY_PAIR_LENGTH {
+                return Err(WasmError::new(
+                    ErrorCode::InvalidArgument,
+                    format!(
+                        "expected {} bytes, got {}",
+                        EPHEMERAL_KEY_PAIR_LENGTH,
+                        key_pair.key_pair_bytes.len()
+                    ),
+                )
+                .with_argument("output_diffie_hellman_keys")
+                .with_index(index));
+            }
+            Ok(EphemeralKeyPair::from_bytes_le(
+                key_pair.key_pair_bytes.clone(),
+            ))
+        })
+        .collect::<Result<_, WasmError>>()?;
+    let mint_asset_proofs = mint_asset_proofs
+        .iter()
+        .map(|proof| proof.proof.clone())
+        .collect();
+    let posted_transaction = transaction
+        .post_wasm(spend_proofs, output_proofs, key_pairs, mint_asset_proofs)
+        .map_err(|e| WasmError::new(ErrorCode::TransactionBuildFailed, e))?;
+
+    Ok(WasmTransactionPosted {
+        transaction: posted_transaction,
+        network_id,
+    })
+}
+
+fn build_transaction_circuits(
+    transaction: &mut ProposedTransaction,
+    change_key: Option<PublicAddress>,
+    intended_transaction_fee: u64,
+    spends: usize,
+    progress: &ProgressReporter,
+) -> Result<TransactionCircuits, JsValue> {
+    let (spend_circuits, output_circuits, output_diffie_hellman_keys, mint_circuits) =
+        build_circuits(
+            transaction,
+            change_key,
+            intended_transaction_fee,
+            spends,
+            progress,
+        )?;
+    let spend_circuits: Vec<Vec<u8>> = spend_circuits
+        .iter()
+        .map(|spend| {
+            let mut spend_bytes = vec![];
+            spend.write(&mut spend_bytes).map_err(WasmIoError)?;
+            Ok(spend_bytes)
+        })
+        .collect::<Result<_, WasmIoError>>()?;
+    let output_circuits: Vec<Vec<u8>> = output_circuits
+        .iter()
+        .map(|output| {
+            let mut output_bytes = vec![];
+            output.write(&mut output_bytes).map_err(WasmIoError)?;
+            Ok(output_bytes)
+        })
+        .collect::<Result<_, WasmIoError>>()?;
+    let output_diffie_hellman_keys: Vec<Vec<u8>> = output_diffie_hellman_keys
+        .iter()
+        .map(|keys| keys.to_bytes_le())
+        .collect();
+    let mint_asset_circuits: Vec<Vec<u8>> = mint_circuits
+        .iter()
+        .map(|mint_asset| {
+            let mut mint_asset_bytes = vec![];
+            mint_asset
+                .write(&mut mint_asset_bytes)
+                .map_err(WasmIoError)?;
+            Ok(mint_asset_bytes)
+        })
+        .collect::<Result<_, WasmIoError>>()?;
+    Ok(TransactionCircuits {
+        spend_circuits,
+        output_circuits,
+        output_diffie_hellman_keys,
+        mint_asset_circuits,
+    })
+}
@@ -0,0 +1,218 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+//! Checks on a proposed transaction that don't need its proofs, so
+//! mistakes show up before any proving time is spent on them.
+
+use std::collections::BTreeMap;
+
+use ironfish_rust::assets::asset_identifier::NATIVE_ASSET;
+
+use super::contents::TransactionContents;
+use super::{ErrorCode, WasmError};
+
+/// Limits and chain state a transaction is validated against.
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct ValidationContext {
+    pub(crate) intended_transaction_fee: u64,
+    /// Sequence of the chain head. Expiration isn't checked without it.
+    pub(crate) head_sequence: Option<u32>,
+    pub(crate) max_inputs: Option<usize>,
+    pub(crate) max_outputs: Option<usize>,
+}
+
+/// Every way in which posting `contents` would fail or build a
+/// transaction the chain rejects, in no particular order.
+pub(crate) fn validate(
+    contents: &TransactionContents,
+    context: &ValidationContext,
+) -> Vec<WasmError> {
+    let mut violations = Vec::new();
+    let fee = context.intended_transaction_fee;
+
+    violations.extend(
+        contents
+            .asset_balances(fee)
+            .iter()
+            .filter(|balance| balance.shortfall() > 0)
+            .map(|balance| balance.shortfall_error()),
+    );
+
+    // All spends are the spender's, so two share a nullifier exactly when
+    // they spend the same note at the same position
+    let mut spent = BTreeMap::new();
+    for (index, spend) in contents.spends.iter().enumerate() {
+        let key = (spend.note.commitment(), spend.position);
+        if let Some(first) = spent.insert(key, index) {
+            violations.push(
+                WasmError::new(
+                    ErrorCode::DuplicateSpend,
+                    format!("spends the same note as spend {}", first),
+                )
+                .with_index(index),
+            );
+        }
+    }
+
+    if let Some(first) = contents.spends.first() {
+        for (index, spend) in contents.spends.iter().enumerate().skip(1) {
+            if spend.root_hash != first.root_hash || spend.tree_size != first.tree_size {
+                violations.push(
+                    WasmError::new(
+                        ErrorCode::WitnessMismatch,
+                        format!(
+                            "witness is for root {} at size {}, spend 0's is for root {} at size {}",
+                            hex::encode(&spend.root_hash),
+                            spend.tree_size,
+                            hex::encode(&first.root_hash),
+                            first.tree_size
+                        ),
+                    )
+                    .with_index(index),
+                );
+            }
+        }
+    }
+
+    // A transaction is expired in blocks at or past its expiration, and the
+    // earliest it can be mined in is the one after the head
+    if let Some(head_sequence) = context.head_sequence {
+        let expiration = contents.expiration;
+        if expiration != 0 && expiration <= head_sequence.saturating_add(1) {
+            violations.push(WasmError::new(
+                ErrorCode::TransactionExpired,
+                format!(
+                    "expires at sequence {}, the next block is {}",
+                    expiration,
+                    head_sequence.saturating_add(1)
+                ),
+            ));
+        }
+    }
+
+    for (index, mint) in contents.mints.iter().enumerate() {
+        if mint.value == 0 {
+            violations.push(
+                WasmError::new(ErrorCode::InvalidMint, "mints nothing")
+                    .with_index(index)
+                    .with_asset_id(mint.asset.id().as_bytes()),
+            );
+        }
+        if mint.transfer_ownership_to.is_some() && contents.version < 2 {
+            violations.push(
+                WasmError::new(
+                    ErrorCode::InvalidMint,
+                    format!(
+                        "transaction version {} can't transfer asset ownership",
+                        contents.version
+                    ),
+                )
+                .with_index(index)
+                .with_asset_id(mint.asset.id().as_bytes()),
+            );
+        }
+    }
+
+    for (index, (asset_id, value)) in contents.burns.iter().enumerate() {
+        if *value == 0 {
+            violations.push(
+                WasmError::new(ErrorCode::InvalidBurn, "burns nothing")
+                    .with_index(index)
+                    .with_asset_id(asset_id),
+            );
+        }
+        if asset_id == NATIVE_ASSET.as_bytes() {
+            violations.push(
+                WasmError::new(ErrorCode::InvalidBurn, "the native asset can't be burned")
+                    .with_index(index)
+                    .with_asset_id(asset_id),
+            );
+        }
+    }
+
+    if let Some(max_inputs) = context.max_inputs {
+        if contents.spends.len() > max_inputs {
+            violations.push(WasmError::new(
+                ErrorCode::InputLimitExceeded,
+                format!(
+                    "spends {} notes, at most {} are allowed",
+                    contents.spends.len(),
+                    max_inputs
+                ),
+            ));
+        }
+    }
+    if let Some(max_outputs) = context.max_outputs {
+        let outputs = contents.shape(fee).outputs;
+        if outputs > max_outputs {
+            violations.push(WasmError::new(
+                ErrorCode::OutputLimitExceeded,
+                format!(
+                    "has {} outputs with change, at most {} are allowed",
+                    outputs, max_outputs
+                ),
+            ));
+        }
+    }
+
+    violations
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ironfish_rust::assets::asset::ID_LENGTH;
+
+    fn codes(contents: &TransactionContents, context: &ValidationContext) -> Vec<ErrorCode> {
+        validate(contents, context)
+            .into_iter()
+            .map(|violation| violation.code)
+            .collect()
+    }
+
+    #[test]
+    fn test_reports_every_violation() {
+        let mut contents = TransactionContents::new(2);
+        contents.expiration = 10;
+        contents.burns.push(([3; ID_LENGTH], 0));
+        contents.burns.push((*NATIVE_ASSET.as_bytes(), 5));
+
+        let context = ValidationContext {
+            head_sequence: Some(9),
+            ..ValidationContext::default()
+        };
+        let mut found = codes(&contents, &context);
+        found.sort_by_key(|code| *code as u32);
+        assert_eq!(
+            found,
+            vec![
+                ErrorCode::InsufficientBalance,
+                ErrorCode::TransactionExpired,
+                ErrorCode::InvalidBurn,
+                ErrorCode::InvalidBurn,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_expiration_and_limits() {
+        let mut contents = TransactionContents::new(2);
+        contents.expiration = 11;
+        let context = ValidationContext {
+            head_sequence: Some(9),
+            max_inputs: Some(0),
+            max_outputs: Some(0),
+            ..ValidationContext::default()
+        };
+        assert!(codes(&contents, &context).is_empty());
+
+        // No expiration never expires
+        contents.expiration = 0;
+        let context = ValidationContext {
+            head_sequence: Some(u32::MAX),
+            ..context
+        };
+        assert!(codes(&contents, &context).is_empty());
+    }
+}
@@ -0,0 +1,277 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+use ironfish_rust::{IncomingViewKey, OutgoingViewKey, ViewKey};
+use js_sys::Promise;
+use wasm_bindgen::prelude::*;
+use web_sys::AbortSignal;
+
+use super::note_encrypted::{decrypt_notes, decrypt_notes_async};
+use super::tasks::to_promise;
+use super::{
+    panic_hook, ErrorCode, ProgressCallback, WasmError, WasmNote, WasmNoteEncrypted,
+    WasmSpendingKey,
+};
+
+const CRH_IVK_PERSONALIZATION: &[u8; 8] = b"Zcashivk";
+
+/// The keys of an account without its spending key, for auditors and
+/// watch-only wallets.
+///
+/// Built from the full view key, the incoming view key is derived and the
+/// key can scan for received notes and compute their nullifiers. Built from
+/// the incoming view key alone it can scan but not compute nullifiers. The
+/// outgoing view key cannot be derived from either and must be passed in to
+/// decrypt sent notes.
+///
+/// Transactions cannot be built from a view-only key: every spend, output
+/// and mint circuit in this protocol version commits to the spender's proof
+/// generation key, which only the spending key holds.
+#[wasm_bindgen]
+pub struct ViewOnlyKey {
+    view_key: Option<ViewKey>,
+    incoming_view_key: IncomingViewKey,
+    outgoing_view_key: Option<OutgoingViewKey>,
+}
+
+#[wasm_bindgen]
+impl ViewOnlyKey {
+    #[wasm_bindgen(js_name = "fromViewKey")]
+    pub fn from_view_key(
+        view_key: &str,
+        outgoing_view_key: Option<String>,
+    ) -> Result<ViewOnlyKey, JsValue> {
+        let _entry = panic_hook::entry!();
+
+        let incoming_view_key = derive_incoming_view_key(view_key)?;
+        let view_key = ViewKey::from_hex(view_key)
+            .map_err(|e| WasmError::new(ErrorCode::InvalidViewKey, e).with_argument("view_key"))?;
+        Ok(ViewOnlyKey {
+            view_key: Some(view_key),
+            incoming_view_key,
+            outgoing_view_key: parse_outgoing_view_key(outgoing_view_key)?,
+        })
+    }
+
+    #[wasm_bindgen(js_name = "fromIncomingViewKey")]
+    pub fn from_incoming_view_key(
+        incoming_view_key: &str,
+        outgoing_view_key: Option<String>,
+    ) -> Result<ViewOnlyKey, JsValue> {
+        let _entry = panic_hook::entry!();
+
+        let incoming_view_key = IncomingViewKey::from_hex(incoming_view_key).map_err(|e| {
+            WasmError::new(ErrorCode::InvalidViewKey, e).with_argument("incoming_view_key")
+        })?;
+        Ok(ViewOnlyKey {
+            view_key: None,
+            incoming_view_key,
+            outgoing_view_key: parse_outgoing_view_key(outgoing_view_key)?,
+        })
+    }
+
+    /// Strips a spending key down to its view keys.
+    #[wasm_bindgen(js_name = "fromSpendingKey")]
+    pub fn from_spending_key(spending_key: &WasmSpendingKey) -> Result<ViewOnlyKey, JsValue> {
+        let _entry = panic_hook::entry!();
+
+        let key = spending_key.sapling_key()?;
+        Ok(ViewOnlyKey {
+            view_key: Some(key.view_key().clone()),
+            incoming_view_key: key.incoming_view_key().clone(),
+            outgoing_view_key: Some(key.outgoing_view_key().clone()),
+        })
+    }
+
+    #[wasm_bindgen(getter, js_name = "viewKey")]
+    pub fn view_key(&self) -> Option<String> {
+        self.view_key.as_ref().map(ViewKey::hex_key)
+    }
+
+    #[wasm_bindgen(getter, js_name = "incomingViewKey")]
+    pub fn incoming_view_key(&self) -> String {
+        self.incoming_view_key.hex_key()
+    }
+
+    #[wasm_bindgen(getter, js_name = "outgoingViewKey")]
+    pub fn outgoing_view_key(&self) -> Option<String> {
+        self.outgoing_view_key
+            .as_ref()
+            .map(OutgoingViewKey::hex_key)
+    }
+
+    #[wasm_bindgen(getter, js_name = "publicAddress")]
+    pub fn public_address(&self) -> String {
+        let _entry = panic_hook::entry!();
+        self.incoming_view_key.public_address().hex_public_address()
+    }
+
+    /// Returns undefined if the note was not sent to this account.
+    #[wasm_bindgen(js_name = "decryptNoteForOwner")]
+    pub fn decrypt_note_for_owner(&self, note: &WasmNoteEncrypted) -> Option<WasmNote> {
+        let _entry = panic_hook::entry!();
+
+        note.note
+            .decrypt_note_for_owner(&self.incoming_view_key)
+            .ok()
+            .map(|note| WasmNote { note })
+    }
+
+    /// Returns undefined if the note was not sent by this account. Throws if
+    /// the key was built without an outgoing view key.
+    #[wasm_bindgen(js_name = "decryptNoteForSpender")]
+    pub fn decrypt_note_for_spender(
+        &self,
+        note: &WasmNoteEncrypted,
+    ) -> Result<Option<WasmNote>, JsValue> {
+        let _entry = panic_hook::entry!();
+
+        let outgoing_view_key = self.outgoing_view_key.as_ref().ok_or_else(|| {
+            WasmError::new(ErrorCode::InvalidViewKey, "key has no outgoing view key")
+        })?;
+        Ok(note
+            .note
+            .decrypt_note_for_spender(outgoing_view_key)
+            .ok()
+            .map(|note| WasmNote { note }))
+    }
+
+    /// Same as the free function `decryptNotesForOwner`, with this key.
+    #[wasm_bindgen(js_name = "decryptNotesForOwner")]
+    pub fn decrypt_notes_for_owner(
+        &self,
+        serialized_notes: js_sys::Array,
+        progress: Option<ProgressCallback>,
+    ) -> Result<js_sys::Array, JsValue> {
+        let _entry = panic_hook::entry!();
+
+        decrypt_notes(&serialized_notes, &self.incoming_view_key, progress)
+    }
+
+    /// Same as the free function `decryptNotesForOwnerAsync`, with this key.
+    /// Resolves to the array of decrypted notes.
+    #[wasm_bindgen(js_name = "decryptNotesForOwnerAsync")]
+    pub fn decrypt_notes_for_owner_async(
+        &self,
+        serialized_notes: js_sys::Array,
+        signal: Option<AbortSignal>,
+        progress: Option<ProgressCallback>,
+    ) -> Promise {
+        let incoming_view_key = self.incoming_view_key.clone();
+        to_promise(panic_hook::function_name!(), async move {
+            let decrypted =
+                decrypt_notes_async(&serialized_notes, &incoming_view_key, signal, progress)
+                    .await?;
+            Ok(decrypted.into())
+        })
+    }
+
+    /// The nullifier `note` will reveal when spent from `position` in the
+    /// note commitment tree. Throws if the key was built without the full
+    /// view key.
+    #[wasm_bindgen]
+    pub fn nullifier(&self, note: &WasmNote, position: u64) -> Result<Vec<u8>, JsValue> {
+        let _entry = panic_hook::entry!();
+
+        let view_key = self
+            .view_key
+            .as_ref()
+            .ok_or_else(|| WasmError::new(ErrorCode::InvalidViewKey, "key has no view key"))?;
+        Ok(note.note.nullifier(view_key, position).to_vec())
+    }
+}
+
+impl ViewOnlyKey {
+    pub(crate) fn from_hex_keys(
+        view_key: &str,
+        incoming_view_key: &str,
+        outgoing_view_key: &str,
+    ) -> Result<ViewOnlyKey, WasmError> {
+        let key = ViewOnlyKey {
+            view_key: Some(ViewKey::from_hex(view_key).map_err(|e| {
+                WasmError::new(ErrorCode::InvalidViewKey, e).with_argument("viewKey")
+            })?),
+            incoming_view_key: IncomingViewKey::from_hex(incoming_view_key).map_err(|e| {
+                WasmError::new(ErrorCode::InvalidViewKey, e).with_argument("incomingViewKey")
+            })?,
+            outgoing_view_key: parse_outgoing_view_key(Some(outgoing_view_key.to_string()))?,
+        };
+        if derive_incoming_view_key(view_key)?.hex_key() != key.incoming_view_key.hex_key() {
+            return Err(WasmError::new(
+                ErrorCode::InvalidViewKey,
+                "incoming view key does not belong to the view key",
+            )
+            .with_argument("incomingViewKey"));
+        }
+        Ok(key)
+    }
+}
+
+fn parse_outgoing_view_key(
+    outgoing_view_key: Option<String>,
+) -> Result<Option<OutgoingViewKey>, WasmError> {
+    outgoing_view_key
+        .map(|hex| {
+            OutgoingViewKey::from_hex(&hex).map_err(|e| {
+                WasmError::new(ErrorCode::InvalidViewKey, e).with_argument("outgoing_view_key")
+            })
+        })
+        .transpose()
+}
+
+/// The incoming view key is CRH_ivk(ak || nk), the same derivation the
+/// spending key uses, truncated to 251 bits so it is a valid scalar.
+pub(crate) fn incoming_view_key_bytes(view_key: &[u8; 64]) -> Option<[u8; 32]> {
+    let hash = blake2s_simd::Params::new()
+        .hash_length(32)
+        .personal(CRH_IVK_PERSONALIZATION)
+        .hash(view_key);
+    let mut bytes = [0u8; 32];
+    bytes.copy_from_slice(hash.as_bytes());
+    bytes[31] &= 0b0000_0111;
+    if bytes == [0u8; 32] {
+        return None;
+    }
+    Some(bytes)
+}
+
+fn derive_incoming_view_key(view_key: &str) -> Result<IncomingViewKey, WasmError> {
+    let invalid = || {
+        WasmError::new(ErrorCode::InvalidViewKey, "expected a 64 byte hex view key")
+            .with_argument("view_key")
+    };
+    let bytes: [u8; 64] = hex::decode(view_key)
+        .ok()
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(invalid)?;
+    let incoming_view_key = incoming_view_key_bytes(&bytes).ok_or_else(invalid)?;
+    IncomingViewKey::from_hex(&hex::encode(incoming_view_key))
+        .map_err(|e| WasmError::new(ErrorCode::InvalidViewKey, e).with_argument("view_key"))
+}
+
+#[cfg(test)]
+mod tests {
+    use ironfish_rust::SaplingKey;
+
+    use super::*;
+
+    #[test]
+    fn test_derives_incoming_view_key_from_view_key() {
+        let key = SaplingKey::generate_key();
+        let derived = derive_incoming_view_key(&key.view_key().hex_key()).unwrap();
+        assert_eq!(derived.hex_key(), key.incoming_view_key().hex_key());
+        assert_eq!(
+            derived.public_address().hex_public_address(),
+            key.public_address().hex_public_address()
+        );
+    }
+
+    #[test]
+    fn test_incoming_view_key_is_a_scalar() {
+        for seed in 0..=255u8 {
+            let bytes = incoming_view_key_bytes(&[seed; 64]).unwrap();
+            assert_eq!(bytes[31] & 0b1111_1000, 0);
+        }
+    }
+}
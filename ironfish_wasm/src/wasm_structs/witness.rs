@@ -9,7 +9,7 @@ use wasm_bindgen::JsCast;
 use ironfish_rust::sapling_bls12::Scalar;
 use ironfish_rust::witness::{WitnessNode, WitnessTrait};
 
-use super::panic_hook;
+use super::{panic_hook, ErrorCode, WasmError};
 
 #[wasm_bindgen(typescript_custom_section)]
 const IWITNESS: &'static str = r#"
@@ -65,35 +65,21 @@ extern "C" {
     pub fn hash_of_sibling(this: &JsWitnessNode) -> Vec<u8>;
 }
 
-/// wrapper object
+/// wrapper object. The auth path and root hash are read from the JS object
+/// up front, since WitnessTrait has no way to report malformed hashes.
 pub struct JsWitness1 {
     pub obj: JsWitness,
+    auth_path: Vec<WitnessNode<Scalar>>,
+    root_hash: Scalar,
 }
 
-/// Implements WitnessTrait on JsWitness so that witnesses from the
-/// TypeScript side can be passed into classes that require witnesses,
-/// like transactions.
-impl WitnessTrait for JsWitness1 {
-    fn verify(&self, hash: &MerkleNoteHash) -> bool {
-        panic_hook::set_once();
-
-        let mut cursor: std::io::Cursor<Vec<u8>> = std::io::Cursor::new(vec![]);
-        hash.write(&mut cursor).unwrap();
-
-        // let obj: JsWitness  = serde_wasm_bindgen::from_value(self.obj).unwrap();
-
-        self.obj.verify(&cursor.into_inner())
-    }
-
-    fn get_auth_path(&self) -> Vec<WitnessNode<Scalar>> {
-        panic_hook::set_once();
-
-        // let obj: JsWitness  = serde_wasm_bindgen::from_value(self.obj).unwrap();
-
-        self.obj
+impl JsWitness1 {
+    pub fn new(obj: JsWitness) -> Result<JsWitness1, WasmError> {
+        let auth_path = obj
             .auth_path()
             .iter()
-            .map(|element| {
+            .enumerate()
+            .map(|(index, element)| {
                 // Unchecked cast here so that wasm-bindgen allows duck-typed objects
                 // rather than asserting that the object is an instance of JsWitnessNode
                 let cast = element.unchecked_into::<JsWitnessNode>();
@@ -102,25 +88,72 @@ impl WitnessTrait for JsWitness1 {
                 // back into a MerkleNoteHash
                 let bytes = cast.hash_of_sibling();
                 let mut cursor = std::io::Cursor::new(&bytes);
-                let fr = MerkleNoteHash::read(&mut cursor).unwrap().0;
-
-                if cast.side() == "Left" {
+                let fr = MerkleNoteHash::read(&mut cursor)
+                    .map_err(|e| {
+                        WasmError::new(ErrorCode::MalformedWitness, e)
+                            .with_argument("witness")
+                            .with_index(index)
+                    })?
+                    .0;
+
+                Ok(if cast.side() == "Left" {
                     WitnessNode::Left(fr)
                 } else {
                     WitnessNode::Right(fr)
-                }
+                })
+            })
+            .collect::<Result<Vec<_>, WasmError>>()?;
+
+        // Convert the serialized root hash back to a Scalar
+        let bytes = obj.serialize_root_hash();
+        let mut cursor: std::io::Cursor<&[u8]> = std::io::Cursor::new(&bytes);
+        let root_hash = MerkleNoteHash::read(&mut cursor)
+            .map_err(|e| WasmError::new(ErrorCode::MalformedWitness, e).with_argument("witness"))?
+            .0;
+
+        Ok(JsWitness1 {
+            obj,
+            auth_path,
+            root_hash,
+        })
+    }
+
+    /// Position of the witnessed note in the tree. Each node of the auth
+    /// path is one bit, set where the path comes from a right child.
+    pub(crate) fn position(&self) -> u64 {
+        self.auth_path
+            .iter()
+            .enumerate()
+            .fold(0, |position, (depth, node)| match node {
+                WitnessNode::Left(_) => position,
+                WitnessNode::Right(_) => position | 1 << depth,
             })
-            .collect()
     }
+}
 
-    fn root_hash(&self) -> Scalar {
+/// Implements WitnessTrait on JsWitness so that witnesses from the
+/// TypeScript side can be passed into classes that require witnesses,
+/// like transactions.
+impl WitnessTrait for JsWitness1 {
+    fn verify(&self, hash: &MerkleNoteHash) -> bool {
         panic_hook::set_once();
 
-        // Convert the serialized root hash back to a Scalar
+        let mut cursor: std::io::Cursor<Vec<u8>> = std::io::Cursor::new(vec![]);
+        if hash.write(&mut cursor).is_err() {
+            return false;
+        }
+
         // let obj: JsWitness  = serde_wasm_bindgen::from_value(self.obj).unwrap();
-        let bytes = self.obj.serialize_root_hash();
-        let mut cursor: std::io::Cursor<&[u8]> = std::io::Cursor::new(&bytes);
-        MerkleNoteHash::read(&mut cursor).unwrap().0
+
+        self.obj.verify(&cursor.into_inner())
+    }
+
+    fn get_auth_path(&self) -> Vec<WitnessNode<Scalar>> {
+        self.auth_path.clone()
+    }
+
+    fn root_hash(&self) -> Scalar {
+        self.root_hash
     }
 
     fn tree_size(&self) -> u32 {